@@ -371,9 +371,11 @@ export function MixComplete() {
 
 function TrackItem({ track, index, isLast }: { track: MixTrack; index: number; isLast: boolean }) {
   const transitionLabels: Record<string, string> = {
+    cut: "Cut",
     crossfade: "Crossfade",
     echo_out: "Echo Out",
     filter_sweep: "Filter Sweep",
+    beatmatch: "Beatmatch",
     backspin: "Backspin",
   };
   
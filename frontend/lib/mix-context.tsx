@@ -200,9 +200,22 @@ export function MixProvider({ children }: { children: React.ReactNode }) {
     setProgress({ stage: "idle", progress: 0, detail: "" });
   }, []);
   
-  // SSE fallback - defined before generateMix since it's used there
-  const fallbackToSSE = useCallback((sessionId: string, playlist: MixTrack[], targetBpm: number) => {
-    const eventSource = new EventSource(`${BACKEND_URL}/sse/mix/${sessionId}`);
+  // Progress streams need a token scoped to the session
+  const fetchStreamToken = async (sessionId: string): Promise<string> => {
+    const response = await fetch(`${BACKEND_URL}/mix/${sessionId}/stream-token`, { method: "POST" });
+    if (!response.ok) {
+      throw new Error("Not allowed to follow this mix's progress");
+    }
+    const data = await response.json();
+    return data.token;
+  };
+  
+  // SSE fallback - defined before generateMix since it's used there.
+  // EventSource can't send headers, so the token goes in the query string.
+  const fallbackToSSE = useCallback((sessionId: string, streamToken: string, playlist: MixTrack[], targetBpm: number) => {
+    const eventSource = new EventSource(
+      `${BACKEND_URL}/sse/mix/${sessionId}?token=${encodeURIComponent(streamToken)}`
+    );
     
     eventSource.onmessage = (event) => {
       try {
@@ -277,7 +290,7 @@ export function MixProvider({ children }: { children: React.ReactNode }) {
       
       if (!response.ok) {
         const errorData = await response.json();
-        throw new Error(errorData.error || "Failed to start mix generation");
+        throw new Error(errorData.error?.message || "Failed to start mix generation");
       }
       
       const data = await response.json();
@@ -287,9 +300,11 @@ export function MixProvider({ children }: { children: React.ReactNode }) {
       const playlist = data.playlist as MixTrack[];
       const targetBpm = data.target_bpm;
       
-      // Connect to WebSocket for progress updates
+      // Connect to WebSocket for progress updates, passing the token as the
+      // "bearer" subprotocol since browsers can't set headers on WebSockets
+      const streamToken = await fetchStreamToken(sessionId);
       const wsUrl = `${BACKEND_URL.replace("http", "ws")}/ws/mix/${sessionId}`;
-      const ws = new WebSocket(wsUrl);
+      const ws = new WebSocket(wsUrl, ["bearer", streamToken]);
       wsRef.current = ws;
       
       ws.onopen = () => {
@@ -397,7 +412,7 @@ export function MixProvider({ children }: { children: React.ReactNode }) {
       ws.onerror = (e) => {
         console.error("WebSocket error:", e);
         // Try SSE fallback
-        fallbackToSSE(sessionId, playlist, targetBpm);
+        fallbackToSSE(sessionId, streamToken, playlist, targetBpm);
       };
       
       ws.onclose = () => {
@@ -454,7 +469,7 @@ export function MixProvider({ children }: { children: React.ReactNode }) {
   
   const listMixes = useCallback(async (): Promise<MixResult[]> => {
     try {
-      const response = await fetch(`${BACKEND_URL}/api/mixes`);
+      const response = await fetch(`${BACKEND_URL}/api/mixes?limit=50`);
       if (!response.ok) {
         throw new Error("Failed to list mixes");
       }
@@ -15,6 +15,7 @@ from fastapi.middleware.cors import CORSMiddleware
 from pydantic import BaseModel
 from pydantic_settings import BaseSettings
 
+from narration import NarrationTransition, NarrationWriter
 from playlist_generator import PlaylistGenerator, PlaylistTrack
 
 
@@ -23,6 +24,10 @@ class Settings(BaseSettings):
     spotify_token: str = ""
     audio_processor_url: str = "http://audio-processor:8001"
     backend_url: str = "http://backend:8000"
+    # Used for narration; without it scripts come from templates
+    openai_api_key: str = ""
+    # Namespaces channels when environments share a Redis; must match the backend's REDIS_PREFIX
+    redis_prefix: str = ""
     
     class Config:
         env_file = ".env"
@@ -34,6 +39,12 @@ settings = Settings()
 redis_client: redis.Redis | None = None
 
 
+def mix_channel(session_id: str, kind: str) -> str:
+    """Redis channel for a session's `progress`/`complete`/`error` events"""
+    channel = f"mix:{session_id}:{kind}"
+    return f"{settings.redis_prefix}:{channel}" if settings.redis_prefix else channel
+
+
 @asynccontextmanager
 async def lifespan(app: FastAPI):
     """Startup and shutdown events"""
@@ -109,7 +120,7 @@ async def publish_progress(session_id: str, stage: str, progress: int, detail: s
     """Publish progress update to Redis"""
     try:
         if redis_client:
-            channel = f"mix:{session_id}:progress"
+            channel = mix_channel(session_id, "progress")
             message = f'{{"stage": "{stage}", "progress": {progress}, "detail": "{detail}"}}'
             await redis_client.publish(channel, message)
             print(f"Published progress to Redis channel {channel}: {message}")
@@ -257,7 +268,7 @@ async def generate_mix(request: GenerateMixRequest):
     except Exception as e:
         if redis_client:
             await redis_client.publish(
-                f"mix:{session_id}:error",
+                mix_channel(session_id, "error"),
                 f'{{"error": "{str(e)}"}}'
             )
         raise HTTPException(status_code=500, detail=str(e))
@@ -308,11 +319,36 @@ async def trigger_audio_processor(
     except Exception as e:
         if redis_client:
             await redis_client.publish(
-                f"mix:{session_id}:error",
+                mix_channel(session_id, "error"),
                 f'{{"error": "{str(e)}"}}'
             )
 
 
+class NarrationRequest(BaseModel):
+    prompt: str = ""
+    transitions: list[NarrationTransition]
+
+
+class NarrationResponse(BaseModel):
+    scripts: list[str]
+
+
+@app.post("/narration", response_model=NarrationResponse)
+async def write_narration(
+    request: NarrationRequest,
+    x_openai_key: Optional[str] = Header(default=None),
+):
+    """
+    Write a short DJ voiceover line for each transition, in order
+    """
+    try:
+        writer = NarrationWriter(x_openai_key or settings.openai_api_key)
+        scripts = await writer.write(request.prompt, request.transitions)
+        return NarrationResponse(scripts=scripts)
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
 @app.get("/trends")
 async def get_trends():
     """
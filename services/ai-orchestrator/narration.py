@@ -0,0 +1,80 @@
+"""
+Narration Writer - short DJ voiceover lines for mix transitions
+"""
+
+import json
+from typing import Optional
+
+from openai import AsyncOpenAI
+from pydantic import BaseModel
+
+
+class NarrationTrack(BaseModel):
+    title: str
+    artist: str
+    key: str = ""
+    energy: float = 0.5
+
+
+class NarrationTransition(BaseModel):
+    from_track: NarrationTrack
+    to_track: NarrationTrack
+    type: str = "crossfade"
+    bars: int = 8
+    direction: Optional[str] = None
+
+
+class NarrationWriter:
+    """
+    Writes one spoken line per transition with GPT, or from templates when
+    no OpenAI key is configured
+    """
+
+    def __init__(self, api_key: str = ""):
+        self.client = AsyncOpenAI(api_key=api_key) if api_key else None
+        self.model = "gpt-4o"
+
+    async def write(self, prompt: str, transitions: list[NarrationTransition]) -> list[str]:
+        if not self.client:
+            return [self._template(t) for t in transitions]
+
+        system_prompt = """You are a radio DJ writing short voiceover lines spoken over mix transitions.
+For each transition write ONE line (max 25 words) that names the incoming track and artist
+and matches the transition's energy: hype for builds, mellow for cooldowns.
+
+Return a JSON object: {"scripts": ["...", "..."]} with exactly one script per transition, in order."""
+
+        transition_info = "\n".join([
+            f"{i + 1}. {t.from_track.artist} - {t.from_track.title} (energy {t.from_track.energy:.2f}) "
+            f"-> {t.to_track.artist} - {t.to_track.title} (energy {t.to_track.energy:.2f}) via {t.type}"
+            for i, t in enumerate(transitions)
+        ])
+        user_prompt = f"Mix theme: {prompt}\n\nTransitions:\n{transition_info}"
+
+        response = await self.client.chat.completions.create(
+            model=self.model,
+            messages=[
+                {"role": "system", "content": system_prompt},
+                {"role": "user", "content": user_prompt}
+            ],
+            response_format={"type": "json_object"},
+            temperature=0.8,
+        )
+
+        scripts = json.loads(response.choices[0].message.content).get("scripts", [])
+        # Fill any lines the model dropped so scripts still line up with transitions
+        return [
+            scripts[i] if i < len(scripts) and isinstance(scripts[i], str) and scripts[i].strip()
+            else self._template(t)
+            for i, t in enumerate(transitions)
+        ]
+
+    @staticmethod
+    def _template(transition: NarrationTransition) -> str:
+        incoming = f"{transition.to_track.title} by {transition.to_track.artist}"
+        change = transition.to_track.energy - transition.from_track.energy
+        if change > 0.2:
+            return f"Turning it up now, here comes {incoming}!"
+        if change < -0.2:
+            return f"Let's bring it down a little with {incoming}."
+        return f"Keeping the vibe going, this is {incoming}."
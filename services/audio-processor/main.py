@@ -28,6 +28,8 @@ class Settings(BaseSettings):
     temp_audio_dir: str = "/tmp/audio"
     cdn_api_url: str = "https://api.cdn.tobiolajide.com"
     cdn_app_name: str = "ai-dj"
+    # Namespaces channels when environments share a Redis; must match the backend's REDIS_PREFIX
+    redis_prefix: str = ""
     
     class Config:
         env_file = ".env"
@@ -40,6 +42,12 @@ settings = Settings()
 redis_client: redis.Redis | None = None
 
 
+def mix_channel(session_id: str, kind: str) -> str:
+    """Redis channel for a session's `progress`/`complete`/`error` events"""
+    channel = f"mix:{session_id}:{kind}"
+    return f"{settings.redis_prefix}:{channel}" if settings.redis_prefix else channel
+
+
 @asynccontextmanager
 async def lifespan(app: FastAPI):
     """Startup and shutdown events"""
@@ -184,7 +192,7 @@ async def publish_progress(session_id: str, stage: str, progress: int, detail: s
             "source": source,
             "current_track": current_track
         })
-        await redis_client.publish(f"mix:{session_id}:progress", message)
+        await redis_client.publish(mix_channel(session_id, "progress"), message)
 
 
 @app.get("/health")
@@ -434,7 +442,7 @@ async def process_full_mix(
         if redis_client:
             import json
             await redis_client.publish(
-                f"mix:{session_id}:complete",
+                mix_channel(session_id, "complete"),
                 json.dumps({"cdn_url": cdn_url})
             )
         
@@ -449,7 +457,7 @@ async def process_full_mix(
         if redis_client:
             import json
             await redis_client.publish(
-                f"mix:{session_id}:error",
+                mix_channel(session_id, "error"),
                 json.dumps({"error": str(e)})
             )
         raise HTTPException(status_code=500, detail=str(e))
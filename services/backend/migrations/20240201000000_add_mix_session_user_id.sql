@@ -0,0 +1,4 @@
+-- Owner of a mix session (JWT subject); NULL for anonymous sessions
+ALTER TABLE dj_mix_sessions ADD COLUMN IF NOT EXISTS user_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_dj_mix_sessions_user_id ON dj_mix_sessions(user_id);
@@ -0,0 +1,3 @@
+-- Serves "my mixes with status X, newest first" from a single index scan
+CREATE INDEX IF NOT EXISTS idx_dj_mix_sessions_user_status_created
+    ON dj_mix_sessions(user_id, status, created_at DESC);
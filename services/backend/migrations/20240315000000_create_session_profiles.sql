@@ -0,0 +1,10 @@
+-- Listener preferences captured during onboarding, keyed by client session id
+CREATE TABLE IF NOT EXISTS dj_session_profiles (
+    session_id TEXT PRIMARY KEY,
+    mood TEXT NOT NULL,
+    genres TEXT[] NOT NULL DEFAULT '{}',
+    energy_level DOUBLE PRECISION NOT NULL,
+    explicit_ok BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,11 @@
+-- Listener ratings of generated mixes, used to tune transition suggestions
+CREATE TABLE IF NOT EXISTS dj_mix_feedback (
+    id UUID PRIMARY KEY,
+    mix_session_id UUID NOT NULL REFERENCES dj_mix_sessions(id) ON DELETE CASCADE,
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    liked_transitions UUID[] NOT NULL DEFAULT '{}',
+    skipped_tracks UUID[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_dj_mix_feedback_session_id ON dj_mix_feedback(mix_session_id);
@@ -0,0 +1,3 @@
+-- Regenerated mixes point back at the session they were derived from
+ALTER TABLE dj_mix_sessions
+    ADD COLUMN IF NOT EXISTS parent_session_id UUID REFERENCES dj_mix_sessions(id) ON DELETE SET NULL;
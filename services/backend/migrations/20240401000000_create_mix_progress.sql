@@ -0,0 +1,16 @@
+-- Durable log of every progress message published for a mix, so the
+-- timeline survives Redis restarts. No foreign key: the orchestrator
+-- publishes progress before the session row is created.
+CREATE TABLE IF NOT EXISTS dj_mix_progress (
+    id UUID PRIMARY KEY,
+    session_id UUID NOT NULL,
+    seq BIGINT NOT NULL,
+    stage VARCHAR(50) NOT NULL,
+    percent INTEGER,
+    message TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_dj_mix_progress_session_created ON dj_mix_progress(session_id, created_at);
+-- Every replica's recorder sees each message; only the first write sticks
+CREATE UNIQUE INDEX IF NOT EXISTS idx_dj_mix_progress_session_seq ON dj_mix_progress(session_id, seq);
@@ -0,0 +1,3 @@
+-- Lets the stale-session sweeper find old "generating" sessions without a scan
+CREATE INDEX IF NOT EXISTS idx_dj_mix_sessions_status_created
+    ON dj_mix_sessions(status, created_at);
@@ -0,0 +1,10 @@
+-- DJ voiceover scripts, one per transition, generated on request
+CREATE TABLE IF NOT EXISTS dj_mix_narration (
+    id UUID PRIMARY KEY,
+    mix_session_id UUID NOT NULL REFERENCES dj_mix_sessions(id) ON DELETE CASCADE,
+    from_track_order INTEGER NOT NULL,
+    to_track_order INTEGER NOT NULL,
+    script TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (mix_session_id, from_track_order)
+);
@@ -0,0 +1,155 @@
+// Aggregate audio features describing a set of tracks
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::camelot;
+
+/// Most tracks summarized in one request
+pub const MAX_AUDIO_PROFILE_TRACKS: usize = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct AudioProfileRequest {
+    pub spotify_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FeatureStats {
+    pub mean: f64,
+    pub median: f64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct BpmRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Summary of a playlist's character, e.g. for the DJ's narration. Only
+/// tracks Spotify has analysed count towards the stats.
+#[derive(Debug, Serialize)]
+pub struct AudioProfile {
+    pub track_count: usize,
+    pub analysed_count: usize,
+    /// Tracks left out because Spotify has no audio features for them
+    pub missing_features: Vec<String>,
+    pub tempo: Option<FeatureStats>,
+    pub energy: Option<FeatureStats>,
+    pub valence: Option<FeatureStats>,
+    pub danceability: Option<FeatureStats>,
+    pub bpm_range: Option<BpmRange>,
+    /// Most common Camelot key; ties go to the key heard first
+    pub dominant_key: Option<String>,
+}
+
+fn stats(mut values: Vec<f64>) -> Option<FeatureStats> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    let median = if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    };
+    Some(FeatureStats {
+        mean: values.iter().sum::<f64>() / values.len() as f64,
+        median,
+    })
+}
+
+/// Aggregate the audio features of `spotify_ids`, looked up in `features`
+/// (Spotify `audio_features` objects keyed by track id)
+pub fn build_profile(spotify_ids: &[String], features: &HashMap<String, serde_json::Value>) -> AudioProfile {
+    let (analysed, missing): (Vec<&String>, Vec<&String>) =
+        spotify_ids.iter().partition(|id| features.contains_key(*id));
+    let analysed: Vec<&serde_json::Value> = analysed.iter().map(|id| &features[*id]).collect();
+    let values = |name: &str| -> Vec<f64> { analysed.iter().filter_map(|f| f[name].as_f64()).collect() };
+
+    let tempos = values("tempo");
+    let bpm_range = tempos
+        .iter()
+        .copied()
+        .fold(None, |range: Option<BpmRange>, tempo| {
+            Some(match range {
+                Some(r) => BpmRange { min: r.min.min(tempo), max: r.max.max(tempo) },
+                None => BpmRange { min: tempo, max: tempo },
+            })
+        });
+
+    let mut key_counts: Vec<(String, usize)> = Vec::new();
+    for feature in &analysed {
+        let Some(key) = camelot::to_camelot(
+            feature["key"].as_i64().unwrap_or(-1) as i32,
+            feature["mode"].as_i64().unwrap_or(-1) as i32,
+        ) else {
+            continue;
+        };
+        match key_counts.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, count)) => *count += 1,
+            None => key_counts.push((key, 1)),
+        }
+    }
+    // max_by_key keeps the last maximum, so walk backwards to favour the first
+    let dominant_key = key_counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(key, _)| key);
+
+    AudioProfile {
+        track_count: spotify_ids.len(),
+        analysed_count: analysed.len(),
+        missing_features: missing.into_iter().cloned().collect(),
+        tempo: stats(tempos),
+        energy: stats(values("energy")),
+        valence: stats(values("valence")),
+        danceability: stats(values("danceability")),
+        bpm_range,
+        dominant_key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feature(tempo: f64, energy: f64, key: i64, mode: i64) -> serde_json::Value {
+        serde_json::json!({
+            "tempo": tempo, "energy": energy, "valence": 0.5, "danceability": 0.7,
+            "key": key, "mode": mode
+        })
+    }
+
+    #[test]
+    fn aggregates_analysed_tracks_and_reports_missing_ones() {
+        let ids: Vec<String> = ["a", "b", "c", "d"].iter().map(|s| s.to_string()).collect();
+        let features = HashMap::from([
+            ("a".to_string(), feature(120.0, 0.4, 9, 0)), // 8A
+            ("b".to_string(), feature(128.0, 0.8, 0, 1)), // 8B
+            ("c".to_string(), feature(124.0, 0.6, 9, 0)), // 8A
+        ]);
+
+        let profile = build_profile(&ids, &features);
+        assert_eq!(profile.track_count, 4);
+        assert_eq!(profile.analysed_count, 3);
+        assert_eq!(profile.missing_features, ["d"]);
+        assert_eq!(profile.tempo, Some(FeatureStats { mean: 124.0, median: 124.0 }));
+        assert_eq!(profile.bpm_range, Some(BpmRange { min: 120.0, max: 128.0 }));
+        assert_eq!(profile.dominant_key.as_deref(), Some("8A"));
+        let energy = profile.energy.unwrap();
+        assert!((energy.mean - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_profile_has_no_stats() {
+        let profile = build_profile(&["x".to_string()], &HashMap::new());
+        assert_eq!(profile.analysed_count, 0);
+        assert!(profile.tempo.is_none() && profile.bpm_range.is_none() && profile.dominant_key.is_none());
+    }
+
+    #[test]
+    fn median_of_even_count_averages_the_middle() {
+        assert_eq!(stats(vec![4.0, 1.0, 3.0, 2.0]).unwrap().median, 2.5);
+    }
+}
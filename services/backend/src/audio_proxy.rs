@@ -0,0 +1,182 @@
+// Streaming proxy for upstream audio
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+
+use crate::error::ApiError;
+
+/// Request headers forwarded upstream so the player can seek
+const FORWARDED_REQUEST_HEADERS: [header::HeaderName; 2] = [header::RANGE, header::IF_RANGE];
+
+/// Upstream response headers relayed to the client
+const RELAYED_RESPONSE_HEADERS: [header::HeaderName; 6] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::ACCEPT_RANGES,
+    header::ETAG,
+    header::LAST_MODIFIED,
+];
+
+/// Whether an `If-Range` validator still matches the upstream representation.
+/// ETags use strong comparison (weak tags never match); dates must equal
+/// `Last-Modified` exactly.
+fn if_range_matches(if_range: &str, upstream: &reqwest::header::HeaderMap) -> bool {
+    let if_range = if_range.trim();
+    if if_range.starts_with("W/") {
+        return false;
+    }
+
+    let header = if if_range.starts_with('"') {
+        reqwest::header::ETAG
+    } else {
+        reqwest::header::LAST_MODIFIED
+    };
+
+    upstream
+        .get(header)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| !v.starts_with("W/") && v == if_range)
+}
+
+async fn send(client: &reqwest::Client, url: &str, headers: &HeaderMap, forward_range: bool) -> reqwest::Result<reqwest::Response> {
+    let mut request = client.get(url);
+    if forward_range {
+        for name in FORWARDED_REQUEST_HEADERS {
+            if let Some(value) = headers.get(&name) {
+                request = request.header(name, value);
+            }
+        }
+    }
+    request.send().await
+}
+
+/// Stream `url` to the client with HTTP range support: the client's `Range`
+/// and `If-Range` are forwarded and `206 Partial Content` (with its
+/// `Content-Range`) is relayed. If the upstream ignores `If-Range` and
+/// returns a range of a changed file, the full file is fetched instead so
+/// the player never splices bytes from two versions.
+pub async fn proxy_audio(client: &reqwest::Client, url: &str, request_headers: &HeaderMap) -> Response {
+    let mut upstream = send(client, url, request_headers, true).await;
+
+    if let Ok(response) = &upstream
+        && response.status() == reqwest::StatusCode::PARTIAL_CONTENT
+        && let Some(if_range) = request_headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok())
+        && !if_range_matches(if_range, response.headers()) {
+        upstream = send(client, url, request_headers, false).await;
+    }
+
+    let upstream = match upstream {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Audio upstream request failed: {}", e);
+            return ApiError::bad_gateway("Audio upstream unavailable").into_response();
+        }
+    };
+
+    let status = StatusCode::from_u16(upstream.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let mut response = Response::builder().status(status);
+    for name in RELAYED_RESPONSE_HEADERS {
+        if let Some(value) = upstream.headers().get(&name) {
+            response = response.header(name, value);
+        }
+    }
+
+    response
+        .body(Body::from_stream(upstream.bytes_stream()))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+
+    const AUDIO: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    const ETAG: &str = "\"v1\"";
+
+    /// Serves `AUDIO` with single-range support. Like some CDNs, it ignores
+    /// `If-Range` entirely.
+    async fn mock_server() -> String {
+        let app = Router::new().route(
+            "/audio",
+            get(|headers: HeaderMap| async move {
+                let range = headers
+                    .get(header::RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("bytes="))
+                    .and_then(|v| v.split_once('-'))
+                    .and_then(|(start, end)| Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?)));
+
+                match range {
+                    Some((start, end)) => Response::builder()
+                        .status(StatusCode::PARTIAL_CONTENT)
+                        .header(header::CONTENT_TYPE, "audio/mpeg")
+                        .header(header::ETAG, ETAG)
+                        .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, AUDIO.len()))
+                        .body(Body::from(&AUDIO[start..=end]))
+                        .unwrap(),
+                    None => Response::builder()
+                        .header(header::CONTENT_TYPE, "audio/mpeg")
+                        .header(header::ETAG, ETAG)
+                        .header(header::ACCEPT_RANGES, "bytes")
+                        .body(Body::from(AUDIO))
+                        .unwrap(),
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        format!("http://{}/audio", addr)
+    }
+
+    async fn body_bytes(response: Response) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn relays_mid_file_range() {
+        let url = mock_server().await;
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, "bytes=10-19".parse().unwrap());
+
+        let response = proxy_audio(&reqwest::Client::new(), &url, &headers).await;
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 10-19/36");
+        assert_eq!(body_bytes(response).await, b"abcdefghij");
+    }
+
+    #[tokio::test]
+    async fn honours_matching_if_range() {
+        let url = mock_server().await;
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, "bytes=10-19".parse().unwrap());
+        headers.insert(header::IF_RANGE, ETAG.parse().unwrap());
+
+        let response = proxy_audio(&reqwest::Client::new(), &url, &headers).await;
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body_bytes(response).await, b"abcdefghij");
+    }
+
+    #[tokio::test]
+    async fn stale_if_range_returns_full_file() {
+        let url = mock_server().await;
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, "bytes=10-19".parse().unwrap());
+        headers.insert(header::IF_RANGE, "\"v0\"".parse().unwrap());
+
+        let response = proxy_audio(&reqwest::Client::new(), &url, &headers).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::CONTENT_RANGE).is_none());
+        assert_eq!(body_bytes(response).await, AUDIO);
+    }
+}
@@ -0,0 +1,112 @@
+// Pluggable audio sources behind the /song routes
+use std::future::Future;
+
+use crate::controllers::song::SongError;
+use crate::error::ApiError;
+use crate::models::song::{Track, TrackValueResponse};
+
+/// Sources `?source=` may name
+pub const AUDIO_SOURCES: &[&str] = &["youtube"];
+
+/// A playable stream resolved from a search result's id
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub id: String,
+    pub stream_url: String,
+}
+
+/// Somewhere tracks can be searched for and streamed from. YouTube (via
+/// the Data API and yt-dlp) is the only one so far.
+pub trait AudioSource {
+    /// Matches for `query`, best first
+    fn search(&self, query: &str) -> impl Future<Output = anyhow::Result<Vec<TrackValueResponse>>> + Send;
+
+    /// A stream for an id returned by `search`
+    fn resolve_stream(&self, id: &str) -> impl Future<Output = anyhow::Result<StreamInfo>> + Send;
+}
+
+/// Which source a `/song` request is served from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SourceKind {
+    #[default]
+    Youtube,
+}
+
+impl SourceKind {
+    /// Parse the optional `source` param, defaulting to YouTube
+    pub fn from_param(source: Option<&str>) -> Result<Self, ApiError> {
+        match source.map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("youtube") => Ok(Self::Youtube),
+            Some(other) => Err(ApiError::bad_request(format!("Unknown source '{}'", other))
+                .with_code("unknown_source")
+                .with_details(serde_json::json!({"allowed": AUDIO_SOURCES}))),
+        }
+    }
+}
+
+/// Best match for `query`, without resolving a stream
+pub async fn search_best<S: AudioSource>(source: &S, query: &str) -> anyhow::Result<TrackValueResponse> {
+    source
+        .search(query)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| SongError::NoResults(query.to_string()).into())
+}
+
+/// Best match for `query` with its stream resolved
+pub async fn find_track<S: AudioSource>(source: &S, query: &str) -> anyhow::Result<Track> {
+    let result = search_best(source, query).await?;
+    let stream = source.resolve_stream(&result.video_id).await?;
+    Ok(Track::from_search(result, stream.stream_url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeSource(Vec<&'static str>);
+
+    impl AudioSource for FakeSource {
+        async fn search(&self, _query: &str) -> anyhow::Result<Vec<TrackValueResponse>> {
+            Ok(self
+                .0
+                .iter()
+                .map(|id| TrackValueResponse {
+                    video_id: id.to_string(),
+                    title: format!("Title {}", id),
+                    channel_title: "Channel".to_string(),
+                    thumbnail_url: None,
+                    duration_ms: None,
+                })
+                .collect())
+        }
+
+        async fn resolve_stream(&self, id: &str) -> anyhow::Result<StreamInfo> {
+            Ok(StreamInfo {
+                id: id.to_string(),
+                stream_url: format!("https://cdn.example/{}", id),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn find_track_streams_the_top_result() {
+        let track = find_track(&FakeSource(vec!["first", "second"]), "q").await.unwrap();
+        assert_eq!(track.video_id, "first");
+        assert_eq!(track.stream_url, "https://cdn.example/first");
+    }
+
+    #[tokio::test]
+    async fn no_results_is_a_song_error() {
+        let err = search_best(&FakeSource(vec![]), "nothing").await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<SongError>(), Some(SongError::NoResults(q)) if q == "nothing"));
+    }
+
+    #[test]
+    fn source_param_defaults_to_youtube_and_rejects_unknown() {
+        assert_eq!(SourceKind::from_param(None).unwrap(), SourceKind::Youtube);
+        assert_eq!(SourceKind::from_param(Some("YouTube")).unwrap(), SourceKind::Youtube);
+        assert!(SourceKind::from_param(Some("soundcloud")).is_err());
+    }
+}
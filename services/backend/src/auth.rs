@@ -0,0 +1,338 @@
+// JWT verification and mix session ownership
+use axum::http::HeaderMap;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::db::Database;
+use crate::error::ApiError;
+use crate::secrets::SECRET_MANAGER;
+
+/// Header carrying the user's JWT on endpoints where `Authorization` already
+/// holds a Spotify access token (e.g. `/mix/generate`)
+pub const USER_TOKEN_HEADER: &str = "X-User-Token";
+
+/// WebSocket subprotocol clients pair with the token, as in
+/// `new WebSocket(url, ["bearer", token])`
+pub const WS_TOKEN_PROTOCOL: &str = "bearer";
+
+/// How long a stream token stays valid. EventSource reconnects reuse the URL
+/// (and so the token), so this outlasts a long generation.
+pub const STREAM_TOKEN_TTL_SECS: i64 = 60 * 60;
+
+/// `sub` of stream tokens issued for sessions without an owner
+const ANONYMOUS_SUBJECT: &str = "anonymous";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    /// The user id
+    pub sub: String,
+    /// Expiry as unix seconds
+    pub exp: i64,
+    /// Set on stream tokens: the only mix session the token grants access to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum TokenError {
+    Malformed,
+    UnsupportedAlgorithm,
+    BadSignature,
+    Expired,
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::Malformed => write!(f, "malformed token"),
+            TokenError::UnsupportedAlgorithm => write!(f, "unsupported signing algorithm"),
+            TokenError::BadSignature => write!(f, "invalid signature"),
+            TokenError::Expired => write!(f, "token expired"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+}
+
+/// Verify an HS256 JWT signed with `JWT_SECRET` and return its claims
+pub fn verify_token(token: &str) -> Result<Claims, TokenError> {
+    verify_token_with_secret(token, SECRET_MANAGER.get("JWT_SECRET").as_bytes())
+}
+
+/// Sign `claims` as an HS256 JWT
+fn sign_token_with_secret(claims: &Claims, secret: &[u8]) -> String {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap_or_default());
+    let signing_input = format!("{}.{}", header, payload);
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(signing_input.as_bytes());
+    format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+}
+
+/// A token for `session_id`'s progress streams only, for `user_id` or an
+/// anonymous caller. Checked by `authorize_session`.
+pub fn issue_stream_token(session_id: &str, user_id: Option<&str>) -> String {
+    let claims = Claims {
+        sub: user_id.unwrap_or(ANONYMOUS_SUBJECT).to_string(),
+        exp: chrono::Utc::now().timestamp() + STREAM_TOKEN_TTL_SECS,
+        sid: Some(session_id.to_string()),
+    };
+    sign_token_with_secret(&claims, SECRET_MANAGER.get("JWT_SECRET").as_bytes())
+}
+
+fn verify_token_with_secret(token: &str, secret: &[u8]) -> Result<Claims, TokenError> {
+    let mut parts = token.split('.');
+    let (Some(encoded_header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(TokenError::Malformed);
+    };
+
+    let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| TokenError::Malformed);
+
+    // Only accept HS256 so a token can't downgrade to `alg: none`
+    let header: Header = serde_json::from_slice(&decode(encoded_header)?).map_err(|_| TokenError::Malformed)?;
+    if header.alg != "HS256" {
+        return Err(TokenError::UnsupportedAlgorithm);
+    }
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).map_err(|_| TokenError::BadSignature)?;
+    mac.update(format!("{}.{}", encoded_header, payload).as_bytes());
+    mac.verify_slice(&decode(signature)?)
+        .map_err(|_| TokenError::BadSignature)?;
+
+    let claims: Claims = serde_json::from_slice(&decode(payload)?).map_err(|_| TokenError::Malformed)?;
+    if claims.exp <= chrono::Utc::now().timestamp() {
+        return Err(TokenError::Expired);
+    }
+    Ok(claims)
+}
+
+/// The user id from a valid `X-User-Token`, if one was sent. Stream tokens
+/// end up in URLs, so they only open their session and never count as this.
+pub fn user_id_from_headers(headers: &HeaderMap) -> Option<String> {
+    let token = headers.get(USER_TOKEN_HEADER)?.to_str().ok()?;
+    match verify_token(token) {
+        Ok(claims) if claims.sid.is_some() => {
+            warn!("Ignoring stream token sent as a user token");
+            None
+        }
+        Ok(claims) => Some(claims.sub),
+        Err(e) => {
+            warn!("Ignoring invalid user token: {}", e);
+            None
+        }
+    }
+}
+
+/// Find the token on a streaming request. Browsers can't set headers on
+/// WebSocket or EventSource connections, so besides `Authorization: Bearer`
+/// we accept the `Sec-WebSocket-Protocol: bearer, <token>` pair and `?token=`.
+pub fn stream_token(headers: &HeaderMap, query_token: Option<String>) -> Option<String> {
+    let bearer = headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|t| t.to_string());
+
+    let protocol = headers
+        .get("Sec-WebSocket-Protocol")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| {
+            let mut protocols = h.split(',').map(str::trim);
+            protocols.find(|p| *p == WS_TOKEN_PROTOCOL)?;
+            protocols.next().map(|t| t.to_string())
+        });
+
+    bearer.or(protocol).or(query_token)
+}
+
+/// Whether `origin` is in `allowed`, ignoring case and a trailing slash.
+/// A `*` entry allows every origin; an empty list allows none.
+fn origin_allowed(origin: &str, allowed: &[String]) -> bool {
+    let normalize = |o: &str| o.trim().trim_end_matches('/').to_ascii_lowercase();
+    let origin = normalize(origin);
+    allowed.iter().any(|a| a == "*" || normalize(a) == origin)
+}
+
+/// Reject browser streaming requests from origins not listed in
+/// `STREAM_ALLOWED_ORIGINS` with a 403. CORS doesn't cover WebSocket
+/// upgrades, so without this any site could open a user's progress stream.
+/// Requests without an `Origin` (non-browser clients) are let through.
+pub fn check_stream_origin(headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(origin) = headers.get("Origin") else {
+        return Ok(());
+    };
+    let origin = origin.to_str().unwrap_or_default();
+    if origin_allowed(origin, &SECRET_MANAGER.get_list("STREAM_ALLOWED_ORIGINS")) {
+        Ok(())
+    } else {
+        debug!("Rejected stream from origin {:?}", origin);
+        Err(ApiError::forbidden("Origin not allowed").with_code("origin_not_allowed"))
+    }
+}
+
+/// For a stream token, whether it was issued for `session_id`. `None` for
+/// other tokens, whose access depends on owning the session.
+fn stream_scope_allows(claims: &Claims, session_id: &str) -> Option<bool> {
+    claims.sid.as_deref().map(|sid| sid == session_id)
+}
+
+/// Verify `token` and check it grants access to `session_id`: a stream token
+/// must have been issued for that session, any other token's user must own it.
+///
+/// Sessions created before ownership was recorded (`user_id IS NULL`) are
+/// open to any authenticated user. Unknown sessions are rejected with 403 so
+/// the response doesn't reveal which session ids exist.
+pub async fn authorize_session(
+    database: &Database,
+    session_id: &str,
+    token: Option<String>,
+) -> Result<Claims, ApiError> {
+    let Some(token) = token else {
+        return Err(ApiError::unauthorized("Missing token"));
+    };
+    let claims = verify_token(&token).map_err(|e| {
+        debug!("Rejected stream token: {}", e);
+        ApiError::unauthorized("Invalid token")
+    })?;
+
+    // Ownership was checked when the stream token was issued
+    match stream_scope_allows(&claims, session_id) {
+        Some(true) => return Ok(claims),
+        Some(false) => return Err(ApiError::forbidden("Not allowed to access this session")),
+        None => {}
+    }
+
+    let session_uuid = Uuid::parse_str(session_id)
+        .map_err(|_| ApiError::forbidden("Not allowed to access this session"))?;
+
+    match database.get_mix_session(session_uuid).await {
+        Ok(Some(session)) if session.user_id.as_deref().is_none_or(|owner| owner == claims.sub) => {
+            Ok(claims)
+        }
+        Ok(_) => Err(ApiError::forbidden("Not allowed to access this session")),
+        Err(e) => {
+            warn!("Failed to look up session {} for auth: {}", session_id, e);
+            Err(ApiError::database(&e, "Failed to verify session ownership"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origins_match_case_insensitively_without_trailing_slash() {
+        let allowed = vec!["https://App.example.com/".to_string()];
+        assert!(origin_allowed("https://app.example.com", &allowed));
+        assert!(!origin_allowed("https://evil.example.com", &allowed));
+        assert!(!origin_allowed("null", &allowed));
+    }
+
+    #[test]
+    fn only_a_wildcard_allows_any_origin() {
+        assert!(origin_allowed("https://anywhere.test", &["*".to_string()]));
+        assert!(!origin_allowed("https://anywhere.test", &[]));
+    }
+
+    fn sign(header: &str, claims: &serde_json::Value, secret: &[u8]) -> String {
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(signing_input.as_bytes());
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+
+    fn claims_for_user(exp_offset: i64) -> serde_json::Value {
+        serde_json::json!({"sub": "user-1", "exp": chrono::Utc::now().timestamp() + exp_offset})
+    }
+
+    const HS256: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+    #[test]
+    fn accepts_valid_token() {
+        let token = sign(HS256, &claims_for_user(60), b"secret");
+        assert_eq!(verify_token_with_secret(&token, b"secret").unwrap().sub, "user-1");
+    }
+
+    #[test]
+    fn rejects_wrong_secret_and_expired_tokens() {
+        let token = sign(HS256, &claims_for_user(60), b"other");
+        assert!(matches!(verify_token_with_secret(&token, b"secret"), Err(TokenError::BadSignature)));
+
+        let token = sign(HS256, &claims_for_user(-60), b"secret");
+        assert!(matches!(verify_token_with_secret(&token, b"secret"), Err(TokenError::Expired)));
+    }
+
+    #[test]
+    fn rejects_alg_none() {
+        let token = sign(r#"{"alg":"none"}"#, &claims_for_user(60), b"secret");
+        assert!(matches!(
+            verify_token_with_secret(&token, b"secret"),
+            Err(TokenError::UnsupportedAlgorithm)
+        ));
+    }
+
+    #[test]
+    fn stream_tokens_are_scoped_to_their_session() {
+        let claims = Claims {
+            sub: ANONYMOUS_SUBJECT.to_string(),
+            exp: chrono::Utc::now().timestamp() + STREAM_TOKEN_TTL_SECS,
+            sid: Some("session-1".to_string()),
+        };
+        let token = sign_token_with_secret(&claims, b"secret");
+        let verified = verify_token_with_secret(&token, b"secret").unwrap();
+        assert_eq!(verified.sid.as_deref(), Some("session-1"));
+        assert_eq!(verified.sub, "anonymous");
+        assert!(matches!(verify_token_with_secret(&token, b"other"), Err(TokenError::BadSignature)));
+
+        // Ordinary user tokens carry no session scope
+        let token = sign(HS256, &claims_for_user(60), b"secret");
+        assert_eq!(verify_token_with_secret(&token, b"secret").unwrap().sid, None);
+    }
+
+    #[test]
+    fn stream_token_only_opens_its_own_session() {
+        let claims = verify_token(&issue_stream_token("session-1", Some("user-1"))).unwrap();
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(stream_scope_allows(&claims, "session-1"), Some(true));
+        assert_eq!(stream_scope_allows(&claims, "session-2"), Some(false));
+
+        let user_token = sign(HS256, &claims_for_user(60), SECRET_MANAGER.get("JWT_SECRET").as_bytes());
+        assert_eq!(stream_scope_allows(&verify_token(&user_token).unwrap(), "session-1"), None);
+    }
+
+    #[test]
+    fn stream_tokens_do_not_identify_a_user() {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_TOKEN_HEADER, issue_stream_token("session-1", Some("user-1")).parse().unwrap());
+        assert_eq!(user_id_from_headers(&headers), None);
+
+        let user_token = sign(HS256, &claims_for_user(60), SECRET_MANAGER.get("JWT_SECRET").as_bytes());
+        headers.insert(USER_TOKEN_HEADER, user_token.parse().unwrap());
+        assert_eq!(user_id_from_headers(&headers).as_deref(), Some("user-1"));
+    }
+
+    #[test]
+    fn reads_token_from_websocket_protocol() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Sec-WebSocket-Protocol", "bearer, abc.def.ghi".parse().unwrap());
+        assert_eq!(stream_token(&headers, None).as_deref(), Some("abc.def.ghi"));
+        assert_eq!(stream_token(&HeaderMap::new(), Some("q".into())).as_deref(), Some("q"));
+    }
+}
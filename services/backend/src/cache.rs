@@ -0,0 +1,68 @@
+// Redis-backed JSON cache. Keys are namespaced under `REDIS_PREFIX` here, so
+// callers pass bare names.
+use redis::AsyncCommands;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
+
+use crate::redis_keys;
+use crate::redis_pool::{PooledConnection, REDIS_POOL};
+
+async fn connection() -> Option<PooledConnection> {
+    match REDIS_POOL.get().await {
+        Ok(conn) => Some(conn),
+        Err(e) => {
+            warn!("Cache unavailable, failed to connect to Redis: {}", e);
+            None
+        }
+    }
+}
+
+/// Fetch a cached value, treating any Redis or decode failure as a miss
+pub async fn get_json<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let mut conn = connection().await?;
+    let raw: Option<String> = conn.get(redis_keys::key(key)).await.ok()?;
+    raw.and_then(|r| serde_json::from_str(&r).ok())
+}
+
+/// Store a value with a TTL; failures are logged and otherwise ignored
+pub async fn set_json<T: Serialize>(key: &str, value: &T, ttl_secs: u64) {
+    let Some(mut conn) = connection().await else {
+        return;
+    };
+
+    let raw = match serde_json::to_string(value) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Failed to serialize cache value for {}: {}", key, e);
+            return;
+        }
+    };
+
+    if let Err(e) = conn.set_ex::<_, _, ()>(redis_keys::key(key), raw, ttl_secs).await {
+        warn!("Failed to write cache key {}: {}", key, e);
+    }
+}
+
+/// Fetch and delete a value in one step, so only one caller ever gets it
+pub async fn take_json<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let mut conn = connection().await?;
+    let raw: Option<String> = conn.get_del(redis_keys::key(key)).await.ok()?;
+    raw.and_then(|r| serde_json::from_str(&r).ok())
+}
+
+/// Fetch cached raw bytes, treating any Redis failure as a miss
+pub async fn get_bytes(key: &str) -> Option<Vec<u8>> {
+    let mut conn = connection().await?;
+    conn.get(redis_keys::key(key)).await.ok()?
+}
+
+/// Store raw bytes with a TTL; failures are logged and otherwise ignored
+pub async fn set_bytes(key: &str, value: &[u8], ttl_secs: u64) {
+    let Some(mut conn) = connection().await else {
+        return;
+    };
+
+    if let Err(e) = conn.set_ex::<_, _, ()>(redis_keys::key(key), value, ttl_secs).await {
+        warn!("Failed to write cache key {}: {}", key, e);
+    }
+}
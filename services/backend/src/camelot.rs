@@ -0,0 +1,181 @@
+// Camelot wheel notation for harmonic mixing
+use std::fmt;
+use std::str::FromStr;
+
+/// Camelot number for each Spotify pitch class (0 = C), as (minor, major).
+/// Mirrors `SPOTIFY_KEY_TO_CAMELOT` in the orchestrator.
+const CAMELOT_BY_PITCH_CLASS: [(u8, u8); 12] = [
+    (5, 8),   // C
+    (12, 3),  // C#/Db
+    (7, 10),  // D
+    (2, 5),   // D#/Eb
+    (9, 12),  // E
+    (4, 7),   // F
+    (11, 2),  // F#/Gb
+    (6, 9),   // G
+    (1, 4),   // G#/Ab
+    (8, 11),  // A
+    (3, 6),   // A#/Bb
+    (10, 1),  // B
+];
+
+/// `A` keys are minor, `B` keys are major
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Minor,
+    Major,
+}
+
+impl Mode {
+    fn letter(self) -> char {
+        match self {
+            Mode::Minor => 'A',
+            Mode::Major => 'B',
+        }
+    }
+
+    fn relative(self) -> Self {
+        match self {
+            Mode::Minor => Mode::Major,
+            Mode::Major => Mode::Minor,
+        }
+    }
+}
+
+/// A position on the Camelot wheel, e.g. `8A` (A minor)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CamelotKey {
+    /// 1–12, clockwise in fifths
+    pub number: u8,
+    pub mode: Mode,
+}
+
+impl CamelotKey {
+    /// From Spotify's `key` (pitch class, -1 if unknown) and `mode`
+    /// (0 = minor, 1 = major)
+    pub fn from_spotify(key: i32, mode: i32) -> Option<Self> {
+        let (minor, major) = CAMELOT_BY_PITCH_CLASS.get(usize::try_from(key).ok()?)?;
+        match mode {
+            0 => Some(Self { number: *minor, mode: Mode::Minor }),
+            1 => Some(Self { number: *major, mode: Mode::Major }),
+            _ => None,
+        }
+    }
+
+    /// `steps` positions around the wheel in the same mode (negative = counter-clockwise)
+    fn rotate(self, steps: i8) -> Self {
+        let number = (self.number as i8 - 1 + steps).rem_euclid(12) as u8 + 1;
+        Self { number, ..self }
+    }
+
+    /// Keys that mix cleanly from this one: itself, its relative major/minor,
+    /// and one step either way around the wheel
+    pub fn compatible(self) -> [Self; 4] {
+        [
+            self,
+            Self { mode: self.mode.relative(), ..self },
+            self.rotate(1),
+            self.rotate(-1),
+        ]
+    }
+
+    /// Distance to another key (0 = same key, lower mixes better).
+    /// Matches `camelot_distance` in the orchestrator.
+    pub fn distance(self, other: Self) -> u32 {
+        if self == other {
+            0
+        } else if self.number == other.number {
+            // Relative major/minor
+            1
+        } else if self.mode == other.mode {
+            let diff = self.number.abs_diff(other.number) as u32;
+            diff.min(12 - diff)
+        } else {
+            6
+        }
+    }
+}
+
+impl fmt::Display for CamelotKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.number, self.mode.letter())
+    }
+}
+
+impl FromStr for CamelotKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is not a Camelot key", s);
+        let s = s.trim();
+        let (number, letter) = s.split_at(s.len().checked_sub(1).ok_or_else(invalid)?);
+
+        let mode = match letter {
+            "A" | "a" => Mode::Minor,
+            "B" | "b" => Mode::Major,
+            _ => return Err(invalid()),
+        };
+        let number = number.parse::<u8>().map_err(|_| invalid())?;
+        if !(1..=12).contains(&number) {
+            return Err(invalid());
+        }
+        Ok(Self { number, mode })
+    }
+}
+
+/// Spotify `key`/`mode` in Camelot notation, `None` when the key is unknown
+pub fn to_camelot(key: i32, mode: i32) -> Option<String> {
+    CamelotKey::from_spotify(key, mode).map(|k| k.to_string())
+}
+
+/// Keys that mix cleanly from `camelot`; empty if it isn't a Camelot key.
+/// Same order as `get_compatible_keys` in the orchestrator.
+pub fn compatible_keys(camelot: &str) -> Vec<String> {
+    camelot
+        .parse::<CamelotKey>()
+        .map(|key| key.compatible().iter().map(|k| k.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Distance between two Camelot keys, 12 if either can't be parsed
+pub fn distance(a: &str, b: &str) -> u32 {
+    match (a.parse::<CamelotKey>(), b.parse::<CamelotKey>()) {
+        (Ok(a), Ok(b)) => a.distance(b),
+        _ => 12,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_spotify_keys_to_camelot() {
+        assert_eq!(to_camelot(9, 0).as_deref(), Some("8A")); // A minor
+        assert_eq!(to_camelot(0, 1).as_deref(), Some("8B")); // C major
+        assert_eq!(to_camelot(7, 1).as_deref(), Some("9B")); // G major
+        assert_eq!(to_camelot(1, 0).as_deref(), Some("12A")); // C# minor
+        assert_eq!(to_camelot(11, 1).as_deref(), Some("1B")); // B major
+        assert_eq!(to_camelot(-1, 1), None);
+        assert_eq!(to_camelot(4, 2), None);
+    }
+
+    #[test]
+    fn compatible_keys_wrap_around_the_wheel() {
+        assert_eq!(compatible_keys("8A"), ["8A", "8B", "9A", "7A"]);
+        assert_eq!(compatible_keys("12B"), ["12B", "12A", "1B", "11B"]);
+        assert_eq!(compatible_keys("1A"), ["1A", "1B", "2A", "12A"]);
+        assert!(compatible_keys("13A").is_empty());
+        assert!(compatible_keys("").is_empty());
+    }
+
+    #[test]
+    fn distance_matches_orchestrator() {
+        assert_eq!(distance("8A", "8A"), 0);
+        assert_eq!(distance("8A", "8B"), 1);
+        assert_eq!(distance("1A", "12A"), 1);
+        assert_eq!(distance("3B", "9B"), 6);
+        assert_eq!(distance("3A", "5B"), 6);
+        assert_eq!(distance("8A", "nope"), 12);
+    }
+}
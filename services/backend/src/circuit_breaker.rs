@@ -0,0 +1,170 @@
+// Circuit breaker for upstream services
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::secrets::SECRET_MANAGER;
+
+/// Guards `/mix/generate` and `/mix/{id}/regenerate` so a dead orchestrator
+/// fails fast instead of holding every request for the full timeout
+pub static ORCHESTRATOR_BREAKER: Lazy<CircuitBreaker> = Lazy::new(|| {
+    let threshold = SECRET_MANAGER
+        .get("ORCHESTRATOR_BREAKER_THRESHOLD")
+        .parse::<u32>()
+        .unwrap_or(5);
+    let cooldown_secs = SECRET_MANAGER
+        .get("ORCHESTRATOR_BREAKER_COOLDOWN_SECS")
+        .parse::<u64>()
+        .unwrap_or(30);
+    CircuitBreaker::new("orchestrator", threshold, Duration::from_secs(cooldown_secs))
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Requests flow normally
+    Closed,
+    /// Requests are rejected until the cooldown ends
+    Open,
+    /// One probe request is in flight to test recovery
+    HalfOpen,
+}
+
+/// Breaker state as reported by `/health`
+#[derive(Debug, Serialize)]
+pub struct BreakerSnapshot {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    /// While open: when the next probe may go out. While half-open: when the
+    /// probe is presumed lost (e.g. the client disconnected) and another may go.
+    until: Instant,
+}
+
+/// Opens after `threshold` consecutive failures and rejects calls for
+/// `cooldown`, then lets a single probe through. A successful probe closes
+/// the circuit; a failed one opens it for another cooldown.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            name,
+            threshold: threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                until: Instant::now(),
+            }),
+        }
+    }
+
+    /// Whether a call may go out. `Err` carries how long until the next probe.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            BreakerState::Closed => Ok(()),
+            BreakerState::Open | BreakerState::HalfOpen if now >= inner.until => {
+                info!("Circuit '{}' half-open, probing upstream", self.name);
+                inner.state = BreakerState::HalfOpen;
+                inner.until = now + self.cooldown;
+                Ok(())
+            }
+            BreakerState::Open | BreakerState::HalfOpen => Err(inner.until - now),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state != BreakerState::Closed {
+            info!("Circuit '{}' closed, upstream recovered", self.name);
+        }
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+
+        let trip = inner.state == BreakerState::HalfOpen || inner.consecutive_failures >= self.threshold;
+        if trip {
+            if inner.state != BreakerState::Open {
+                warn!(
+                    "Circuit '{}' open after {} consecutive failures; failing fast for {}s",
+                    self.name,
+                    inner.consecutive_failures,
+                    self.cooldown.as_secs()
+                );
+            }
+            inner.state = BreakerState::Open;
+            inner.until = now + self.cooldown;
+        }
+    }
+
+    pub fn snapshot(&self) -> BreakerSnapshot {
+        let inner = self.inner.lock().unwrap();
+        let retry_after_secs = (inner.state == BreakerState::Open)
+            .then(|| inner.until.saturating_duration_since(Instant::now()).as_secs());
+        BreakerSnapshot {
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            retry_after_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_and_probes_after_cooldown() {
+        let breaker = CircuitBreaker::new("test", 2, Duration::from_secs(30));
+        let start = Instant::now();
+
+        breaker.record_failure_at(start);
+        assert!(breaker.try_acquire_at(start).is_ok());
+        breaker.record_failure_at(start);
+        assert_eq!(breaker.snapshot().state, BreakerState::Open);
+        assert_eq!(breaker.try_acquire_at(start + Duration::from_secs(10)), Err(Duration::from_secs(20)));
+
+        // Only one probe goes out after the cooldown
+        let later = start + Duration::from_secs(30);
+        assert!(breaker.try_acquire_at(later).is_ok());
+        assert_eq!(breaker.snapshot().state, BreakerState::HalfOpen);
+        assert!(breaker.try_acquire_at(later).is_err());
+
+        // A failed probe reopens immediately; a successful one closes
+        breaker.record_failure_at(later);
+        assert_eq!(breaker.snapshot().state, BreakerState::Open);
+        assert!(breaker.try_acquire_at(later + Duration::from_secs(30)).is_ok());
+        breaker.record_success();
+        assert_eq!(breaker.snapshot().state, BreakerState::Closed);
+        assert_eq!(breaker.snapshot().consecutive_failures, 0);
+    }
+}
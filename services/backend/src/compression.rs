@@ -0,0 +1,65 @@
+// Response compression
+use axum::http::{Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::{
+    predicate::{DefaultPredicate, NotForContentType, Predicate},
+    CompressionLayer,
+};
+
+/// Never compress protocol switches: a WebSocket upgrade has no body to
+/// compress and must reach the client untouched
+fn not_upgrade(status: StatusCode, _: Version, _: &HeaderMap, _: &Extensions) -> bool {
+    status != StatusCode::SWITCHING_PROTOCOLS
+}
+
+/// Proxied audio is already compressed, and compressing it would break the
+/// byte offsets in `Content-Range`
+const NOT_FOR_AUDIO: NotForContentType = NotForContentType::const_new("audio/");
+
+fn predicate() -> impl Predicate {
+    DefaultPredicate::new().and(not_upgrade).and(NOT_FOR_AUDIO)
+}
+
+/// gzip/br compression for clients that send `Accept-Encoding`. The default
+/// predicate already skips tiny bodies, images and `text/event-stream`, so the
+/// SSE progress stream is delivered uncompressed.
+pub fn compression_layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new().compress_when(predicate())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Response};
+
+    fn response(status: StatusCode, content_type: &str, body: &'static str) -> Response<Body> {
+        Response::builder()
+            .status(status)
+            .header("content-type", content_type)
+            .header("content-length", body.len())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    const LARGE_JSON: &str = r#"{"tracks":["aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"]}"#;
+
+    #[test]
+    fn compresses_json() {
+        assert!(predicate().should_compress(&response(StatusCode::OK, "application/json", LARGE_JSON)));
+    }
+
+    #[test]
+    fn skips_server_sent_events() {
+        assert!(!predicate().should_compress(&response(StatusCode::OK, "text/event-stream", LARGE_JSON)));
+    }
+
+    #[test]
+    fn skips_proxied_audio() {
+        assert!(!predicate().should_compress(&response(StatusCode::PARTIAL_CONTENT, "audio/mpeg", LARGE_JSON)));
+    }
+
+    #[test]
+    fn skips_websocket_upgrades() {
+        let upgrade = response(StatusCode::SWITCHING_PROTOCOLS, "application/json", LARGE_JSON);
+        assert!(!predicate().should_compress(&upgrade));
+    }
+}
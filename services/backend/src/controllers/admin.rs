@@ -0,0 +1,42 @@
+// Admin controller
+use axum::{extract::State, response::Json};
+use tracing::{info, warn};
+
+use crate::db::Database;
+use crate::error::ApiError;
+use crate::secrets::SECRET_MANAGER;
+
+/// Compare without short-circuiting so response timing doesn't leak the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Check the `Authorization: Bearer <ADMIN_TOKEN>` header
+pub fn is_admin(headers: &axum::http::HeaderMap) -> bool {
+    let admin_token = SECRET_MANAGER.get("ADMIN_TOKEN");
+    if admin_token.is_empty() {
+        return false;
+    }
+
+    headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), admin_token.as_bytes()))
+}
+
+/// POST /admin/secrets/reload - Re-read secrets from the env file and environment
+pub async fn reload_secrets_route(
+    State(_database): State<Database>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if !is_admin(&headers) {
+        warn!("Rejected unauthorized secrets reload");
+        return Err(ApiError::unauthorized("Invalid admin token"));
+    }
+
+    SECRET_MANAGER.reload();
+    info!("Secrets reloaded via admin endpoint");
+
+    Ok(Json(serde_json::json!({"status": "reloaded"})))
+}
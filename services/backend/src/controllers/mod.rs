@@ -1,3 +1,6 @@
+pub mod admin;
 pub mod root;
+pub mod session;
+pub mod song;
 pub mod spotify;
 pub use root::RootController;
\ No newline at end of file
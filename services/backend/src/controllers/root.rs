@@ -1,11 +1,230 @@
+use axum::http::StatusCode;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::circuit_breaker::{BreakerSnapshot, ORCHESTRATOR_BREAKER};
+use crate::controllers::song::SONG_CONTROLLER;
+use crate::controllers::spotify::SPOTIFY_CONTROLLER;
+use crate::db::Database;
+use crate::generations::GENERATIONS;
+use crate::redis_pool::REDIS_POOL;
+use crate::secrets::SECRET_MANAGER;
+
+/// How long a single dependency check may take before it counts as failed
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a Spotify credentials check is reused, so `/health` polling
+/// doesn't request a fresh token every time
+const SPOTIFY_CHECK_TTL: Duration = Duration::from_secs(300);
+
+/// Outcome of a Spotify credentials check and when it ran
+type CachedCheck = (Instant, Result<(), String>);
+
+static SPOTIFY_CHECK: Lazy<tokio::sync::Mutex<Option<CachedCheck>>> =
+    Lazy::new(|| tokio::sync::Mutex::new(None));
+
+/// Build version reported by `/` and `/health`
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Process start, forced in `main` so uptime counts from boot
+pub static STARTED_AT: Lazy<Instant> = Lazy::new(Instant::now);
+
+#[derive(Debug, Serialize)]
+pub struct RootResponse {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    /// "ok" when every critical dependency is reachable, else "unavailable"
+    pub status: &'static str,
+    pub version: &'static str,
+    pub uptime_seconds: u64,
+    pub dependencies: Dependencies,
+    /// Whether mix generation is currently failing fast
+    pub orchestrator_circuit: BreakerSnapshot,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependencyStatus {
+    pub ok: bool,
+    /// Critical dependencies must pass for the service to report ready
+    pub critical: bool,
+    pub latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Dependencies {
+    pub postgres: DependencyStatus,
+    pub redis: DependencyStatus,
+    pub orchestrator: DependencyStatus,
+    pub yt_dlp: DependencyStatus,
+    /// Whether the Spotify client id/secret are accepted
+    pub spotify: DependencyStatus,
+}
+
+impl Dependencies {
+    fn critical_ok(&self) -> bool {
+        [&self.postgres, &self.redis, &self.orchestrator, &self.yt_dlp, &self.spotify]
+            .iter()
+            .all(|d| d.ok || !d.critical)
+    }
+}
+
+async fn check<F>(critical: bool, fut: F) -> DependencyStatus
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, fut).await {
+        Ok(r) => r,
+        Err(_) => Err("timed out".to_string()),
+    };
+
+    DependencyStatus {
+        ok: result.is_ok(),
+        critical,
+        latency_ms: started.elapsed().as_millis(),
+        error: result.err(),
+    }
+}
+
+async fn check_postgres(database: &Database) -> Result<(), String> {
+    sqlx::query("SELECT 1")
+        .execute(database.pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_redis() -> Result<(), String> {
+    let mut conn = REDIS_POOL.get().await.map_err(|e| e.to_string())?;
+    redis::cmd("PING")
+        .query_async::<String>(&mut conn)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_orchestrator() -> Result<(), String> {
+    let orchestrator_url = SECRET_MANAGER
+        .get_url("ORCHESTRATOR_URL")
+        .ok_or_else(|| "not configured".to_string())?;
+
+    // Any HTTP response means the orchestrator is reachable
+    reqwest::Client::new()
+        .get(format!("{}/health", orchestrator_url))
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_yt_dlp() -> Result<(), String> {
+    let output = tokio::process::Command::new(SECRET_MANAGER.get("YTDLP_PATH"))
+        .arg("--version")
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Request a client-credentials token to prove the Spotify client id and
+/// secret are valid. The outcome is cached for [`SPOTIFY_CHECK_TTL`]; the
+/// lock is held while checking so concurrent callers share one request.
+pub async fn check_spotify_credentials() -> Result<(), String> {
+    let mut cached = SPOTIFY_CHECK.lock().await;
+    if let Some((checked_at, result)) = cached.as_ref()
+        && checked_at.elapsed() < SPOTIFY_CHECK_TTL
+    {
+        return result.clone();
+    }
+
+    let result = SPOTIFY_CONTROLLER.get_client_credentials_token().await.map(|_| ());
+    *cached = Some((Instant::now(), result.clone()));
+    result
+}
+
 pub struct RootController;
 
 impl RootController {
-        pub async fn root() -> &'static str {
-            "Hello, World!"
+        pub async fn root() -> RootResponse {
+            RootResponse {
+                name: env!("CARGO_PKG_NAME"),
+                version: VERSION,
+            }
         }
-    
-        pub async fn health_check() -> &'static str {
+
+        /// Prometheus text exposition of this instance's counters
+        pub fn metrics() -> String {
+            let (redis_open, redis_idle) = REDIS_POOL.status();
+            format!(
+                "# HELP ai_dj_mix_generations_active Mix generations in flight on this instance\n\
+                 # TYPE ai_dj_mix_generations_active gauge\n\
+                 ai_dj_mix_generations_active {}\n\
+                 # HELP ai_dj_mix_generations_limit Concurrent mix generations allowed on this instance\n\
+                 # TYPE ai_dj_mix_generations_limit gauge\n\
+                 ai_dj_mix_generations_limit {}\n\
+                 # HELP ai_dj_redis_pool_connections Redis connections open in the shared pool\n\
+                 # TYPE ai_dj_redis_pool_connections gauge\n\
+                 ai_dj_redis_pool_connections {}\n\
+                 # HELP ai_dj_redis_pool_idle Idle Redis connections in the shared pool\n\
+                 # TYPE ai_dj_redis_pool_idle gauge\n\
+                 ai_dj_redis_pool_idle {}\n\
+                 # HELP ai_dj_ytdlp_extractions_in_flight Distinct yt-dlp extractions running; identical requests share one\n\
+                 # TYPE ai_dj_ytdlp_extractions_in_flight gauge\n\
+                 ai_dj_ytdlp_extractions_in_flight {}\n",
+                GENERATIONS.active(),
+                GENERATIONS.limit(),
+                redis_open,
+                redis_idle,
+                SONG_CONTROLLER.extractions_in_flight(),
+            )
+        }
+
+        /// Liveness: the process is up and serving requests
+        pub async fn liveness() -> &'static str {
             "OK"
         }
-}
\ No newline at end of file
+
+        /// Readiness: checks every upstream dependency concurrently.
+        /// Postgres and Redis are critical; the orchestrator, yt-dlp and
+        /// Spotify only degrade mix generation, song extraction and search.
+        pub async fn health_check(database: &Database) -> (StatusCode, HealthResponse) {
+            let (postgres, redis, orchestrator, yt_dlp, spotify) = tokio::join!(
+                check(true, check_postgres(database)),
+                check(true, check_redis()),
+                check(false, check_orchestrator()),
+                check(false, check_yt_dlp()),
+                check(false, check_spotify_credentials()),
+            );
+
+            let dependencies = Dependencies { postgres, redis, orchestrator, yt_dlp, spotify };
+            let ready = dependencies.critical_ok();
+
+            let body = HealthResponse {
+                status: if ready { "ok" } else { "unavailable" },
+                version: VERSION,
+                uptime_seconds: STARTED_AT.elapsed().as_secs(),
+                dependencies,
+                orchestrator_circuit: ORCHESTRATOR_BREAKER.snapshot(),
+            };
+
+            if ready {
+                (StatusCode::OK, body)
+            } else {
+                (StatusCode::SERVICE_UNAVAILABLE, body)
+            }
+        }
+}
@@ -0,0 +1,75 @@
+// Session profile (onboarding preferences) controller
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use tracing::{error, info};
+
+use crate::controllers::spotify::{self, SPOTIFY_CONTROLLER};
+use crate::db::Database;
+use crate::error::ApiError;
+use crate::models::session::{SessionProfile, SessionProfileRequest};
+
+/// Genres in `requested` that Spotify doesn't accept as recommendation seeds
+async fn unknown_genres(headers: &axum::http::HeaderMap, requested: &[String]) -> Result<Vec<String>, String> {
+    if requested.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let access_token = spotify::access_token_or_app_token(headers).await?;
+    let known = SPOTIFY_CONTROLLER.get_available_genre_seeds(&access_token).await?;
+
+    Ok(requested
+        .iter()
+        .filter(|genre| !known.contains(genre))
+        .cloned()
+        .collect())
+}
+
+/// POST /session/{id}/profile - Save the listener's vibe preferences
+pub async fn save_session_profile_route(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(mut body): Json<SessionProfileRequest>,
+) -> Result<Json<SessionProfile>, ApiError> {
+    if !(0.0..=1.0).contains(&body.energy_level) {
+        return Err(ApiError::bad_request("energy_level must be between 0.0 and 1.0"));
+    }
+
+    body.genres = body.genres.iter().map(|g| g.trim().to_lowercase()).collect();
+    let unknown = unknown_genres(&headers, &body.genres).await.map_err(|e| {
+        error!("Failed to validate genres: {}", e);
+        ApiError::bad_gateway("Could not validate genres against Spotify")
+    })?;
+    if !unknown.is_empty() {
+        return Err(ApiError::bad_request("Unknown genres")
+            .with_details(serde_json::json!({"genres": unknown})));
+    }
+
+    let profile = database
+        .upsert_session_profile(&session_id, &body)
+        .await
+        .map_err(|e| {
+            error!("Failed to save session profile: {}", e);
+            ApiError::database(&e, "Failed to save session profile")
+        })?;
+
+    info!("Saved profile for session {}", session_id);
+    Ok(Json(profile))
+}
+
+/// GET /session/{id}/profile - Fetch the saved preferences
+pub async fn get_session_profile_route(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+) -> Result<Json<SessionProfile>, ApiError> {
+    match database.get_session_profile(&session_id).await {
+        Ok(Some(profile)) => Ok(Json(profile)),
+        Ok(None) => Err(ApiError::not_found("Session profile not found")),
+        Err(e) => {
+            error!("Failed to get session profile: {}", e);
+            Err(ApiError::database(&e, "Failed to retrieve session profile"))
+        }
+    }
+}
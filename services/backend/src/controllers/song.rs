@@ -0,0 +1,1108 @@
+// YouTube search and stream extraction controller
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+use moka::policy::EvictionPolicy;
+use moka::sync::Cache;
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::process::Command;
+use tracing::{error, info, warn};
+
+use crate::audio_source::{self, AudioSource, SourceKind, StreamInfo};
+use crate::cache;
+use crate::db::Database;
+use crate::error::ApiError;
+use crate::models::song::{AudioFormat, SongMetadata, Track, TrackFormats, TrackValueResponse};
+use crate::retry::{send_with_retry, RetryPolicy};
+use crate::secrets::SECRET_MANAGER;
+use crate::singleflight::SingleFlight;
+
+/// Errors callers need to tell apart from generic failures
+#[derive(Debug, Clone)]
+pub enum SongError {
+    /// The YouTube API key hit its daily quota (resets at midnight Pacific time)
+    QuotaExceeded,
+    /// yt-dlp didn't finish within `YTDLP_TIMEOUT_SECS`
+    ExtractionTimeout(u64),
+    /// Live streams have no fixed duration, so they can't be mixed
+    LiveStream,
+    /// A valid search that matched no videos, with the query as sent
+    NoResults(String),
+}
+
+impl std::fmt::Display for SongError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SongError::QuotaExceeded => write!(f, "YouTube API quota exceeded"),
+            SongError::ExtractionTimeout(secs) => {
+                write!(f, "Stream extraction timed out after {}s", secs)
+            }
+            SongError::LiveStream => write!(f, "Live streams can't be used in a mix"),
+            SongError::NoResults(query) => write!(f, "No search results found for '{}'", query),
+        }
+    }
+}
+
+impl std::error::Error for SongError {}
+
+/// A yt-dlp outcome shared between coalesced callers
+type SharedExtraction<T> = Result<T, Arc<anyhow::Error>>;
+
+/// Rebuild an error for one of several callers that shared it, keeping a
+/// `SongError` intact so it still maps to the right status
+fn unshare_error(error: Arc<anyhow::Error>) -> anyhow::Error {
+    match error.downcast_ref::<SongError>() {
+        Some(e) => e.clone().into(),
+        None => anyhow!("{:#}", error),
+    }
+}
+
+/// Whether a YouTube API error body carries `reason: "quotaExceeded"`
+fn is_quota_exceeded(body: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v["error"]["errors"].as_array().cloned())
+        .is_some_and(|errors| errors.iter().any(|e| e["reason"] == "quotaExceeded"))
+}
+
+/// UTC offset of US Pacific time, accounting for daylight saving
+fn pacific_offset_hours(now: DateTime<Utc>) -> i32 {
+    let year = now.year();
+    let nth_sunday = |month: u32, n: i64| {
+        let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap_or_default();
+        let to_sunday = (7 - first.weekday().num_days_from_sunday() as i64) % 7;
+        first + chrono::Duration::days(to_sunday + 7 * (n - 1))
+    };
+
+    // DST runs from 2am on the second Sunday of March to 2am on the first Sunday of November
+    let dst_start = nth_sunday(3, 2).and_hms_opt(10, 0, 0).unwrap_or_default().and_utc();
+    let dst_end = nth_sunday(11, 1).and_hms_opt(9, 0, 0).unwrap_or_default().and_utc();
+
+    if now >= dst_start && now < dst_end { -7 } else { -8 }
+}
+
+/// Seconds until the YouTube quota resets at the next Pacific-time midnight
+fn seconds_until_quota_reset(now: DateTime<Utc>) -> i64 {
+    let Some(offset) = FixedOffset::east_opt(pacific_offset_hours(now) * 3600) else {
+        return 0;
+    };
+    let local = now.with_timezone(&offset);
+    let next_midnight = (local.date_naive() + chrono::Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .and_then(|m| m.and_local_timezone(offset).single());
+
+    match next_midnight {
+        Some(m) => (m - local).num_seconds(),
+        None => 0,
+    }
+}
+
+/// How long YouTube search results are cached in Redis, and the default for
+/// `YOUTUBE_SEARCH_LRU_TTL_SECS`
+pub const SEARCH_CACHE_TTL_SECS: u64 = 60 * 60 * 6;
+
+/// In-process cache of search candidates keyed on the normalized query, in
+/// front of Redis so single-instance deployments without it still save quota.
+/// Sized by `YOUTUBE_SEARCH_LRU_CAPACITY` (0 disables it) and expired after
+/// `YOUTUBE_SEARCH_LRU_TTL_SECS`.
+fn search_lru() -> Cache<String, Vec<TrackValueResponse>> {
+    let capacity = SECRET_MANAGER.get("YOUTUBE_SEARCH_LRU_CAPACITY").parse::<u64>().unwrap_or(1000);
+    let ttl_secs = SECRET_MANAGER
+        .get("YOUTUBE_SEARCH_LRU_TTL_SECS")
+        .parse::<u64>()
+        .unwrap_or(SEARCH_CACHE_TTL_SECS);
+    Cache::builder()
+        .max_capacity(capacity)
+        .time_to_live(Duration::from_secs(ttl_secs.max(1)))
+        .eviction_policy(EvictionPolicy::lru())
+        .build()
+}
+
+/// How long resolved stream URLs are cached. googlevideo URLs expire after
+/// roughly six hours, so this stays well inside that window.
+const STREAM_CACHE_TTL_SECS: u64 = 60 * 60;
+
+/// How far a YouTube video's duration may differ from the Spotify track's
+/// and still count as the same recording
+pub const DURATION_MATCH_TOLERANCE_MS: i64 = 5_000;
+
+/// Parse an ISO 8601 duration as returned by YouTube (e.g. `PT3M45S`) into ms
+pub fn parse_iso8601_duration_ms(duration: &str) -> Option<i64> {
+    let rest = duration.strip_prefix('P')?;
+    let mut total_secs = 0i64;
+    let mut number = String::new();
+    let mut in_time = false;
+
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            unit => {
+                let value: i64 = number.parse().ok()?;
+                number.clear();
+                total_secs += match (unit, in_time) {
+                    ('D', false) => value * 86_400,
+                    ('H', true) => value * 3_600,
+                    ('M', true) => value * 60,
+                    ('S', true) => value,
+                    _ => return None,
+                };
+            }
+        }
+    }
+
+    if !number.is_empty() {
+        return None;
+    }
+    Some(total_secs * 1000)
+}
+
+/// Normalize a search query into a cache key so equivalent searches share
+/// one entry: lowercase, drop apostrophes, turn other punctuation into
+/// spaces and collapse whitespace
+pub fn normalize_query(query: &str) -> String {
+    query
+        .to_lowercase()
+        .chars()
+        .filter(|c| *c != '\'' && *c != '’')
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SongQuery {
+    pub q: String,
+    /// Audio source to search, `youtube` when omitted
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// Structured lookup for a track the client already knows, e.g. from Spotify
+#[derive(Debug, Deserialize)]
+pub struct SongInfoRequest {
+    pub artist: String,
+    pub title: String,
+    /// Prefer a video within `DURATION_MATCH_TOLERANCE_MS` of this length
+    #[serde(default)]
+    pub duration_ms: Option<i64>,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+impl SongInfoRequest {
+    /// `Artist - Title`, the way music uploads are usually named
+    pub fn query(&self) -> Result<String, ApiError> {
+        let (artist, title) = (self.artist.trim(), self.title.trim());
+        if artist.is_empty() || title.is_empty() {
+            return Err(ApiError::bad_request("artist and title are required"));
+        }
+        if self.duration_ms.is_some_and(|d| d <= 0) {
+            return Err(ApiError::bad_request("duration_ms must be positive"));
+        }
+        Ok(format!("{} - {}", artist, title))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VideoIdQuery {
+    pub video_id: String,
+    /// Audio source the id belongs to, `youtube` when omitted
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshStreamRequest {
+    pub video_id: String,
+    /// The URL the player got a 403 for. The stream is always re-extracted
+    /// unless the cache already holds a different URL, i.e. someone else
+    /// refreshed it first.
+    #[serde(default)]
+    pub stale_url: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+fn stream_cache_key(video_id: &str) -> String {
+    format!("youtube:stream:{}", video_id)
+}
+
+/// The cached stream URL, if a refresh can reuse it: only when the player
+/// named its stale URL and the cache holds a different one. Without
+/// `stale_url` the cached URL may be the very one that stopped working.
+fn reusable_stream_url(cached: Option<String>, stale_url: Option<&str>) -> Option<String> {
+    cached.filter(|url| stale_url.is_some_and(|stale| stale != url))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RefreshStreamResponse {
+    pub video_id: String,
+    pub stream_url: String,
+}
+
+pub struct SongController {
+    client: Client,
+    /// YouTube Data API `search` and `videos` endpoints
+    search_api_url: String,
+    videos_api_url: String,
+    /// Watch page handed to yt-dlp, without the `?v=` query
+    watch_url: String,
+    /// Round-robin position into the configured API keys
+    next_key: AtomicUsize,
+    /// Keys that hit their quota, with when they become usable again
+    exhausted_keys: Mutex<HashMap<String, DateTime<Utc>>>,
+    /// yt-dlp runs in flight per video id, so concurrent requests for the
+    /// same video share one subprocess
+    stream_extractions: SingleFlight<SharedExtraction<String>>,
+    info_extractions: SingleFlight<SharedExtraction<serde_json::Value>>,
+    /// Search candidates by normalized query; checked before Redis
+    search_cache: Cache<String, Vec<TrackValueResponse>>,
+}
+
+impl SongController {
+    pub fn new() -> Self {
+        Self::with_urls(
+            &SECRET_MANAGER.get("YOUTUBE_API_URL"),
+            &SECRET_MANAGER.get("YOUTUBE_VIDEOS_API_URL"),
+            &SECRET_MANAGER.get("YOUTUBE_WATCH_URL"),
+        )
+    }
+
+    /// A controller talking to the given YouTube endpoints, e.g. a local fake
+    /// in tests
+    pub fn with_urls(search_api_url: &str, videos_api_url: &str, watch_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            search_api_url: search_api_url.to_string(),
+            videos_api_url: videos_api_url.to_string(),
+            watch_url: watch_url.to_string(),
+            next_key: AtomicUsize::new(0),
+            exhausted_keys: Mutex::new(HashMap::new()),
+            stream_extractions: SingleFlight::default(),
+            info_extractions: SingleFlight::default(),
+            search_cache: search_lru(),
+        }
+    }
+
+    fn video_url(&self, video_id: &str) -> String {
+        format!("{}?v={}", self.watch_url, video_id)
+    }
+
+    /// API keys that haven't hit their quota, starting from the next in rotation
+    fn available_keys(&self) -> Vec<String> {
+        let keys = SECRET_MANAGER.get_list("YOUTUBE_API_KEYS");
+        if keys.is_empty() {
+            return keys;
+        }
+
+        let now = Utc::now();
+        let mut exhausted = self.exhausted_keys.lock().unwrap_or_else(|e| e.into_inner());
+        exhausted.retain(|_, until| *until > now);
+
+        let start = self.next_key.fetch_add(1, Ordering::Relaxed) % keys.len();
+        keys.iter()
+            .cycle()
+            .skip(start)
+            .take(keys.len())
+            .filter(|k| !exhausted.contains_key(*k))
+            .cloned()
+            .collect()
+    }
+
+    fn mark_exhausted(&self, api_key: &str) {
+        let now = Utc::now();
+        let until = now + chrono::Duration::seconds(seconds_until_quota_reset(now));
+        let mut exhausted = self.exhausted_keys.lock().unwrap_or_else(|e| e.into_inner());
+        exhausted.insert(api_key.to_string(), until);
+    }
+
+    /// Call a YouTube Data API endpoint, rotating to the next API key whenever
+    /// one reports `quotaExceeded`
+    async fn youtube_get(&self, api_url: &str, params: &[(&str, &str)]) -> anyhow::Result<serde_json::Value> {
+        if SECRET_MANAGER.get_list("YOUTUBE_API_KEYS").is_empty() {
+            return Err(anyhow!("YouTube API key not configured"));
+        }
+
+        for api_key in self.available_keys() {
+            match self.get_with_key(api_url, params, &api_key).await {
+                Err(e) if matches!(e.downcast_ref::<SongError>(), Some(SongError::QuotaExceeded)) => {
+                    warn!("YouTube API key exhausted, rotating to the next key");
+                    self.mark_exhausted(&api_key);
+                }
+                result => return result,
+            }
+        }
+
+        Err(SongError::QuotaExceeded.into())
+    }
+
+    async fn get_with_key(
+        &self,
+        api_url: &str,
+        params: &[(&str, &str)],
+        api_key: &str,
+    ) -> anyhow::Result<serde_json::Value> {
+        let response = send_with_retry(RetryPolicy::default(), || {
+            self.client.get(api_url).query(params).query(&[("key", api_key)])
+        })
+        .await
+        .context("YouTube API request failed")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            // quotaExceeded is permanent until the daily reset, so it's never retried
+            if status == reqwest::StatusCode::FORBIDDEN && is_quota_exceeded(&body) {
+                return Err(SongError::QuotaExceeded.into());
+            }
+            return Err(anyhow!("YouTube API returned status: {}", status));
+        }
+
+        response
+            .json()
+            .await
+            .context("Failed to parse YouTube API response")
+    }
+
+    /// Search YouTube for videos matching the query, best match first
+    pub async fn _search_candidates(&self, query: &str) -> anyhow::Result<Vec<TrackValueResponse>> {
+        // Cache on the normalized query, but search with what the user typed
+        let normalized = normalize_query(query);
+        if let Some(cached) = self.search_cache.get(&normalized) {
+            return Ok(cached);
+        }
+        let cache_key = format!("youtube:search:{}", normalized);
+        if let Some(cached) = cache::get_json::<Vec<TrackValueResponse>>(&cache_key).await {
+            self.search_cache.insert(normalized, cached.clone());
+            return Ok(cached);
+        }
+
+        let data = self
+            .youtube_get(&self.search_api_url, &[
+                ("part", "snippet"),
+                ("type", "video"),
+                ("maxResults", "5"),
+                ("q", query),
+            ])
+            .await?;
+
+        let candidates: Vec<TrackValueResponse> = data
+            .get("items")
+            .and_then(|i| i.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter(|item| item["id"]["videoId"].is_string())
+                    .map(|item| {
+                        let snippet = &item["snippet"];
+                        TrackValueResponse {
+                            video_id: item["id"]["videoId"].as_str().unwrap_or_default().to_string(),
+                            title: snippet["title"].as_str().unwrap_or_default().to_string(),
+                            channel_title: snippet["channelTitle"].as_str().unwrap_or_default().to_string(),
+                            thumbnail_url: snippet["thumbnails"]["high"]["url"]
+                                .as_str()
+                                .map(|u| u.to_string()),
+                            duration_ms: None,
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if !candidates.is_empty() {
+            cache::set_json(&cache_key, &candidates, SEARCH_CACHE_TTL_SECS).await;
+            self.search_cache.insert(normalized, candidates.clone());
+        }
+        Ok(candidates)
+    }
+
+    /// Search YouTube for the best video matching the query
+    pub async fn _search_song(&self, query: &str) -> anyhow::Result<TrackValueResponse> {
+        audio_source::search_best(self, query).await
+    }
+
+    /// Look up video durations (in ms) via the `videos` endpoint
+    async fn get_durations(&self, video_ids: &[String]) -> anyhow::Result<HashMap<String, i64>> {
+        let ids = video_ids.join(",");
+        let data = self
+            .youtube_get(&self.videos_api_url, &[("part", "contentDetails"), ("id", ids.as_str())])
+            .await?;
+
+        Ok(data
+            .get("items")
+            .and_then(|i| i.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        let id = item["id"].as_str()?;
+                        let duration = parse_iso8601_duration_ms(item["contentDetails"]["duration"].as_str()?)?;
+                        Some((id.to_string(), duration))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Search YouTube and prefer the candidate whose duration is within
+    /// `DURATION_MATCH_TOLERANCE_MS` of the target, so extended mixes and
+    /// remixes don't win over the original. Falls back to the top result.
+    pub async fn resolve_best_match(
+        &self,
+        query: &str,
+        target_duration_ms: i64,
+    ) -> anyhow::Result<TrackValueResponse> {
+        let mut candidates = self._search_candidates(query).await?;
+        if candidates.is_empty() {
+            return Err(SongError::NoResults(query.to_string()).into());
+        }
+
+        let ids: Vec<String> = candidates.iter().map(|c| c.video_id.clone()).collect();
+        match self.get_durations(&ids).await {
+            Ok(durations) => {
+                for candidate in candidates.iter_mut() {
+                    candidate.duration_ms = durations.get(&candidate.video_id).copied();
+                }
+            }
+            Err(e) => warn!("Failed to fetch YouTube durations, using top result: {}", e),
+        }
+
+        let best = candidates
+            .iter()
+            .position(|c| {
+                c.duration_ms
+                    .is_some_and(|d| (d - target_duration_ms).abs() <= DURATION_MATCH_TOLERANCE_MS)
+            })
+            .unwrap_or(0);
+
+        Ok(candidates.swap_remove(best))
+    }
+
+    /// Resolve the direct audio stream URL for a video with yt-dlp
+    async fn _get_stream_static(&self, video_id: &str) -> anyhow::Result<String> {
+        let video_url = self.video_url(video_id);
+        // -g: print the direct URL instead of downloading
+        let command = ytdlp_command(&["-f", "bestaudio", "-g", &video_url])?;
+
+        let timeout_secs = SECRET_MANAGER.get("YTDLP_TIMEOUT_SECS").parse::<u64>().unwrap_or(30);
+        let output = run_killable(command, Duration::from_secs(timeout_secs)).await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("yt-dlp failed: {}", stderr.trim()));
+        }
+
+        let url = String::from_utf8(output.stdout)?.trim().to_string();
+        if url.is_empty() {
+            return Err(anyhow!("yt-dlp returned no stream URL"));
+        }
+        Ok(url)
+    }
+
+    /// Resolve a stream URL, reusing a cached one
+    pub async fn get_stream_url(&self, video_id: &str) -> anyhow::Result<String> {
+        if let Some(cached) = cache::get_json(&stream_cache_key(video_id)).await {
+            return Ok(cached);
+        }
+        self.extract_stream_url(video_id).await
+    }
+
+    /// Re-extract a stream URL that stopped working, see `reusable_stream_url`
+    pub async fn refresh_stream_url(&self, video_id: &str, stale_url: Option<&str>) -> anyhow::Result<String> {
+        if let Some(cached) = reusable_stream_url(cache::get_json(&stream_cache_key(video_id)).await, stale_url) {
+            return Ok(cached);
+        }
+        self.extract_stream_url(video_id).await
+    }
+
+    /// Run yt-dlp for a stream URL and cache it
+    async fn extract_stream_url(&self, video_id: &str) -> anyhow::Result<String> {
+        let stream_url = self
+            .stream_extractions
+            .run(video_id, || async { self._get_stream_static(video_id).await.map_err(Arc::new) })
+            .await
+            .map_err(unshare_error)?;
+        cache::set_json(&stream_cache_key(video_id), &stream_url, STREAM_CACHE_TTL_SECS).await;
+        Ok(stream_url)
+    }
+
+    /// `dump_info_json`, shared with any identical extraction in flight
+    async fn info_json(&self, video_id: &str) -> anyhow::Result<serde_json::Value> {
+        self.info_extractions
+            .run(video_id, || async { self.dump_info_json(video_id).await.map_err(Arc::new) })
+            .await
+            .map_err(unshare_error)
+    }
+
+    /// yt-dlp subprocesses currently running for stream or info extraction
+    pub fn extractions_in_flight(&self) -> usize {
+        self.stream_extractions.in_flight() + self.info_extractions.in_flight()
+    }
+
+    /// Run `yt-dlp -J --skip-download` and parse the info JSON
+    async fn dump_info_json(&self, video_id: &str) -> anyhow::Result<serde_json::Value> {
+        let video_url = self.video_url(video_id);
+        // -J: dump info JSON
+        let command = ytdlp_command(&["-J", "--skip-download", "--no-playlist", &video_url])?;
+
+        let timeout_secs = SECRET_MANAGER.get("YTDLP_TIMEOUT_SECS").parse::<u64>().unwrap_or(30);
+        let output = run_killable(command, Duration::from_secs(timeout_secs)).await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("yt-dlp failed: {}", stderr.trim()));
+        }
+
+        serde_json::from_slice(&output.stdout).context("Failed to parse yt-dlp output")
+    }
+
+    /// Fetch video metadata with `yt-dlp -J --skip-download`, without resolving
+    /// a stream. Live streams are rejected since they can't be mixed.
+    pub async fn get_song_metadata(&self, video_id: &str) -> anyhow::Result<SongMetadata> {
+        let info = self.info_json(video_id).await?;
+        let metadata = SongMetadata::from_info_json(&info)
+            .ok_or_else(|| anyhow!("yt-dlp output is missing the video id"))?;
+
+        if metadata.is_live {
+            return Err(SongError::LiveStream.into());
+        }
+        Ok(metadata)
+    }
+
+    /// Search YouTube and list every audio-only stream of the top result from
+    /// a single extraction
+    pub async fn get_song_formats(&self, query: &str) -> anyhow::Result<TrackFormats> {
+        let result = self._search_song(query).await?;
+        let info = self.info_json(&result.video_id).await?;
+        if info.get("is_live").and_then(|l| l.as_bool()).unwrap_or(false) {
+            return Err(SongError::LiveStream.into());
+        }
+
+        let formats = AudioFormat::list_from_info_json(&info);
+        if formats.is_empty() {
+            return Err(anyhow!("yt-dlp returned no audio-only formats"));
+        }
+
+        Ok(TrackFormats {
+            video_id: result.video_id,
+            title: result.title,
+            channel_title: result.channel_title,
+            thumbnail_url: result.thumbnail_url,
+            formats,
+        })
+    }
+}
+
+impl AudioSource for SongController {
+    async fn search(&self, query: &str) -> anyhow::Result<Vec<TrackValueResponse>> {
+        self._search_candidates(query).await
+    }
+
+    async fn resolve_stream(&self, id: &str) -> anyhow::Result<StreamInfo> {
+        Ok(StreamInfo {
+            id: id.to_string(),
+            stream_url: self.get_stream_url(id).await?,
+        })
+    }
+}
+
+/// Split an argument string the way a POSIX shell would (whitespace, single
+/// and double quotes, backslash escapes) without ever invoking one
+pub fn split_shell_args(input: &str) -> anyhow::Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(anyhow!("unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => current.push(c),
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => return Err(anyhow!("unterminated double quote")),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(anyhow!("unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => current.push(c),
+                    None => return Err(anyhow!("trailing backslash")),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+/// `program` with `args`, then `extra_args` appended in order
+fn build_command(program: &str, args: &[&str], extra_args: &[String]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args).args(extra_args);
+    command
+}
+
+/// A yt-dlp invocation using `YTDLP_PATH`, with `YTDLP_EXTRA_ARGS` appended
+fn ytdlp_command(args: &[&str]) -> anyhow::Result<Command> {
+    let extra_args = split_shell_args(&SECRET_MANAGER.get("YTDLP_EXTRA_ARGS"))
+        .context("Invalid YTDLP_EXTRA_ARGS")?;
+    Ok(build_command(&SECRET_MANAGER.get("YTDLP_PATH"), args, &extra_args))
+}
+
+/// Run a command to completion within `timeout`. The child is killed if the
+/// returned future is dropped, so a client disconnect (which drops the axum
+/// handler) or hitting the timeout aborts it.
+async fn run_killable(mut command: Command, timeout: Duration) -> anyhow::Result<std::process::Output> {
+    command
+        .kill_on_drop(true)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    match tokio::time::timeout(timeout, command.output()).await {
+        Ok(output) => output.context("Failed to run yt-dlp"),
+        Err(_) => Err(SongError::ExtractionTimeout(timeout.as_secs()).into()),
+    }
+}
+
+// Singleton instance
+pub static SONG_CONTROLLER: Lazy<SongController> = Lazy::new(SongController::new);
+
+// Route handlers
+
+/// Map a song lookup failure to a response, using `fallback` for errors
+/// that aren't a `SongError`
+pub fn song_api_error(error: &anyhow::Error, fallback: StatusCode) -> ApiError {
+    match error.downcast_ref::<SongError>() {
+        Some(SongError::QuotaExceeded) => {
+            let retry_after = seconds_until_quota_reset(Utc::now());
+            ApiError::service_unavailable(error.to_string())
+                .with_code("youtube_quota_exceeded")
+                .with_details(serde_json::json!({"retry_after": retry_after}))
+                .with_retry_after(retry_after)
+        }
+        Some(SongError::ExtractionTimeout(_)) => {
+            ApiError::new(StatusCode::GATEWAY_TIMEOUT, error.to_string())
+        }
+        Some(SongError::LiveStream) => {
+            ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, error.to_string())
+        }
+        Some(SongError::NoResults(query)) => {
+            ApiError::not_found(error.to_string()).with_details(serde_json::json!({"query": query}))
+        }
+        None => ApiError::new(fallback, error.to_string()),
+    }
+}
+
+/// GET /song/info - Search an audio source (`?source=`, YouTube by default)
+/// and resolve a playable stream
+pub async fn song_info_route(
+    State(_database): State<Database>,
+    Query(params): Query<SongQuery>,
+) -> Result<Json<Track>, ApiError> {
+    let track = match SourceKind::from_param(params.source.as_deref())? {
+        SourceKind::Youtube => audio_source::find_track(&*SONG_CONTROLLER, &params.q).await,
+    }
+    .map_err(|e| {
+        error!("Failed to get song data for '{}': {}", params.q, e);
+        song_api_error(&e, StatusCode::BAD_REQUEST)
+    })?;
+
+    info!("Resolved song '{}' to video {}", params.q, track.video_id);
+    Ok(Json(track))
+}
+
+/// POST /song/info - Like GET, but from a structured artist and title, with
+/// `duration_ms` picking between candidates (e.g. original over extended mix)
+pub async fn song_info_match_route(
+    State(_database): State<Database>,
+    Json(body): Json<SongInfoRequest>,
+) -> Result<Json<Track>, ApiError> {
+    let query = body.query()?;
+    let track = match SourceKind::from_param(body.source.as_deref())? {
+        SourceKind::Youtube => async {
+            let result = match body.duration_ms {
+                Some(duration_ms) => SONG_CONTROLLER.resolve_best_match(&query, duration_ms).await?,
+                None => audio_source::search_best(&*SONG_CONTROLLER, &query).await?,
+            };
+            let stream = SONG_CONTROLLER.resolve_stream(&result.video_id).await?;
+            anyhow::Ok(Track::from_search(result, stream.stream_url))
+        }
+        .await,
+    }
+    .map_err(|e| {
+        error!("Failed to get song data for '{}': {}", query, e);
+        song_api_error(&e, StatusCode::BAD_REQUEST)
+    })?;
+
+    info!("Resolved song '{}' to video {}", query, track.video_id);
+    Ok(Json(track))
+}
+
+/// GET /song/search - Best YouTube match for a query, without stream extraction
+pub async fn song_search_route(
+    State(_database): State<Database>,
+    Query(params): Query<SongQuery>,
+) -> Result<Json<TrackValueResponse>, ApiError> {
+    let result = match SourceKind::from_param(params.source.as_deref())? {
+        SourceKind::Youtube => audio_source::search_best(&*SONG_CONTROLLER, &params.q).await,
+    }
+    .map_err(|e| {
+        error!("Failed to search for '{}': {}", params.q, e);
+        song_api_error(&e, StatusCode::BAD_REQUEST)
+    })?;
+
+    Ok(Json(result))
+}
+
+/// GET /song/formats - Like /song/info, but with every audio-only stream
+/// (m4a, opus, ...) sorted by bitrate
+pub async fn song_formats_route(
+    State(_database): State<Database>,
+    Query(params): Query<SongQuery>,
+) -> Result<Json<TrackFormats>, ApiError> {
+    let track = match SourceKind::from_param(params.source.as_deref())? {
+        SourceKind::Youtube => SONG_CONTROLLER.get_song_formats(&params.q).await,
+    }
+    .map_err(|e| {
+        error!("Failed to get song formats for '{}': {}", params.q, e);
+        song_api_error(&e, StatusCode::BAD_REQUEST)
+    })?;
+
+    info!("Resolved {} formats for '{}' ({})", track.formats.len(), params.q, track.video_id);
+    Ok(Json(track))
+}
+
+/// POST /song/refresh-stream - Re-resolve a stream URL that stopped working
+pub async fn song_refresh_stream_route(
+    State(_database): State<Database>,
+    Json(body): Json<RefreshStreamRequest>,
+) -> Result<Json<RefreshStreamResponse>, ApiError> {
+    let stream_url = match SourceKind::from_param(body.source.as_deref())? {
+        SourceKind::Youtube => {
+            SONG_CONTROLLER
+                .refresh_stream_url(&body.video_id, body.stale_url.as_deref())
+                .await
+        }
+    }
+    .map_err(|e| {
+        error!("Failed to refresh stream for {}: {}", body.video_id, e);
+        song_api_error(&e, StatusCode::BAD_REQUEST)
+    })?;
+
+    Ok(Json(RefreshStreamResponse {
+        video_id: body.video_id,
+        stream_url,
+    }))
+}
+
+/// GET /song/metadata - Video duration, uploader and view count without stream extraction
+pub async fn song_metadata_route(
+    State(_database): State<Database>,
+    Query(params): Query<VideoIdQuery>,
+) -> Result<Json<SongMetadata>, ApiError> {
+    let metadata = match SourceKind::from_param(params.source.as_deref())? {
+        SourceKind::Youtube => SONG_CONTROLLER.get_song_metadata(&params.video_id).await,
+    }
+    .map_err(|e| {
+        error!("Failed to get song metadata: {}", e);
+        song_api_error(&e, StatusCode::BAD_REQUEST)
+    })?;
+
+    Ok(Json(metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn song_info_request_builds_an_artist_title_query() {
+        let request = |artist: &str, title: &str, duration_ms| SongInfoRequest {
+            artist: artist.to_string(),
+            title: title.to_string(),
+            duration_ms,
+            source: None,
+        };
+        assert_eq!(request(" Daft Punk ", "One More Time", None).query().unwrap(), "Daft Punk - One More Time");
+        assert!(request("Daft Punk", "  ", None).query().is_err());
+        assert!(request("", "One More Time", Some(320_000)).query().is_err());
+        assert!(request("Daft Punk", "One More Time", Some(0)).query().is_err());
+    }
+
+    #[test]
+    fn normalize_query_maps_variants_to_one_key() {
+        let expected = "daft punk one more time";
+        for variant in [
+            "Daft Punk - One More Time",
+            "daft punk one more time",
+            "  DAFT   PUNK  one more time ",
+            "Daft Punk: One More Time!",
+            "daft-punk, one more time?",
+        ] {
+            assert_eq!(normalize_query(variant), expected, "variant: {:?}", variant);
+        }
+    }
+
+    #[test]
+    fn normalize_query_drops_apostrophes_without_splitting_words() {
+        assert_eq!(normalize_query("Don't Stop Me Now"), "dont stop me now");
+        assert_eq!(normalize_query("Don’t Stop Me Now"), "dont stop me now");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn dropping_the_future_kills_the_child() {
+        let pid_file = std::env::temp_dir().join(format!("song-test-{}.pid", uuid::Uuid::new_v4()));
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("echo $$ > {}; exec sleep 30", pid_file.display()));
+
+        // Simulates axum dropping the handler future when the client disconnects
+        let result = tokio::time::timeout(
+            Duration::from_millis(300),
+            run_killable(command, Duration::from_secs(60)),
+        )
+        .await;
+        assert!(result.is_err(), "command should still have been running");
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+        let _ = std::fs::remove_file(&pid_file);
+
+        // Give the kill a moment to land; a reaped or zombie process no longer runs
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let state = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|stat| stat.rsplit(')').next().map(|rest| rest.trim().chars().next()))
+            .flatten();
+        assert!(matches!(state, None | Some('Z') | Some('X')), "child still running: {:?}", state);
+    }
+
+    #[tokio::test]
+    async fn slow_extraction_times_out() {
+        let mut command = Command::new("sleep");
+        command.arg("30");
+
+        let started = std::time::Instant::now();
+        let err = run_killable(command, Duration::from_millis(200)).await.unwrap_err();
+
+        assert!(matches!(err.downcast_ref::<SongError>(), Some(SongError::ExtractionTimeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn lists_audio_only_formats_by_bitrate() {
+        let info = serde_json::json!({"formats": [
+            {"format_id": "140", "url": "https://a/140", "ext": "m4a", "acodec": "mp4a.40.2", "vcodec": "none", "abr": 129.5, "filesize": 3_000_000},
+            {"format_id": "18", "url": "https://a/18", "ext": "mp4", "acodec": "mp4a.40.2", "vcodec": "avc1", "abr": 96.0},
+            {"format_id": "251", "url": "https://a/251", "ext": "webm", "acodec": "opus", "vcodec": "none", "abr": 160.0, "filesize_approx": 3_500_000},
+            {"format_id": "sb0", "url": "https://a/sb0", "ext": "mhtml", "acodec": "none", "vcodec": "none"},
+        ]});
+
+        let formats = AudioFormat::list_from_info_json(&info);
+        let ids: Vec<&str> = formats.iter().map(|f| f.format_id.as_str()).collect();
+        assert_eq!(ids, ["251", "140"]);
+        assert_eq!(formats[0].filesize, Some(3_500_000));
+        assert_eq!(formats[1].ext, "m4a");
+    }
+
+    #[test]
+    fn no_results_is_not_found_not_bad_request() {
+        use axum::response::IntoResponse;
+
+        let err: anyhow::Error = SongError::NoResults("zzqx".to_string()).into();
+        let response = song_api_error(&err, StatusCode::BAD_REQUEST).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let bad_request = song_api_error(&anyhow!("yt-dlp failed"), StatusCode::BAD_REQUEST).into_response();
+        assert_eq!(bad_request.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn talks_to_injected_youtube_urls() {
+        use axum::{Router, extract::Query, response::IntoResponse, routing::get};
+
+        async fn search(Query(params): Query<HashMap<String, String>>) -> axum::response::Response {
+            match params.get("key").map(String::as_str) {
+                Some("spent") => (
+                    axum::http::StatusCode::FORBIDDEN,
+                    r#"{"error":{"errors":[{"reason":"quotaExceeded"}]}}"#,
+                )
+                    .into_response(),
+                _ => axum::Json(serde_json::json!({"items": [{"id": {"videoId": "abc"}}]})).into_response(),
+            }
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, Router::new().route("/search", get(search))).await.unwrap()
+        });
+
+        let youtube = SongController::with_urls(
+            &format!("{}/search", base),
+            &format!("{}/videos", base),
+            "https://yt.example/watch",
+        );
+        assert_eq!(youtube.video_url("abc"), "https://yt.example/watch?v=abc");
+
+        let data = youtube.get_with_key(&youtube.search_api_url, &[("q", "x")], "good").await.unwrap();
+        assert_eq!(data["items"][0]["id"]["videoId"], "abc");
+
+        let err = youtube.get_with_key(&youtube.search_api_url, &[("q", "x")], "spent").await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<SongError>(), Some(SongError::QuotaExceeded)));
+    }
+
+    #[tokio::test]
+    async fn search_hits_the_in_process_cache_first() {
+        // Unreachable, so anything but a cache hit fails
+        let youtube = SongController::with_urls("http://127.0.0.1:1/search", "http://127.0.0.1:1/videos", "");
+        let cached = TrackValueResponse {
+            video_id: "FGBhQbmPwH8".to_string(),
+            title: "Daft Punk - One More Time".to_string(),
+            channel_title: "Daft Punk".to_string(),
+            thumbnail_url: None,
+            duration_ms: None,
+        };
+        youtube.search_cache.insert(normalize_query("Daft Punk - One More Time"), vec![cached]);
+
+        let candidates = youtube._search_candidates("daft punk: one more time").await.unwrap();
+        assert_eq!(candidates[0].video_id, "FGBhQbmPwH8");
+        assert!(youtube._search_candidates("something else").await.is_err());
+    }
+
+    #[test]
+    fn parses_youtube_durations() {
+        assert_eq!(parse_iso8601_duration_ms("PT3M45S"), Some(225_000));
+        assert_eq!(parse_iso8601_duration_ms("PT1H2M3S"), Some(3_723_000));
+        assert_eq!(parse_iso8601_duration_ms("PT45S"), Some(45_000));
+        assert_eq!(parse_iso8601_duration_ms("P1DT1S"), Some(86_401_000));
+        assert_eq!(parse_iso8601_duration_ms("PT0S"), Some(0));
+        assert_eq!(parse_iso8601_duration_ms("3M45S"), None);
+        assert_eq!(parse_iso8601_duration_ms("PT3X"), None);
+    }
+
+    #[test]
+    fn splits_extra_args_shell_style() {
+        let args = split_shell_args(r#"--cookies '/etc/yt dlp/cookies.txt' --proxy "socks5://a b" x\ y"#).unwrap();
+        assert_eq!(args, ["--cookies", "/etc/yt dlp/cookies.txt", "--proxy", "socks5://a b", "x y"]);
+        assert!(split_shell_args("").unwrap().is_empty());
+        assert!(split_shell_args("--cookies 'unterminated").is_err());
+        assert_eq!(split_shell_args("a; rm -rf /").unwrap(), ["a;", "rm", "-rf", "/"]);
+    }
+
+    #[test]
+    fn extra_args_are_appended_in_order() {
+        let extra = split_shell_args("--proxy socks5://localhost:1080 --cookies c.txt").unwrap();
+        let command = build_command("/opt/bin/yt-dlp", &["-f", "bestaudio", "-g", "URL"], &extra);
+
+        assert_eq!(command.as_std().get_program(), "/opt/bin/yt-dlp");
+        let args: Vec<_> = command.as_std().get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args,
+            ["-f", "bestaudio", "-g", "URL", "--proxy", "socks5://localhost:1080", "--cookies", "c.txt"]
+        );
+    }
+
+    #[test]
+    fn normalize_query_keeps_non_ascii_letters() {
+        assert_eq!(normalize_query("Beyoncé — Halo"), "beyoncé halo");
+    }
+
+    #[test]
+    fn refreshes_reuse_only_a_cached_stream_other_than_the_stale_one() {
+        let cached = || Some("https://rr1.googlevideo.com/a".to_string());
+        // Without a stale URL the cached one may be the expired one
+        assert_eq!(reusable_stream_url(cached(), None), None);
+        assert_eq!(reusable_stream_url(cached(), Some("https://rr1.googlevideo.com/a")), None);
+        assert_eq!(reusable_stream_url(cached(), Some("https://rr1.googlevideo.com/old")), cached());
+        assert_eq!(reusable_stream_url(None, Some("https://rr1.googlevideo.com/a")), None);
+
+        let request: RefreshStreamRequest = serde_json::from_str(r#"{"video_id":"tKi9Z-f6qX4"}"#).unwrap();
+        assert_eq!(request.stale_url, None);
+    }
+
+    #[test]
+    fn detects_quota_errors_by_reason() {
+        let quota = r#"{"error":{"code":403,"errors":[{"reason":"quotaExceeded","domain":"youtube.quota"}]}}"#;
+        assert!(is_quota_exceeded(quota));
+
+        let forbidden = r#"{"error":{"code":403,"errors":[{"reason":"forbidden","message":"not quotaExceeded"}]}}"#;
+        assert!(!is_quota_exceeded(forbidden));
+        assert!(!is_quota_exceeded("quotaExceeded"));
+        assert!(!is_quota_exceeded(""));
+    }
+
+    #[test]
+    fn follows_pacific_daylight_saving() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        assert_eq!(pacific_offset_hours(at("2026-01-15T12:00:00Z")), -8);
+        assert_eq!(pacific_offset_hours(at("2026-07-15T12:00:00Z")), -7);
+
+        // 2am local on the second Sunday of March and the first Sunday of November
+        assert_eq!(pacific_offset_hours(at("2026-03-08T09:59:59Z")), -8);
+        assert_eq!(pacific_offset_hours(at("2026-03-08T10:00:00Z")), -7);
+        assert_eq!(pacific_offset_hours(at("2026-11-01T08:59:59Z")), -7);
+        assert_eq!(pacific_offset_hours(at("2026-11-01T09:00:00Z")), -8);
+    }
+
+    #[test]
+    fn counts_down_to_pacific_midnight() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        // Noon local, in winter and in summer
+        assert_eq!(seconds_until_quota_reset(at("2026-01-15T20:00:00Z")), 12 * 3600);
+        assert_eq!(seconds_until_quota_reset(at("2026-07-15T19:00:00Z")), 12 * 3600);
+        // A second before midnight local
+        assert_eq!(seconds_until_quota_reset(at("2026-01-16T07:59:59Z")), 1);
+    }
+}
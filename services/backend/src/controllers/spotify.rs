@@ -1,31 +1,116 @@
 // Spotify OAuth and API controller
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::{IntoResponse, Json, Redirect},
+    response::{IntoResponse, Json, Redirect, Response},
 };
 use once_cell::sync::Lazy;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use futures_util::future::join_all;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{error, info};
-
+use tokio::sync::{RwLock, Semaphore};
+use tracing::{error, info, warn};
+
+use crate::audio_source::AudioSource;
+use crate::audio_profile::{build_profile, AudioProfile, AudioProfileRequest, MAX_AUDIO_PROFILE_TRACKS};
+use crate::audio_proxy;
+use crate::cache;
+use crate::enrich;
+use crate::controllers::song::{song_api_error, DURATION_MATCH_TOLERANCE_MS, SONG_CONTROLLER};
+use crate::error::ApiError;
+use crate::pagination::Pagination;
+use crate::models::mix::CreateTrackRequest;
+use crate::models::song::TrackValueResponse;
+use crate::models::spotify::{is_valid_spotify_id, SearchResult};
+use crate::models::track::UnifiedTrack;
 use crate::secrets::SECRET_MANAGER;
+use crate::singleflight::SingleFlight;
 use crate::db::Database;
 
 /// Spotify OAuth token storage (in production, use Redis)
 pub static TOKEN_STORE: Lazy<Arc<RwLock<HashMap<String, SpotifyTokens>>>> =
     Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
 
-/// Spotify API endpoints
-const SPOTIFY_AUTH_URL: &str = "https://accounts.spotify.com/authorize";
-const SPOTIFY_TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
-const SPOTIFY_API_URL: &str = "https://api.spotify.com/v1";
+/// Error returned when Spotify answers 429
+pub const RATE_LIMITED: &str = "Spotify rate limit reached";
+
+/// Redis cache for the recommendation genre seeds
+const GENRE_SEEDS_CACHE_KEY: &str = "spotify:genre_seeds";
+const GENRE_SEEDS_CACHE_TTL_SECS: u64 = 60 * 60 * 24;
+
+/// Browse shelves change a few times a day; a short cache keeps the "what's
+/// hot" screen from hitting Spotify on every load
+const BROWSE_CACHE_TTL_SECS: u64 = 60 * 10;
+
+/// Profiles rarely change, so UIs showing the avatar on every page can be
+/// served from cache; `?refresh=true` bypasses it
+const USER_PROFILE_CACHE_TTL_SECS: u64 = 60 * 5;
+
+/// Album art is immutable per URL, so cache it for a week server-side and
+/// let clients keep it for a year
+const ARTWORK_CACHE_TTL_SECS: u64 = 60 * 60 * 24 * 7;
+const ARTWORK_MAX_BYTES: usize = 5 * 1024 * 1024;
+
+/// Spotify OAuth scopes required for full functionality; the default for the
+/// `SPOTIFY_SCOPES` secret and the fallback when it's invalid
+pub const DEFAULT_SPOTIFY_SCOPES: &str = "user-read-private user-read-email streaming user-library-read user-top-read playlist-read-private user-read-playback-state user-modify-playback-state";
+
+/// Every scope Spotify's Web API accepts
+const KNOWN_SPOTIFY_SCOPES: &[&str] = &[
+    "ugc-image-upload",
+    "user-read-playback-state",
+    "user-modify-playback-state",
+    "user-read-currently-playing",
+    "app-remote-control",
+    "streaming",
+    "playlist-read-private",
+    "playlist-read-collaborative",
+    "playlist-modify-private",
+    "playlist-modify-public",
+    "user-follow-modify",
+    "user-follow-read",
+    "user-read-playback-position",
+    "user-top-read",
+    "user-read-recently-played",
+    "user-library-modify",
+    "user-library-read",
+    "user-read-email",
+    "user-read-private",
+];
+
+/// Normalize a space-separated scope list, rejecting unknown scopes and an
+/// empty list
+pub fn parse_scopes(raw: &str) -> Result<String, String> {
+    let scopes: Vec<&str> = raw.split_whitespace().collect();
+    if scopes.is_empty() {
+        return Err("no Spotify scopes given".to_string());
+    }
+    let unknown: Vec<&str> = scopes
+        .iter()
+        .copied()
+        .filter(|scope| !KNOWN_SPOTIFY_SCOPES.contains(scope))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!("unknown Spotify scopes: {}", unknown.join(", ")));
+    }
+    Ok(scopes.join(" "))
+}
+
+/// Scopes from `SPOTIFY_SCOPES`, falling back to the defaults if it names
+/// a scope Spotify doesn't know
+static SPOTIFY_SCOPES: Lazy<String> = Lazy::new(|| {
+    parse_scopes(&SECRET_MANAGER.get("SPOTIFY_SCOPES")).unwrap_or_else(|e| {
+        warn!("Ignoring SPOTIFY_SCOPES ({}); using the defaults", e);
+        DEFAULT_SPOTIFY_SCOPES.to_string()
+    })
+});
 
-/// Spotify OAuth scopes required for full functionality
-const SPOTIFY_SCOPES: &str = "user-read-private user-read-email streaming user-library-read user-top-read playlist-read-private";
+/// Error returned when a player command needs Spotify Premium
+const PREMIUM_REQUIRED: &str = "Spotify Premium required";
+const NO_ACTIVE_DEVICE: &str = "No active device";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpotifyTokens {
@@ -35,6 +120,27 @@ pub struct SpotifyTokens {
     pub token_type: String,
     #[serde(default)]
     pub scope: String,
+    /// When the tokens were issued (unix seconds), used to expire idle sessions
+    #[serde(default = "unix_now", skip_serializing)]
+    pub obtained_at: i64,
+}
+
+/// Refresh tokens (the shared app token, or a user's on validation) this
+/// long before Spotify expires them
+const TOKEN_REFRESH_MARGIN_SECS: i64 = 60;
+
+impl SpotifyTokens {
+    /// Seconds of validity left at `now` (unix seconds)
+    fn remaining_secs(&self, now: i64) -> i64 {
+        self.obtained_at + self.expires_in - now
+    }
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,12 +155,6 @@ pub struct RefreshTokenQuery {
     pub session_id: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct AuthUrlResponse {
-    pub auth_url: String,
-    pub state: String,
-}
-
 #[derive(Debug, Serialize)]
 pub struct TokenResponse {
     pub access_token: String,
@@ -68,6 +168,7 @@ pub struct SpotifyUser {
     pub email: Option<String>,
     pub images: Vec<SpotifyImage>,
     pub product: Option<String>, // "premium", "free", etc.
+    pub country: Option<String>, // ISO 3166-1 alpha-2, needs user-read-private
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -77,45 +178,84 @@ pub struct SpotifyImage {
     pub width: Option<i32>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct AudioFeatures {
-    pub id: String,
-    pub tempo: f64,
-    pub key: i32,
-    pub mode: i32, // 0 = minor, 1 = major
-    pub energy: f64,
-    pub danceability: f64,
-    pub valence: f64,
-    pub acousticness: f64,
-    pub instrumentalness: f64,
-    pub loudness: f64,
-    pub speechiness: f64,
-    pub duration_ms: i64,
-    pub time_signature: i32,
-}
-
 #[derive(Debug, Deserialize)]
 pub struct SearchQuery {
     pub q: String,
     #[serde(default = "default_search_type")]
     pub search_type: String,
-    #[serde(default = "default_limit")]
-    pub limit: i32,
+    /// Set to false to drop explicit tracks; defaults to the session profile, then true
+    pub explicit_ok: Option<bool>,
+    /// Session whose profile supplies the explicit filter default
+    pub session_id: Option<String>,
+    /// ISO country code; defaults to the user's profile country
+    pub market: Option<String>,
 }
 
 fn default_search_type() -> String {
     "track".to_string()
 }
 
-fn default_limit() -> i32 {
-    20
-}
-
 #[derive(Debug, Deserialize)]
 pub struct AudioFeaturesQuery {
     pub ids: String, // Comma-separated track IDs
 }
 
+#[derive(Debug, Serialize)]
+pub struct SavedTracksResponse {
+    pub items: Vec<SearchResult>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnrichTracksRequest {
+    pub spotify_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrackToYoutubeQuery {
+    pub track_id: String,
+    /// ISO country code; defaults to the user's profile country
+    pub market: Option<String>,
+    /// Also extract a playable stream URL (slow: runs yt-dlp)
+    #[serde(default)]
+    pub stream: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackToYoutubeResponse {
+    pub spotify: SearchResult,
+    pub youtube: TrackValueResponse,
+    /// Whether the YouTube video's duration is within tolerance of the Spotify track
+    pub duration_matched: bool,
+    /// Both halves merged for the player
+    pub track: UnifiedTrack,
+}
+
+/// Ordered Spotify ids of a planned mix to resolve to YouTube videos
+#[derive(Debug, Deserialize)]
+pub struct ResolveYoutubeRequest {
+    pub spotify_ids: Vec<String>,
+}
+
+/// Outcome for one track of a bulk YouTube resolution
+#[derive(Debug, Serialize)]
+pub struct YoutubeResolution {
+    pub spotify_id: String,
+    pub resolved: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<TrackToYoutubeResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Tracks resolved at once; each one costs YouTube API quota
+pub const YOUTUBE_RESOLVE_CONCURRENCY: usize = 4;
+
+/// Largest mix `resolve_tracks_to_youtube` accepts in one request
+pub const MAX_YOUTUBE_RESOLVE_TRACKS: usize = 100;
+
 #[derive(Debug, Deserialize)]
 pub struct RecommendationsQuery {
     pub seed_tracks: Option<String>,
@@ -123,17 +263,278 @@ pub struct RecommendationsQuery {
     pub seed_genres: Option<String>,
     pub target_tempo: Option<f64>,
     pub target_energy: Option<f64>,
-    pub limit: Option<i32>,
+    /// Drop results whose actual tempo falls outside this window, since
+    /// `target_tempo` is only a soft preference for Spotify
+    pub min_bpm: Option<f64>,
+    pub max_bpm: Option<f64>,
+    /// Shorthand for a `target_tempo ± bpm_tolerance` window
+    pub bpm_tolerance: Option<f64>,
+    /// Set to false to drop explicit tracks; overrides the session profile
+    pub explicit_ok: Option<bool>,
+    /// Session whose profile fills in seeds, energy and the explicit filter
+    pub session_id: Option<String>,
+    /// ISO country code; defaults to the user's profile country
+    pub market: Option<String>,
+}
+
+/// Comma-separated seeds and tunable targets for `/recommendations`
+#[derive(Debug, Default)]
+pub struct RecommendationSeeds {
+    pub seed_tracks: Option<String>,
+    pub seed_artists: Option<String>,
+    pub seed_genres: Option<String>,
+    pub target_tempo: Option<f64>,
+    pub target_energy: Option<f64>,
+    pub target_valence: Option<f64>,
+}
+
+/// Spotify accepts at most five seeds across tracks, artists and genres
+const MAX_RECOMMENDATION_SEEDS: usize = 5;
+
+impl RecommendationsQuery {
+    /// BPM window to post-filter results with; explicit bounds win over the tolerance
+    fn bpm_window(&self) -> Option<(f64, f64)> {
+        let around_target = self
+            .target_tempo
+            .zip(self.bpm_tolerance)
+            .map(|(tempo, tolerance)| (tempo - tolerance, tempo + tolerance));
+
+        match (self.min_bpm, self.max_bpm, around_target) {
+            (None, None, None) => None,
+            (min, max, around) => Some((
+                min.or(around.map(|(lo, _)| lo)).unwrap_or(0.0),
+                max.or(around.map(|(_, hi)| hi)).unwrap_or(f64::MAX),
+            )),
+        }
+    }
+}
+
+/// Upper bound on the extra audio-features call made for BPM filtering
+const BPM_FILTER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long to wait on the accounts service for an app token
+const APP_TOKEN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpotifyArtist {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    pub popularity: Option<i32>,
+    #[serde(default)]
+    pub images: Vec<SpotifyImage>,
+}
+
+/// A Spotify Connect device the user can play on
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpotifyDevice {
+    /// Missing for some restricted devices
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub device_type: String,
+    pub is_active: bool,
+    #[serde(default)]
+    pub is_restricted: bool,
+    pub volume_percent: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DevicesResponse {
+    pub devices: Vec<SpotifyDevice>,
+}
+
+/// Body of `PUT /spotify/player`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum PlayerCommand {
+    /// Move playback to `device_id`, starting it if `play`
+    Transfer {
+        device_id: String,
+        #[serde(default)]
+        play: bool,
+    },
+    /// Resume, or start `uris` (Spotify track URIs) at `position_ms`
+    Play {
+        device_id: Option<String>,
+        uris: Option<Vec<String>>,
+        position_ms: Option<i64>,
+    },
+    Pause {
+        device_id: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelatedArtistsResponse {
+    pub artists: Vec<SpotifyArtist>,
+}
+
+/// Spotify's album art tiers
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtworkSize {
+    /// 64px
+    Small,
+    /// 300px
+    #[default]
+    Medium,
+    /// 640px
+    Large,
+}
+
+impl ArtworkSize {
+    fn width(self) -> i32 {
+        match self {
+            ArtworkSize::Small => 64,
+            ArtworkSize::Medium => 300,
+            ArtworkSize::Large => 640,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ArtworkSize::Small => "small",
+            ArtworkSize::Medium => "medium",
+            ArtworkSize::Large => "large",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArtworkQuery {
+    #[serde(default)]
+    pub size: ArtworkSize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopArtistsResponse {
+    pub items: Vec<SpotifyArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    /// ISO country code; defaults to the user's profile country
+    pub market: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProfileRecommendationsQuery {
+    pub session_id: String,
+    /// ISO country code; defaults to the user's profile country
+    pub market: Option<String>,
+}
+
+/// Whether a stored Spotify session can still make API calls
+#[derive(Debug, Serialize)]
+pub struct SessionValidation {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenreSeedsResponse {
+    pub genres: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BrowseQuery {
+    /// ISO 3166-1 alpha-2 market, e.g. `GB`
+    pub country: Option<String>,
+}
+
+/// An artist as embedded in albums and tracks
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpotifyArtistRef {
+    pub id: Option<String>,
+    pub name: String,
+}
+
+/// An album on the new-releases shelf
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpotifyAlbum {
+    pub id: String,
+    pub name: String,
+    pub album_type: Option<String>,
+    pub release_date: Option<String>,
+    pub total_tracks: Option<i32>,
+    #[serde(default)]
+    pub artists: Vec<SpotifyArtistRef>,
+    #[serde(default)]
+    pub images: Vec<SpotifyImage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpotifyPlaylistTracks {
+    pub total: i64,
+}
+
+/// A playlist on the featured shelf
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpotifyPlaylist {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub images: Vec<SpotifyImage>,
+    pub tracks: Option<SpotifyPlaylistTracks>,
+}
+
+/// A page of a browse shelf. `message` is Spotify's headline for the
+/// featured playlists (e.g. "Monday morning moods").
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BrowsePage<T> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub items: Vec<T>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct NewReleasesResponse {
+    albums: BrowsePage<SpotifyAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeaturedPlaylistsResponse {
+    message: Option<String>,
+    playlists: BrowsePage<SpotifyPlaylist>,
 }
 
 pub struct SpotifyController {
     client: Client,
+    /// Base of the accounts service (`/authorize`, `/api/token`)
+    accounts_url: String,
+    /// Base of the Web API, including the version
+    api_url: String,
+    /// Client-credentials token shared by every anonymous request
+    app_tokens: RwLock<Option<SpotifyTokens>>,
+    /// Concurrent callers finding the app token stale wait on one refresh
+    /// rather than each fetching their own
+    app_token_refresh: SingleFlight<Result<SpotifyTokens, String>>,
 }
 
 impl SpotifyController {
     pub fn new() -> Self {
+        Self::with_base_urls(
+            &SECRET_MANAGER.get("SPOTIFY_ACCOUNTS_URL"),
+            &SECRET_MANAGER.get("SPOTIFY_API_URL"),
+        )
+    }
+
+    /// A controller talking to the given accounts and Web API bases, e.g. a
+    /// local fake Spotify in tests
+    pub fn with_base_urls(accounts_url: &str, api_url: &str) -> Self {
         Self {
             client: Client::new(),
+            accounts_url: accounts_url.trim_end_matches('/').to_string(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            app_tokens: RwLock::new(None),
+            app_token_refresh: SingleFlight::default(),
         }
     }
 
@@ -143,11 +544,11 @@ impl SpotifyController {
         let redirect_uri = SECRET_MANAGER.get("SPOTIFY_REDIRECT_URI");
 
         format!(
-            "{}?client_id={}&response_type=code&redirect_uri={}&scope={}&state={}",
-            SPOTIFY_AUTH_URL,
+            "{}/authorize?client_id={}&response_type=code&redirect_uri={}&scope={}&state={}",
+            self.accounts_url,
             client_id,
             urlencoding::encode(&redirect_uri),
-            urlencoding::encode(SPOTIFY_SCOPES),
+            urlencoding::encode(&SPOTIFY_SCOPES),
             state
         )
     }
@@ -166,7 +567,7 @@ impl SpotifyController {
 
         let response = self
             .client
-            .post(SPOTIFY_TOKEN_URL)
+            .post(format!("{}/api/token", self.accounts_url))
             .basic_auth(&client_id, Some(&client_secret))
             .form(&params)
             .send()
@@ -199,7 +600,7 @@ impl SpotifyController {
 
         let response = self
             .client
-            .post(SPOTIFY_TOKEN_URL)
+            .post(format!("{}/api/token", self.accounts_url))
             .basic_auth(&client_id, Some(&client_secret))
             .form(&params)
             .send()
@@ -224,11 +625,25 @@ impl SpotifyController {
         Ok(tokens)
     }
 
+    /// The current user's profile, cached per access token unless `refresh`
+    pub async fn get_current_user_cached(&self, access_token: &str, refresh: bool) -> Result<SpotifyUser, String> {
+        let cache_key = user_profile_cache_key(access_token);
+        if !refresh
+            && let Some(user) = cache::get_json::<SpotifyUser>(&cache_key).await
+        {
+            return Ok(user);
+        }
+
+        let user = self.get_current_user(access_token).await?;
+        cache::set_json(&cache_key, &user, USER_PROFILE_CACHE_TTL_SECS).await;
+        Ok(user)
+    }
+
     /// Get current user's profile
     pub async fn get_current_user(&self, access_token: &str) -> Result<SpotifyUser, String> {
         let response = self
             .client
-            .get(format!("{}/me", SPOTIFY_API_URL))
+            .get(format!("{}/me", self.api_url))
             .bearer_auth(access_token)
             .send()
             .await
@@ -251,20 +666,29 @@ impl SpotifyController {
         query: &str,
         search_type: &str,
         limit: i32,
+        market: Option<&str>,
     ) -> Result<serde_json::Value, String> {
         let response = self
             .client
-            .get(format!("{}/search", SPOTIFY_API_URL))
+            .get(format!("{}/search", self.api_url))
             .bearer_auth(access_token)
             .query(&[
                 ("q", query),
                 ("type", search_type),
                 ("limit", &limit.to_string()),
             ])
+            .query(&[("market", market)])
             .send()
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            warn!(
+                "Spotify search rate limited, retry after {:?}",
+                response.headers().get(reqwest::header::RETRY_AFTER)
+            );
+            return Err(RATE_LIMITED.to_string());
+        }
         if !response.status().is_success() {
             return Err("Search failed".to_string());
         }
@@ -275,6 +699,79 @@ impl SpotifyController {
             .map_err(|e| format!("Failed to parse search results: {}", e))
     }
 
+    /// Search for tracks and flatten the results into `SearchResult`s
+    pub async fn search_tracks_normalized(
+        &self,
+        access_token: &str,
+        query: &str,
+        limit: i32,
+        explicit_ok: bool,
+        market: Option<&str>,
+    ) -> Result<Vec<SearchResult>, String> {
+        let results = self.search(access_token, query, "track", limit, market).await?;
+
+        // Spotify search has no reliable explicit filter, so drop them here
+        Ok(results
+            .get("tracks")
+            .and_then(|t| t.get("items"))
+            .and_then(|i| i.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(SearchResult::from_spotify_track)
+                    .filter(|track| explicit_ok || !track.explicit)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Get the user's saved ("liked") tracks, flattened into `SearchResult`s
+    pub async fn get_saved_tracks(
+        &self,
+        access_token: &str,
+        limit: i32,
+        offset: i32,
+    ) -> Result<SavedTracksResponse, String> {
+        let response = self
+            .client
+            .get(format!("{}/me/tracks", self.api_url))
+            .bearer_auth(access_token)
+            .query(&[("limit", limit.to_string()), ("offset", offset.to_string())])
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Failed to get saved tracks: {}", error_text));
+        }
+
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse saved tracks: {}", e))?;
+
+        // Saved items wrap the track: { added_at, track: {...} }
+        let items = body
+            .get("items")
+            .and_then(|i| i.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item.get("track"))
+                    .filter_map(SearchResult::from_spotify_track)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(SavedTracksResponse {
+            items,
+            total: body.get("total").and_then(|t| t.as_i64()).unwrap_or(0),
+            limit,
+            offset,
+        })
+    }
+
     /// Get audio features for multiple tracks
     pub async fn get_audio_features(
         &self,
@@ -283,7 +780,7 @@ impl SpotifyController {
     ) -> Result<serde_json::Value, String> {
         let response = self
             .client
-            .get(format!("{}/audio-features", SPOTIFY_API_URL))
+            .get(format!("{}/audio-features", self.api_url))
             .bearer_auth(access_token)
             .query(&[("ids", track_ids)])
             .send()
@@ -304,35 +801,38 @@ impl SpotifyController {
     pub async fn get_recommendations(
         &self,
         access_token: &str,
-        seed_tracks: Option<&str>,
-        seed_artists: Option<&str>,
-        seed_genres: Option<&str>,
-        target_tempo: Option<f64>,
-        target_energy: Option<f64>,
-        limit: Option<i32>,
+        seeds: &RecommendationSeeds,
+        limit: i32,
+        market: Option<&str>,
     ) -> Result<serde_json::Value, String> {
         let mut query: Vec<(&str, String)> = vec![];
 
-        if let Some(tracks) = seed_tracks {
-            query.push(("seed_tracks", tracks.to_string()));
+        if let Some(tracks) = &seeds.seed_tracks {
+            query.push(("seed_tracks", tracks.clone()));
         }
-        if let Some(artists) = seed_artists {
-            query.push(("seed_artists", artists.to_string()));
+        if let Some(artists) = &seeds.seed_artists {
+            query.push(("seed_artists", artists.clone()));
         }
-        if let Some(genres) = seed_genres {
-            query.push(("seed_genres", genres.to_string()));
+        if let Some(genres) = &seeds.seed_genres {
+            query.push(("seed_genres", genres.clone()));
         }
-        if let Some(tempo) = target_tempo {
+        if let Some(tempo) = seeds.target_tempo {
             query.push(("target_tempo", tempo.to_string()));
         }
-        if let Some(energy) = target_energy {
+        if let Some(energy) = seeds.target_energy {
             query.push(("target_energy", energy.to_string()));
         }
-        query.push(("limit", limit.unwrap_or(20).to_string()));
+        if let Some(valence) = seeds.target_valence {
+            query.push(("target_valence", valence.to_string()));
+        }
+        query.push(("limit", limit.to_string()));
+        if let Some(market) = market {
+            query.push(("market", market.to_string()));
+        }
 
         let response = self
             .client
-            .get(format!("{}/recommendations", SPOTIFY_API_URL))
+            .get(format!("{}/recommendations", self.api_url))
             .bearer_auth(access_token)
             .query(&query)
             .send()
@@ -350,98 +850,561 @@ impl SpotifyController {
             .map_err(|e| format!("Failed to parse recommendations: {}", e))
     }
 
-    /// Get access token using Client Credentials flow (no user login needed)
-    /// This works for search, recommendations, audio features - anything that doesn't need user data
-    pub async fn get_client_credentials_token(&self) -> Result<SpotifyTokens, String> {
-        let client_id = SECRET_MANAGER.get("SPOTIFY_CLIENT_ID");
-        let client_secret = SECRET_MANAGER.get("SPOTIFY_CLIENT_SECRET");
+    /// Keep only recommended tracks whose tempo is within `min_bpm..=max_bpm`.
+    /// Tracks without audio features are dropped since their tempo is unknown.
+    /// Returns how many tracks were removed.
+    pub async fn filter_by_bpm(
+        &self,
+        access_token: &str,
+        recommendations: &mut serde_json::Value,
+        min_bpm: f64,
+        max_bpm: f64,
+    ) -> Result<usize, String> {
+        let Some(tracks) = recommendations.get_mut("tracks").and_then(|t| t.as_array_mut()) else {
+            return Ok(0);
+        };
+        let ids: Vec<&str> = tracks.iter().filter_map(|t| t["id"].as_str()).collect();
+        if ids.is_empty() {
+            return Ok(0);
+        }
 
-        let params = [("grant_type", "client_credentials")];
+        let features = tokio::time::timeout(
+            BPM_FILTER_TIMEOUT,
+            self.get_audio_features(access_token, &ids.join(",")),
+        )
+        .await
+        .map_err(|_| "Audio features request timed out".to_string())??;
+
+        let tempos: HashMap<&str, f64> = features["audio_features"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|f| Some((f["id"].as_str()?, f["tempo"].as_f64()?)))
+            .collect();
+
+        let before = tracks.len();
+        tracks.retain(|t| {
+            t["id"]
+                .as_str()
+                .and_then(|id| tempos.get(id))
+                .is_some_and(|tempo| (min_bpm..=max_bpm).contains(tempo))
+        });
+        Ok(before - tracks.len())
+    }
 
+    /// Get a single track, flattened into a `SearchResult`
+    pub async fn get_track(
+        &self,
+        access_token: &str,
+        track_id: &str,
+        market: Option<&str>,
+    ) -> Result<SearchResult, String> {
         let response = self
             .client
-            .post(SPOTIFY_TOKEN_URL)
-            .basic_auth(&client_id, Some(&client_secret))
-            .form(&params)
+            .get(format!("{}/tracks/{}", self.api_url, track_id))
+            .bearer_auth(access_token)
+            .query(&[("market", market)])
             .send()
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err("Track not found".to_string());
+        }
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            error!("Client credentials auth failed: {}", error_text);
-            return Err(format!("Client credentials auth failed: {}", error_text));
+            return Err("Failed to get track".to_string());
         }
 
-        let tokens: SpotifyTokens = response
+        let track: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse tokens: {}", e))?;
+            .map_err(|e| format!("Failed to parse track: {}", e))?;
 
-        Ok(tokens)
+        SearchResult::from_spotify_track(&track).ok_or_else(|| "Failed to parse track".to_string())
     }
-}
-
-// Singleton instance
-pub static SPOTIFY_CONTROLLER: Lazy<SpotifyController> = Lazy::new(|| SpotifyController::new());
 
-// OAuth state store for CSRF protection
-pub static OAUTH_STATE_STORE: Lazy<Arc<RwLock<HashMap<String, i64>>>> =
-    Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+    /// Get a single artist, including the genre tags Spotify assigns them
+    pub async fn get_artist(&self, access_token: &str, artist_id: &str) -> Result<SpotifyArtist, String> {
+        let response = self
+            .client
+            .get(format!("{}/artists/{}", self.api_url, artist_id))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
 
-// Generate a cryptographically secure random state string
-fn generate_state() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    
-    // Use random bytes for unpredictability + timestamp for uniqueness
-    let random_bytes: [u8; 16] = rand::random();
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
-    
-    format!("{:032x}{:x}", 
-        u128::from_be_bytes(random_bytes),
-        timestamp
-    )
-}
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err("Artist not found".to_string());
+        }
+        if !response.status().is_success() {
+            return Err("Failed to get artist".to_string());
+        }
 
-// Validate and consume OAuth state (one-time use)
-async fn validate_state(state: &str) -> bool {
-    let mut store = OAUTH_STATE_STORE.write().await;
-    
-    // Check if state exists and is not expired (5 minutes max)
-    if let Some(created_at) = store.remove(state) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        
-        // State valid for 5 minutes
-        return now - created_at < 300;
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse artist: {}", e))
     }
-    
-    false
-}
-
-// Store OAuth state with timestamp
-async fn store_state(state: &str) {
-    let mut store = OAUTH_STATE_STORE.write().await;
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
-    
-    store.insert(state.to_string(), now);
-    
-    // Clean up old states (older than 10 minutes)
-    store.retain(|_, created_at| now - *created_at < 600);
-}
 
-// Route handlers
-
-/// GET /spotify/auth - Redirect to Spotify authorization
-pub async fn spotify_auth_route(State(_database): State<Database>) -> impl IntoResponse {
+    /// Download a track's album art at the closest available size.
+    /// Returns the image bytes and content type.
+    pub async fn get_track_artwork(
+        &self,
+        access_token: &str,
+        track_id: &str,
+        size: ArtworkSize,
+    ) -> Result<(Vec<u8>, String), String> {
+        let response = self
+            .client
+            .get(format!("{}/tracks/{}", self.api_url, track_id))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err("Track not found".to_string());
+        }
+        if !response.status().is_success() {
+            return Err("Failed to get track".to_string());
+        }
+
+        let track: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse track: {}", e))?;
+        let images: Vec<SpotifyImage> =
+            serde_json::from_value(track["album"]["images"].clone()).unwrap_or_default();
+
+        let image = images
+            .iter()
+            .min_by_key(|i| (i.width.unwrap_or(0) - size.width()).abs())
+            .ok_or_else(|| "Track has no artwork".to_string())?;
+
+        let response = self
+            .client
+            .get(&image.url)
+            .send()
+            .await
+            .map_err(|e| format!("Artwork request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Artwork request failed with {}", response.status()));
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("image/jpeg")
+            .to_string();
+        let bytes = read_capped(response, ARTWORK_MAX_BYTES).await?;
+
+        Ok((bytes, content_type))
+    }
+
+    /// Get the current user's most listened-to artists (needs `user-top-read`)
+    pub async fn get_top_artists(&self, access_token: &str, limit: usize) -> Result<Vec<SpotifyArtist>, String> {
+        let response = self
+            .client
+            .get(format!("{}/me/top/artists", self.api_url))
+            .bearer_auth(access_token)
+            .query(&[("limit", limit.to_string())])
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err("Failed to get top artists".to_string());
+        }
+
+        let body: TopArtistsResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse top artists: {}", e))?;
+
+        Ok(body.items)
+    }
+
+    /// Get artists Spotify considers similar to the given artist
+    pub async fn get_related_artists(
+        &self,
+        access_token: &str,
+        artist_id: &str,
+    ) -> Result<Vec<SpotifyArtist>, String> {
+        let response = self
+            .client
+            .get(format!("{}/artists/{}/related-artists", self.api_url, artist_id))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err("Artist not found".to_string());
+        }
+        if !response.status().is_success() {
+            return Err("Failed to get related artists".to_string());
+        }
+
+        let body: RelatedArtistsResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse related artists: {}", e))?;
+
+        Ok(body.artists)
+    }
+
+    /// List the user's Spotify Connect devices (requires user-read-playback-state)
+    pub async fn get_devices(&self, access_token: &str) -> Result<Vec<SpotifyDevice>, String> {
+        let response = self
+            .client
+            .get(format!("{}/me/player/devices", self.api_url))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Failed to get devices: {}", error_text));
+        }
+
+        let body: DevicesResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse devices: {}", e))?;
+
+        Ok(body.devices)
+    }
+
+    /// Transfer, play or pause on the user's Spotify Connect player (requires
+    /// user-modify-playback-state). Fails with `PREMIUM_REQUIRED` for free accounts.
+    pub async fn control_player(&self, access_token: &str, command: &PlayerCommand) -> Result<(), String> {
+        let request = match command {
+            PlayerCommand::Transfer { device_id, play } => self
+                .client
+                .put(format!("{}/me/player", self.api_url))
+                .json(&serde_json::json!({"device_ids": [device_id], "play": play})),
+            PlayerCommand::Play { device_id, uris, position_ms } => {
+                let mut body = serde_json::Map::new();
+                if let Some(uris) = uris {
+                    body.insert("uris".to_string(), serde_json::json!(uris));
+                }
+                if let Some(position_ms) = position_ms {
+                    body.insert("position_ms".to_string(), serde_json::json!(position_ms));
+                }
+                self.client
+                    .put(format!("{}/me/player/play", self.api_url))
+                    .query(&[("device_id", device_id)])
+                    .json(&body)
+            }
+            PlayerCommand::Pause { device_id } => self
+                .client
+                .put(format!("{}/me/player/pause", self.api_url))
+                .query(&[("device_id", device_id)])
+                .header(reqwest::header::CONTENT_LENGTH, 0),
+        };
+
+        let response = request
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        match (status, body["error"]["reason"].as_str()) {
+            (reqwest::StatusCode::FORBIDDEN, Some("PREMIUM_REQUIRED")) => Err(PREMIUM_REQUIRED.to_string()),
+            (reqwest::StatusCode::NOT_FOUND, _) => Err(NO_ACTIVE_DEVICE.to_string()),
+            _ => Err(format!(
+                "Player command failed: {}",
+                body["error"]["message"].as_str().unwrap_or(status.as_str())
+            )),
+        }
+    }
+
+    /// Get the genres accepted as `seed_genres` by the recommendations endpoint.
+    /// Cached in Redis for a day since Spotify rarely changes the list.
+    pub async fn get_available_genre_seeds(&self, access_token: &str) -> Result<Vec<String>, String> {
+        if let Some(genres) = cache::get_json::<Vec<String>>(GENRE_SEEDS_CACHE_KEY).await {
+            return Ok(genres);
+        }
+
+        let response = self
+            .client
+            .get(format!("{}/recommendations/available-genre-seeds", self.api_url))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Failed to get genre seeds: {}", error_text));
+        }
+
+        let body: GenreSeedsResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse genre seeds: {}", e))?;
+
+        cache::set_json(GENRE_SEEDS_CACHE_KEY, &body.genres, GENRE_SEEDS_CACHE_TTL_SECS).await;
+
+        Ok(body.genres)
+    }
+
+    /// GET `/browse/{path}` with paging and an optional market, cached briefly
+    async fn get_browse_page<T, R>(
+        &self,
+        access_token: &str,
+        path: &str,
+        country: Option<&str>,
+        limit: i64,
+        offset: i64,
+        into_page: impl FnOnce(R) -> BrowsePage<T>,
+    ) -> Result<BrowsePage<T>, String>
+    where
+        T: Serialize + serde::de::DeserializeOwned,
+        R: serde::de::DeserializeOwned,
+    {
+        let cache_key = format!("spotify:browse:{}:{}:{}:{}", path, country.unwrap_or("any"), limit, offset);
+        if let Some(page) = cache::get_json::<BrowsePage<T>>(&cache_key).await {
+            return Ok(page);
+        }
+
+        let mut query = vec![("limit", limit.to_string()), ("offset", offset.to_string())];
+        if let Some(country) = country {
+            query.push(("country", country.to_string()));
+        }
+        let response = self
+            .client
+            .get(format!("{}/browse/{}", self.api_url, path))
+            .bearer_auth(access_token)
+            .query(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Failed to get {}: {}", path, error_text));
+        }
+
+        let body: R = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        let page = into_page(body);
+
+        cache::set_json(&cache_key, &page, BROWSE_CACHE_TTL_SECS).await;
+        Ok(page)
+    }
+
+    /// Albums Spotify has just released, optionally for one market
+    pub async fn get_new_releases(
+        &self,
+        access_token: &str,
+        country: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<BrowsePage<SpotifyAlbum>, String> {
+        self.get_browse_page(access_token, "new-releases", country, limit, offset, |body: NewReleasesResponse| {
+            body.albums
+        })
+        .await
+    }
+
+    /// Spotify's editorially featured playlists, optionally for one market
+    pub async fn get_featured_playlists(
+        &self,
+        access_token: &str,
+        country: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<BrowsePage<SpotifyPlaylist>, String> {
+        self.get_browse_page(
+            access_token,
+            "featured-playlists",
+            country,
+            limit,
+            offset,
+            |body: FeaturedPlaylistsResponse| BrowsePage { message: body.message, ..body.playlists },
+        )
+        .await
+    }
+
+    /// The shared app token, fetched on first use and refreshed when it's
+    /// within `TOKEN_REFRESH_MARGIN_SECS` of expiring
+    pub async fn app_tokens(&self) -> Result<SpotifyTokens, String> {
+        if let Some(tokens) = self.fresh_app_tokens().await {
+            return Ok(tokens);
+        }
+
+        self.app_token_refresh
+            .run("app", || async {
+                // Another refresh may have landed while we were queued
+                if let Some(tokens) = self.fresh_app_tokens().await {
+                    return Ok(tokens);
+                }
+                let tokens = tokio::time::timeout(APP_TOKEN_TIMEOUT, self.get_client_credentials_token())
+                    .await
+                    .map_err(|_| "Timed out fetching Spotify app token".to_string())??;
+                info!("Fetched Spotify app token, valid for {}s", tokens.expires_in);
+                *self.app_tokens.write().await = Some(tokens.clone());
+                Ok(tokens)
+            })
+            .await
+    }
+
+    /// The cached app token, unless it's within `TOKEN_REFRESH_MARGIN_SECS` of expiring
+    async fn fresh_app_tokens(&self) -> Option<SpotifyTokens> {
+        self.app_tokens
+            .read()
+            .await
+            .as_ref()
+            .filter(|tokens| tokens.remaining_secs(unix_now()) > TOKEN_REFRESH_MARGIN_SECS)
+            .cloned()
+    }
+
+    /// Get access token using Client Credentials flow (no user login needed)
+    /// This works for search, recommendations, audio features - anything that doesn't need user data
+    pub async fn get_client_credentials_token(&self) -> Result<SpotifyTokens, String> {
+        let client_id = SECRET_MANAGER.get("SPOTIFY_CLIENT_ID");
+        let client_secret = SECRET_MANAGER.get("SPOTIFY_CLIENT_SECRET");
+
+        let params = [("grant_type", "client_credentials")];
+
+        let response = self
+            .client
+            .post(format!("{}/api/token", self.accounts_url))
+            .basic_auth(&client_id, Some(&client_secret))
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            error!("Client credentials auth failed: {}", error_text);
+            return Err(format!("Client credentials auth failed: {}", error_text));
+        }
+
+        let tokens: SpotifyTokens = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse tokens: {}", e))?;
+
+        Ok(tokens)
+    }
+}
+
+// Singleton instance
+pub static SPOTIFY_CONTROLLER: Lazy<SpotifyController> = Lazy::new(SpotifyController::new);
+
+// OAuth state store for CSRF protection
+pub static OAUTH_STATE_STORE: Lazy<Arc<RwLock<HashMap<String, i64>>>> =
+    Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+/// How long an OAuth state stays valid between /auth and /callback
+const OAUTH_STATE_TTL_SECS: i64 = 300;
+
+/// How long a renewable token session is kept without being refreshed.
+/// An active client refreshes at least hourly, which resets `obtained_at`.
+const SESSION_IDLE_TTL_SECS: i64 = 7 * 24 * 3600;
+
+// Generate a cryptographically secure random state string
+fn generate_state() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    
+    // Use random bytes for unpredictability + timestamp for uniqueness
+    let random_bytes: [u8; 16] = rand::random();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    
+    format!("{:032x}{:x}", 
+        u128::from_be_bytes(random_bytes),
+        timestamp
+    )
+}
+
+// Validate and consume OAuth state (one-time use)
+async fn validate_state(state: &str) -> bool {
+    let mut store = OAUTH_STATE_STORE.write().await;
+    
+    // Check if state exists and is not expired
+    if let Some(created_at) = store.remove(state) {
+        return unix_now() - created_at < OAUTH_STATE_TTL_SECS;
+    }
+    
+    false
+}
+
+// Store OAuth state with timestamp
+async fn store_state(state: &str) {
+    let mut store = OAUTH_STATE_STORE.write().await;
+    store.insert(state.to_string(), unix_now());
+}
+
+/// Whether a token session is worth keeping at `now`: its access token is
+/// still valid, or it can be renewed and was refreshed within
+/// `SESSION_IDLE_TTL_SECS`
+fn session_is_live(tokens: &SpotifyTokens, now: i64) -> bool {
+    if tokens.refresh_token.is_some() {
+        now - tokens.obtained_at < SESSION_IDLE_TTL_SECS
+    } else {
+        tokens.remaining_secs(now) > 0
+    }
+}
+
+/// Drop expired OAuth states and token sessions that can no longer be used
+/// or have sat idle too long
+async fn prune_expired_sessions() {
+    let now = unix_now();
+
+    let pruned_states = {
+        let mut store = OAUTH_STATE_STORE.write().await;
+        let before = store.len();
+        store.retain(|_, created_at| now - *created_at < OAUTH_STATE_TTL_SECS);
+        before - store.len()
+    };
+
+    let pruned_tokens = {
+        let mut store = TOKEN_STORE.write().await;
+        let before = store.len();
+        store.retain(|_, tokens| session_is_live(tokens, now));
+        before - store.len()
+    };
+
+    if pruned_states > 0 || pruned_tokens > 0 {
+        info!(
+            "Pruned {} expired OAuth states and {} expired token sessions",
+            pruned_states, pruned_tokens
+        );
+    }
+}
+
+/// Sweep the in-memory OAuth stores every minute. `store_state` only runs on
+/// new logins, so without this a quiet server would keep stale entries forever.
+pub fn spawn_session_sweeper() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            prune_expired_sessions().await;
+        }
+    });
+}
+
+// Route handlers
+
+/// GET /spotify/auth - Redirect to Spotify authorization
+pub async fn spotify_auth_route(State(_database): State<Database>) -> impl IntoResponse {
     let state = generate_state();
     
     // Store state for validation in callback
@@ -525,160 +1488,293 @@ pub async fn spotify_callback_route(
 pub async fn spotify_refresh_route(
     State(_database): State<Database>,
     Query(params): Query<RefreshTokenQuery>,
-) -> impl IntoResponse {
+) -> Result<Json<TokenResponse>, ApiError> {
     let store = TOKEN_STORE.read().await;
-    let tokens = match store.get(&params.session_id) {
-        Some(t) => t.clone(),
-        None => {
-            return (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Session not found"})))
-                .into_response();
-        }
-    };
+    let tokens = store
+        .get(&params.session_id)
+        .cloned()
+        .ok_or_else(|| ApiError::not_found("Session not found"))?;
     drop(store);
 
-    let refresh_token = match &tokens.refresh_token {
-        Some(rt) => rt.clone(),
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"error": "No refresh token available"})),
-            )
-                .into_response();
-        }
-    };
-
-    match SPOTIFY_CONTROLLER.refresh_token(&refresh_token).await {
-        Ok(new_tokens) => {
-            // Update stored tokens
-            let mut store = TOKEN_STORE.write().await;
-            store.insert(params.session_id, new_tokens.clone());
+    let refresh_token = tokens
+        .refresh_token
+        .ok_or_else(|| ApiError::bad_request("No refresh token available"))?;
 
-            Json(TokenResponse {
-                access_token: new_tokens.access_token,
-                expires_in: new_tokens.expires_in,
-            })
-            .into_response()
-        }
-        Err(e) => {
+    let new_tokens = SPOTIFY_CONTROLLER
+        .refresh_token(&refresh_token)
+        .await
+        .map_err(|e| {
             error!("Token refresh failed: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e})),
-            )
-                .into_response()
-        }
-    }
-}
+            ApiError::internal(e)
+        })?;
+
+    // Update stored tokens
+    let mut store = TOKEN_STORE.write().await;
+    store.insert(params.session_id, new_tokens.clone());
+
+    Ok(Json(TokenResponse {
+        access_token: new_tokens.access_token,
+        expires_in: new_tokens.expires_in,
+    }))
+}
+
+/// GET /spotify/session/validate - Whether a stored session still works,
+/// refreshing its token first if it's about to expire. Lets the UI decide
+/// whether to show "reconnect Spotify" without a real API call.
+pub async fn spotify_validate_session_route(
+    State(_database): State<Database>,
+    Query(params): Query<RefreshTokenQuery>,
+) -> Json<SessionValidation> {
+    Json(validate_session(&SPOTIFY_CONTROLLER, &TOKEN_STORE, params.session_id).await)
+}
+
+/// Whether `session_id` in `store` can still make API calls, refreshing it
+/// through `spotify` when it's within `TOKEN_REFRESH_MARGIN_SECS` of expiring
+async fn validate_session(
+    spotify: &SpotifyController,
+    store: &RwLock<HashMap<String, SpotifyTokens>>,
+    session_id: String,
+) -> SessionValidation {
+    let invalid = SessionValidation { valid: false, expires_in: None };
+    let Some(tokens) = store.read().await.get(&session_id).cloned() else {
+        return invalid;
+    };
+
+    let remaining = tokens.remaining_secs(unix_now());
+    if remaining > TOKEN_REFRESH_MARGIN_SECS {
+        return SessionValidation { valid: true, expires_in: Some(remaining) };
+    }
+    let Some(refresh_token) = tokens.refresh_token else {
+        return if remaining > 0 {
+            SessionValidation { valid: true, expires_in: Some(remaining) }
+        } else {
+            invalid
+        };
+    };
+
+    match spotify.refresh_token(&refresh_token).await {
+        Ok(new_tokens) => {
+            let expires_in = new_tokens.expires_in;
+            store.write().await.insert(session_id, new_tokens);
+            SessionValidation { valid: true, expires_in: Some(expires_in) }
+        }
+        Err(e) => {
+            warn!("Spotify session failed to refresh during validation: {}", e);
+            invalid
+        }
+    }
+}
 
 /// GET /spotify/token - Fetch access token for session (one-time use after OAuth)
 pub async fn spotify_token_route(
     State(_database): State<Database>,
     Query(params): Query<RefreshTokenQuery>,
-) -> impl IntoResponse {
+) -> Result<Json<TokenResponse>, ApiError> {
     let store = TOKEN_STORE.read().await;
-    let tokens = match store.get(&params.session_id) {
-        Some(t) => t.clone(),
-        None => {
-            return (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Session not found"})))
-                .into_response();
-        }
-    };
+    let tokens = store
+        .get(&params.session_id)
+        .cloned()
+        .ok_or_else(|| ApiError::not_found("Session not found"))?;
     drop(store);
 
-    Json(TokenResponse {
+    Ok(Json(TokenResponse {
         access_token: tokens.access_token,
         expires_in: tokens.expires_in,
-    })
-    .into_response()
+    }))
 }
 
 /// GET /spotify/auto-auth - Auto-authenticate using Client Credentials (no user login needed)
 /// Returns an access token that works for search, recommendations, audio features
-pub async fn spotify_auto_auth_route(State(_database): State<Database>) -> impl IntoResponse {
-    match SPOTIFY_CONTROLLER.get_client_credentials_token().await {
-        Ok(tokens) => {
-            // Store tokens with a session ID
-            let session_id = generate_state();
-            {
-                let mut store = TOKEN_STORE.write().await;
-                store.insert(session_id.clone(), tokens.clone());
-            }
+pub async fn spotify_auto_auth_route(
+    State(_database): State<Database>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let tokens = SPOTIFY_CONTROLLER
+        .app_tokens()
+        .await
+        .map_err(|e| {
+            error!("Auto-auth failed: {}", e);
+            ApiError::internal(e)
+        })?;
 
-            info!("Auto-auth successful, session: {}", session_id);
+    // Store tokens with a session ID
+    let session_id = generate_state();
+    {
+        let mut store = TOKEN_STORE.write().await;
+        store.insert(session_id.clone(), tokens.clone());
+    }
 
-            Json(serde_json::json!({
-                "access_token": tokens.access_token,
-                "expires_in": tokens.expires_in,
-                "session_id": session_id,
-                "type": "client_credentials"
-            }))
-            .into_response()
-        }
-        Err(e) => {
-            error!("Auto-auth failed: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e})),
-            )
-                .into_response()
-        }
+    info!("Auto-auth successful, session: {}", session_id);
+
+    Ok(Json(serde_json::json!({
+        "access_token": tokens.access_token,
+        "expires_in": tokens.remaining_secs(unix_now()),
+        "session_id": session_id,
+        "type": "client_credentials"
+    })))
+}
+
+/// The caller's Spotify access token from the `Authorization` header
+pub fn bearer_token(headers: &axum::http::HeaderMap) -> Result<String, ApiError> {
+    headers
+        .get("Authorization")
+        .map(|h| h.to_str().unwrap_or("").replace("Bearer ", ""))
+        .ok_or_else(|| ApiError::unauthorized("No authorization header"))
+}
+
+/// 400 listing every id that isn't a Spotify id, so malformed input fails
+/// here with a clear message instead of as an opaque Spotify error
+pub fn check_spotify_ids<'a>(ids: impl IntoIterator<Item = &'a str>) -> Result<(), ApiError> {
+    let invalid: Vec<&str> = ids.into_iter().filter(|id| !is_valid_spotify_id(id)).collect();
+    if invalid.is_empty() {
+        return Ok(());
     }
+    Err(ApiError::bad_request(format!("Invalid Spotify ids: {}", invalid.join(", ")))
+        .with_code("invalid_spotify_id")
+        .with_details(serde_json::json!({"invalid_ids": invalid})))
+}
+
+/// Ids from a comma-separated query parameter
+fn split_ids(ids: &str) -> impl Iterator<Item = &str> {
+    ids.split(',').map(str::trim)
+}
+
+/// `access_token_or_app_token` for routes that don't need user scopes, so
+/// they work for anonymous visitors
+pub async fn bearer_or_app_token(headers: &axum::http::HeaderMap) -> Result<String, ApiError> {
+    access_token_or_app_token(headers).await.map_err(|e| {
+        error!("No Spotify token available: {}", e);
+        ApiError::bad_gateway("Spotify unavailable")
+    })
+}
+
+/// Cache key for a user's profile. Keyed on a hash so access tokens never
+/// end up in Redis.
+fn user_profile_cache_key(access_token: &str) -> String {
+    let digest = Sha256::digest(access_token.as_bytes());
+    let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("spotify:me:{}", hash)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MeQuery {
+    /// Skip the cached profile and fetch a fresh one
+    #[serde(default)]
+    pub refresh: bool,
 }
 
 /// GET /spotify/me - Get current user profile
 pub async fn spotify_me_route(
     State(_database): State<Database>,
+    Query(params): Query<MeQuery>,
     headers: axum::http::HeaderMap,
-) -> impl IntoResponse {
-    let access_token = match headers.get("Authorization") {
-        Some(h) => h.to_str().unwrap_or("").replace("Bearer ", ""),
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "No authorization header"})),
-            )
-                .into_response();
-        }
+) -> Result<Json<SpotifyUser>, ApiError> {
+    let access_token = bearer_token(&headers)?;
+
+    SPOTIFY_CONTROLLER
+        .get_current_user_cached(&access_token, params.refresh)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+/// Whether explicit tracks are allowed: the query param wins, then the
+/// session profile, then true
+async fn resolve_explicit_ok(
+    database: &Database,
+    explicit_ok: Option<bool>,
+    session_id: Option<&str>,
+) -> bool {
+    if let Some(explicit_ok) = explicit_ok {
+        return explicit_ok;
+    }
+    let Some(session_id) = session_id else {
+        return true;
     };
+    match database.get_session_profile(session_id).await {
+        Ok(profile) => profile.is_none_or(|p| p.explicit_ok),
+        Err(e) => {
+            warn!("Failed to load profile for explicit filter: {}", e);
+            true
+        }
+    }
+}
 
-    match SPOTIFY_CONTROLLER.get_current_user(&access_token).await {
-        Ok(user) => Json(user).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e})),
-        )
-            .into_response(),
+/// Remove explicit tracks from a raw Spotify track array
+fn retain_clean_tracks(tracks: Option<&mut serde_json::Value>) {
+    if let Some(tracks) = tracks.and_then(|t| t.as_array_mut()) {
+        tracks.retain(|t| !t["explicit"].as_bool().unwrap_or(false));
     }
 }
 
 /// GET /spotify/search - Search for tracks
 pub async fn spotify_search_route(
-    State(_database): State<Database>,
+    State(database): State<Database>,
     Query(params): Query<SearchQuery>,
+    pagination: Pagination,
     headers: axum::http::HeaderMap,
-) -> impl IntoResponse {
-    let access_token = match headers.get("Authorization") {
-        Some(h) => h.to_str().unwrap_or("").replace("Bearer ", ""),
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "No authorization header"})),
-            )
-                .into_response();
-        }
-    };
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let limit = pagination.capped(50).limit as i32;
+    let market = resolve_market(params.market.as_deref(), &headers).await?;
+    let access_token = bearer_or_app_token(&headers).await?;
 
-    match SPOTIFY_CONTROLLER
-        .search(&access_token, &params.q, &params.search_type, params.limit)
+    let mut results = SPOTIFY_CONTROLLER
+        .search(&access_token, &params.q, &params.search_type, limit, market.as_deref())
         .await
-    {
-        Ok(results) => Json(results).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e})),
-        )
-            .into_response(),
+        .map_err(search_error)?;
+
+    if !resolve_explicit_ok(&database, params.explicit_ok, params.session_id.as_deref()).await {
+        retain_clean_tracks(results.get_mut("tracks").and_then(|t| t.get_mut("items")));
     }
+    Ok(Json(results))
+}
+
+/// Surface Spotify's rate limit as our own 429 so clients back off
+fn search_error(e: String) -> ApiError {
+    if e == RATE_LIMITED {
+        ApiError::new(StatusCode::TOO_MANY_REQUESTS, e).with_code("spotify_rate_limited")
+    } else {
+        ApiError::internal(e)
+    }
+}
+
+/// GET /spotify/search/normalized - Search for tracks, returning flattened results
+pub async fn spotify_search_normalized_route(
+    State(database): State<Database>,
+    Query(params): Query<SearchQuery>,
+    pagination: Pagination,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<SearchResult>>, ApiError> {
+    let limit = pagination.capped(50).limit as i32;
+    let market = resolve_market(params.market.as_deref(), &headers).await?;
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    let explicit_ok =
+        resolve_explicit_ok(&database, params.explicit_ok, params.session_id.as_deref()).await;
+
+    SPOTIFY_CONTROLLER
+        .search_tracks_normalized(&access_token, &params.q, limit, explicit_ok, market.as_deref())
+        .await
+        .map(Json)
+        .map_err(search_error)
+}
+
+/// GET /spotify/saved-tracks - Get the user's liked songs (requires user-library-read)
+pub async fn spotify_saved_tracks_route(
+    State(_database): State<Database>,
+    pagination: Pagination,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<SavedTracksResponse>, ApiError> {
+    let access_token = bearer_token(&headers)?;
+
+    // Spotify caps /me/tracks at 50 items per page
+    let Pagination { limit, offset } = pagination.capped(50);
+    let offset = i32::try_from(offset).map_err(|_| ApiError::bad_request("offset is too large"))?;
+
+    SPOTIFY_CONTROLLER
+        .get_saved_tracks(&access_token, limit as i32, offset)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
 }
 
 /// GET /spotify/audio-features - Get audio features for tracks
@@ -686,65 +1782,895 @@ pub async fn spotify_audio_features_route(
     State(_database): State<Database>,
     Query(params): Query<AudioFeaturesQuery>,
     headers: axum::http::HeaderMap,
-) -> impl IntoResponse {
-    let access_token = match headers.get("Authorization") {
-        Some(h) => h.to_str().unwrap_or("").replace("Bearer ", ""),
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "No authorization header"})),
-            )
-                .into_response();
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_spotify_ids(split_ids(&params.ids))?;
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    SPOTIFY_CONTROLLER
+        .get_audio_features(&access_token, &params.ids)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+/// GET /spotify/recommendations - Get track recommendations
+pub async fn spotify_recommendations_route(
+    State(database): State<Database>,
+    Query(mut params): Query<RecommendationsQuery>,
+    pagination: Pagination,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_spotify_ids(
+        [params.seed_tracks.as_deref(), params.seed_artists.as_deref()]
+            .into_iter()
+            .flatten()
+            .flat_map(split_ids),
+    )?;
+    let market = resolve_market(params.market.as_deref(), &headers).await?;
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    // Seed from the onboarding profile where the caller didn't say otherwise
+    let mut explicit_ok = params.explicit_ok.unwrap_or(true);
+    if let Some(session_id) = params.session_id.as_deref() {
+        match database.get_session_profile(session_id).await {
+            Ok(Some(profile)) => {
+                let has_seeds = params.seed_tracks.is_some()
+                    || params.seed_artists.is_some()
+                    || params.seed_genres.is_some();
+                if !has_seeds && !profile.genres.is_empty() {
+                    let genres: Vec<&str> = profile
+                        .genres
+                        .iter()
+                        .take(MAX_RECOMMENDATION_SEEDS)
+                        .map(|g| g.as_str())
+                        .collect();
+                    params.seed_genres = Some(genres.join(","));
+                }
+                params.target_energy = params.target_energy.or(Some(profile.energy_level));
+                explicit_ok = params.explicit_ok.unwrap_or(profile.explicit_ok);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load profile for recommendations: {}", e),
+        }
+    }
+
+    let mut recs = SPOTIFY_CONTROLLER
+        .get_recommendations(
+            &access_token,
+            &RecommendationSeeds {
+                seed_tracks: params.seed_tracks.clone(),
+                seed_artists: params.seed_artists.clone(),
+                seed_genres: params.seed_genres.clone(),
+                target_tempo: params.target_tempo,
+                target_energy: params.target_energy,
+                target_valence: None,
+            },
+            pagination.limit as i32,
+            market.as_deref(),
+        )
+        .await
+        .map_err(ApiError::internal)?;
+
+    if !explicit_ok {
+        retain_clean_tracks(recs.get_mut("tracks"));
+    }
+
+    if let Some((min_bpm, max_bpm)) = params.bpm_window() {
+        // A failed filter still returns the unfiltered recommendations
+        let filter = match SPOTIFY_CONTROLLER
+            .filter_by_bpm(&access_token, &mut recs, min_bpm, max_bpm)
+            .await
+        {
+            Ok(removed) => serde_json::json!({
+                "applied": true,
+                "min_bpm": min_bpm,
+                "max_bpm": max_bpm,
+                "removed": removed,
+            }),
+            Err(e) => {
+                warn!("BPM filter skipped: {}", e);
+                serde_json::json!({"applied": false, "error": e})
+            }
+        };
+        if let Some(recs) = recs.as_object_mut() {
+            recs.insert("bpm_filter".to_string(), filter);
+        }
+    }
+    Ok(Json(recs))
+}
+
+/// GET /spotify/recommendations/seeded-from-profile - Recommendations for a
+/// session's stored vibe: profile genres first, then the user's top artists
+/// fill the remaining seed slots
+pub async fn spotify_profile_recommendations_route(
+    State(database): State<Database>,
+    Query(params): Query<ProfileRecommendationsQuery>,
+    pagination: Pagination,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<SearchResult>>, ApiError> {
+    let market = resolve_market(params.market.as_deref(), &headers).await?;
+    let access_token = bearer_token(&headers)?;
+
+    let profile = match database.get_session_profile(&params.session_id).await {
+        Ok(Some(profile)) => profile,
+        Ok(None) => return Err(ApiError::not_found("Session profile not found")),
+        Err(e) => {
+            error!("Failed to get session profile: {}", e);
+            return Err(ApiError::database(&e, "Failed to retrieve session profile"));
+        }
+    };
+
+    let seed_genres: Vec<&str> = profile
+        .genres
+        .iter()
+        .take(MAX_RECOMMENDATION_SEEDS)
+        .map(|g| g.as_str())
+        .collect();
+
+    let open_slots = MAX_RECOMMENDATION_SEEDS - seed_genres.len();
+    let seed_artists: Vec<String> = if open_slots > 0 {
+        SPOTIFY_CONTROLLER
+            .get_top_artists(&access_token, open_slots)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("No top artists for profile seeding: {}", e);
+                vec![]
+            })
+            .into_iter()
+            .map(|a| a.id)
+            .collect()
+    } else {
+        vec![]
+    };
+
+    if seed_genres.is_empty() && seed_artists.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Profile has no usable seeds; add genres or listen to some artists first",
+        ));
+    }
+
+    let seeds = RecommendationSeeds {
+        seed_artists: Some(seed_artists.join(",")).filter(|s| !s.is_empty()),
+        seed_genres: Some(seed_genres.join(",")).filter(|s| !s.is_empty()),
+        target_energy: Some(profile.energy_level),
+        target_valence: profile.target_valence(),
+        ..Default::default()
+    };
+    let recs = SPOTIFY_CONTROLLER
+        .get_recommendations(&access_token, &seeds, pagination.limit as i32, market.as_deref())
+        .await
+        .map_err(ApiError::internal)?;
+
+    let tracks: Vec<SearchResult> = recs["tracks"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|t| profile.explicit_ok || !t["explicit"].as_bool().unwrap_or(false))
+        .filter_map(SearchResult::from_spotify_track)
+        .collect();
+    Ok(Json(tracks))
+}
+
+/// The caller's Spotify token if sent, else an app (client credentials) token.
+/// For endpoints that don't need user scopes, like `<img>` sources.
+pub async fn access_token_or_app_token(headers: &axum::http::HeaderMap) -> Result<String, String> {
+    if let Some(token) = headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+    {
+        return Ok(token.to_string());
+    }
+
+    SPOTIFY_CONTROLLER
+        .app_tokens()
+        .await
+        .map(|tokens| tokens.access_token)
+}
+
+/// Validate a `country`/`market` query parameter as an ISO 3166-1 alpha-2 code
+fn country_code(value: Option<&str>, param: &str) -> Result<Option<String>, ApiError> {
+    match value {
+        None => Ok(None),
+        Some(c) if c.len() == 2 && c.chars().all(|ch| ch.is_ascii_alphabetic()) => Ok(Some(c.to_ascii_uppercase())),
+        Some(_) => Err(ApiError::bad_request(format!("{} must be a two-letter ISO 3166-1 code", param))),
+    }
+}
+
+/// The Spotify market to query: the `market` param if given, else the
+/// logged-in user's profile country. App tokens have no profile, so anonymous
+/// callers get Spotify's default.
+async fn resolve_market(
+    market: Option<&str>,
+    headers: &axum::http::HeaderMap,
+) -> Result<Option<String>, ApiError> {
+    if let Some(market) = country_code(market, "market")? {
+        return Ok(Some(market));
+    }
+    let Ok(access_token) = bearer_token(headers) else {
+        return Ok(None);
+    };
+    match SPOTIFY_CONTROLLER.get_current_user_cached(&access_token, false).await {
+        Ok(user) => Ok(user.country),
+        Err(e) => {
+            warn!("No profile country for market default: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// GET /spotify/new-releases - Newly released albums; works without a user login
+pub async fn spotify_new_releases_route(
+    State(_database): State<Database>,
+    Query(params): Query<BrowseQuery>,
+    pagination: Pagination,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<BrowsePage<SpotifyAlbum>>, ApiError> {
+    let country = country_code(params.country.as_deref(), "country")?;
+    // Spotify caps browse pages at 50 items
+    let Pagination { limit, offset } = pagination.capped(50);
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    SPOTIFY_CONTROLLER
+        .get_new_releases(&access_token, country.as_deref(), limit, offset)
+        .await
+        .map(Json)
+        .map_err(ApiError::bad_gateway)
+}
+
+/// GET /spotify/featured-playlists - Editorially featured playlists; works
+/// without a user login
+pub async fn spotify_featured_playlists_route(
+    State(_database): State<Database>,
+    Query(params): Query<BrowseQuery>,
+    pagination: Pagination,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<BrowsePage<SpotifyPlaylist>>, ApiError> {
+    let country = country_code(params.country.as_deref(), "country")?;
+    let Pagination { limit, offset } = pagination.capped(50);
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    SPOTIFY_CONTROLLER
+        .get_featured_playlists(&access_token, country.as_deref(), limit, offset)
+        .await
+        .map(Json)
+        .map_err(ApiError::bad_gateway)
+}
+
+/// Read a response body of at most `max_bytes`. An oversized
+/// `Content-Length` is refused before anything is read, and the running
+/// total is checked as chunks arrive in case it's absent or wrong.
+async fn read_capped(mut response: reqwest::Response, max_bytes: usize) -> Result<Vec<u8>, String> {
+    let too_large = || "Artwork too large".to_string();
+    if response.content_length().is_some_and(|len| len > max_bytes as u64) {
+        return Err(too_large());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read artwork: {}", e))?
+    {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(too_large());
         }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// GET /spotify/track/{id}/artwork - Album art served (and cached) by us, so
+/// clients avoid Spotify's expiring CDN URLs and CORS
+pub async fn spotify_artwork_route(
+    State(_database): State<Database>,
+    Path(track_id): Path<String>,
+    Query(params): Query<ArtworkQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    check_spotify_ids([track_id.as_str()])?;
+    let cache_key = format!("spotify:artwork:{}:{}", track_id, params.size.name());
+    let cache_headers = |content_type: String| {
+        [
+            (axum::http::header::CONTENT_TYPE, content_type),
+            (
+                axum::http::header::CACHE_CONTROL,
+                "public, max-age=31536000, immutable".to_string(),
+            ),
+        ]
     };
 
+    if let Some(bytes) = cache::get_bytes(&cache_key).await {
+        let content_type = cache::get_json::<String>(&format!("{}:content-type", cache_key))
+            .await
+            .unwrap_or_else(|| "image/jpeg".to_string());
+        return Ok((cache_headers(content_type), bytes).into_response());
+    }
+
+    let access_token = bearer_or_app_token(&headers).await?;
+
     match SPOTIFY_CONTROLLER
-        .get_audio_features(&access_token, &params.ids)
+        .get_track_artwork(&access_token, &track_id, params.size)
         .await
     {
-        Ok(features) => Json(features).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e})),
-        )
-            .into_response(),
+        Ok((bytes, content_type)) => {
+            cache::set_bytes(&cache_key, &bytes, ARTWORK_CACHE_TTL_SECS).await;
+            cache::set_json(&format!("{}:content-type", cache_key), &content_type, ARTWORK_CACHE_TTL_SECS).await;
+            Ok((cache_headers(content_type), bytes).into_response())
+        }
+        Err(e) if e == "Track not found" || e == "Track has no artwork" => Err(ApiError::not_found(e)),
+        Err(e) => {
+            error!("Failed to get artwork for {}: {}", track_id, e);
+            Err(ApiError::bad_gateway(e))
+        }
     }
 }
 
-/// GET /spotify/recommendations - Get track recommendations
-pub async fn spotify_recommendations_route(
+/// GET /spotify/track/{id}/preview - Proxy the track's 30s preview MP3,
+/// with range support so the player can seek
+pub async fn spotify_preview_route(
     State(_database): State<Database>,
-    Query(params): Query<RecommendationsQuery>,
+    Path(track_id): Path<String>,
+    Query(params): Query<PreviewQuery>,
     headers: axum::http::HeaderMap,
-) -> impl IntoResponse {
-    let access_token = match headers.get("Authorization") {
-        Some(h) => h.to_str().unwrap_or("").replace("Bearer ", ""),
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "No authorization header"})),
-            )
-                .into_response();
+) -> Result<Response, ApiError> {
+    check_spotify_ids([track_id.as_str()])?;
+    let market = resolve_market(params.market.as_deref(), &headers).await?;
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    let track = match SPOTIFY_CONTROLLER.get_track(&access_token, &track_id, market.as_deref()).await {
+        Ok(track) => track,
+        Err(e) if e == "Track not found" => return Err(ApiError::not_found(e)),
+        Err(e) => {
+            error!("Failed to get track {} for preview: {}", track_id, e);
+            return Err(ApiError::bad_gateway(e));
         }
     };
 
+    let preview_url = track
+        .preview_url
+        .ok_or_else(|| ApiError::not_found("Track has no preview"))?;
+
+    Ok(audio_proxy::proxy_audio(&SPOTIFY_CONTROLLER.client, &preview_url, &headers).await)
+}
+
+/// GET /spotify/genres - List genres accepted as recommendation seeds
+pub async fn spotify_genres_route(
+    State(_database): State<Database>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<GenreSeedsResponse>, ApiError> {
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    SPOTIFY_CONTROLLER
+        .get_available_genre_seeds(&access_token)
+        .await
+        .map(|genres| Json(GenreSeedsResponse { genres }))
+        .map_err(ApiError::internal)
+}
+
+/// GET /spotify/devices - List the user's Spotify Connect devices
+pub async fn spotify_devices_route(
+    State(_database): State<Database>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<DevicesResponse>, ApiError> {
+    let access_token = bearer_token(&headers)?;
+
+    SPOTIFY_CONTROLLER
+        .get_devices(&access_token)
+        .await
+        .map(|devices| Json(DevicesResponse { devices }))
+        .map_err(ApiError::bad_gateway)
+}
+
+/// PUT /spotify/player - Transfer, play or pause on a Spotify Connect device,
+/// so Premium users can play full tracks on their own speakers
+pub async fn spotify_player_route(
+    State(_database): State<Database>,
+    headers: axum::http::HeaderMap,
+    Json(command): Json<PlayerCommand>,
+) -> Result<StatusCode, ApiError> {
+    let access_token = bearer_token(&headers)?;
+
+    match SPOTIFY_CONTROLLER.control_player(&access_token, &command).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) if e == PREMIUM_REQUIRED => Err(ApiError::forbidden(e).with_code("premium_required")),
+        Err(e) if e == NO_ACTIVE_DEVICE => Err(ApiError::not_found(e).with_code("no_active_device")),
+        Err(e) => {
+            error!("Spotify player command failed: {}", e);
+            Err(ApiError::bad_gateway(e))
+        }
+    }
+}
+
+/// GET /spotify/artist/{id} - Get artist details and genre tags
+pub async fn spotify_artist_route(
+    State(_database): State<Database>,
+    Path(artist_id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<SpotifyArtist>, ApiError> {
+    check_spotify_ids([artist_id.as_str()])?;
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    match SPOTIFY_CONTROLLER.get_artist(&access_token, &artist_id).await {
+        Ok(artist) => Ok(Json(artist)),
+        Err(e) if e == "Artist not found" => Err(ApiError::not_found(e)),
+        Err(e) => Err(ApiError::internal(e)),
+    }
+}
+
+/// GET /spotify/artist/{id}/related - Get related artists for artist-seeded mixes
+pub async fn spotify_related_artists_route(
+    State(_database): State<Database>,
+    Path(artist_id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_spotify_ids([artist_id.as_str()])?;
+    let access_token = bearer_or_app_token(&headers).await?;
+
     match SPOTIFY_CONTROLLER
-        .get_recommendations(
-            &access_token,
-            params.seed_tracks.as_deref(),
-            params.seed_artists.as_deref(),
-            params.seed_genres.as_deref(),
-            params.target_tempo,
-            params.target_energy,
-            params.limit,
-        )
+        .get_related_artists(&access_token, &artist_id)
         .await
     {
-        Ok(recs) => Json(recs).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e})),
-        )
+        Ok(artists) => Ok(Json(serde_json::json!({"artists": artists}))),
+        Err(e) if e == "Artist not found" => Err(ApiError::not_found(e)),
+        Err(e) => Err(ApiError::internal(e)),
+    }
+}
+
+/// POST /spotify/enrich - Build mix tracks (metadata + audio features) for Spotify ids
+pub async fn spotify_enrich_route(
+    State(_database): State<Database>,
+    headers: axum::http::HeaderMap,
+    Json(body): Json<EnrichTracksRequest>,
+) -> Result<Json<Vec<CreateTrackRequest>>, ApiError> {
+    check_spotify_ids(body.spotify_ids.iter().map(String::as_str))?;
+    let access_token = bearer_token(&headers)?;
+
+    enrich::enrich_tracks(&access_token, &body.spotify_ids)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Track enrichment failed: {}", e);
+            ApiError::bad_gateway(e)
+        })
+}
+
+/// POST /spotify/audio-profile - Mean/median tempo, energy, valence and
+/// danceability, BPM range and dominant key across a set of tracks
+pub async fn spotify_audio_profile_route(
+    State(_database): State<Database>,
+    headers: axum::http::HeaderMap,
+    Json(body): Json<AudioProfileRequest>,
+) -> Result<Json<AudioProfile>, ApiError> {
+    if body.spotify_ids.is_empty() {
+        return Err(ApiError::bad_request("At least one track is required"));
+    }
+    if body.spotify_ids.len() > MAX_AUDIO_PROFILE_TRACKS {
+        return Err(ApiError::bad_request(format!(
+            "At most {} tracks can be profiled at once",
+            MAX_AUDIO_PROFILE_TRACKS
+        )));
+    }
+    check_spotify_ids(body.spotify_ids.iter().map(String::as_str))?;
+    let access_token = bearer_or_app_token(&headers).await?;
+
+    let features = enrich::fetch_audio_features(&access_token, &body.spotify_ids)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch audio features for profile: {}", e);
+            ApiError::bad_gateway(e)
+        })?;
+    Ok(Json(build_profile(&body.spotify_ids, &features)))
+}
+
+/// Resolve a Spotify track to its best-matching YouTube video, optionally
+/// extracting its stream too
+pub async fn resolve_track_to_youtube(
+    access_token: &str,
+    track_id: &str,
+    market: Option<&str>,
+    features: Option<&serde_json::Value>,
+    with_stream: bool,
+) -> Result<TrackToYoutubeResponse, ApiError> {
+    let track = match SPOTIFY_CONTROLLER.get_track(access_token, track_id, market).await {
+        Ok(t) => t,
+        Err(e) if e == "Track not found" => return Err(ApiError::not_found(e)),
+        Err(e) => return Err(ApiError::internal(e)),
+    };
+
+    let query = match track.artists.first() {
+        Some(artist) => format!("{} - {}", artist, track.title),
+        None => track.title.clone(),
+    };
+
+    let video = SONG_CONTROLLER
+        .resolve_best_match(&query, track.duration_ms)
+        .await
+        .map_err(|e| {
+            error!("Failed to resolve YouTube video for '{}': {}", query, e);
+            song_api_error(&e, StatusCode::BAD_GATEWAY)
+        })?;
+
+    let duration_matched = video
+        .duration_ms
+        .is_some_and(|d| (d - track.duration_ms).abs() <= DURATION_MATCH_TOLERANCE_MS);
+    info!(
+        "Resolved Spotify track {} to YouTube video {} (duration match: {})",
+        track.id, video.video_id, duration_matched
+    );
+
+    let mut unified = UnifiedTrack::builder(track.clone())
+        .audio_features(features)
+        .youtube(&video);
+    if with_stream {
+        let stream = SONG_CONTROLLER
+            .resolve_stream(&video.video_id)
+            .await
+            .map_err(|e| {
+                error!("Failed to extract stream for {}: {}", video.video_id, e);
+                song_api_error(&e, StatusCode::BAD_GATEWAY)
+            })?;
+        unified = unified.stream(stream);
+    }
+
+    Ok(TrackToYoutubeResponse {
+        spotify: track,
+        youtube: video,
+        duration_matched,
+        track: unified.build(),
+    })
+}
+
+/// Audio features keyed by track id; the tracks still resolve without them
+async fn features_or_empty(access_token: &str, spotify_ids: &[String]) -> HashMap<String, serde_json::Value> {
+    enrich::fetch_audio_features(access_token, spotify_ids)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Resolving without audio features: {}", e);
+            HashMap::new()
+        })
+}
+
+/// Resolve every track of a mix concurrently (bounded), keeping the input
+/// order. A failed track is reported in its slot rather than failing the batch.
+pub async fn resolve_tracks_to_youtube(access_token: &str, spotify_ids: &[String]) -> Vec<YoutubeResolution> {
+    let semaphore = Arc::new(Semaphore::new(YOUTUBE_RESOLVE_CONCURRENCY));
+    let features = features_or_empty(access_token, spotify_ids).await;
+
+    join_all(spotify_ids.iter().map(|id| {
+        let semaphore = semaphore.clone();
+        let features = features.get(id);
+        async move {
+            let result = match semaphore.acquire().await {
+                Ok(_permit) => resolve_track_to_youtube(access_token, id, None, features, false).await,
+                Err(e) => Err(ApiError::internal(e.to_string())),
+            };
+            match result {
+                Ok(resolved) => YoutubeResolution {
+                    spotify_id: id.clone(),
+                    resolved: true,
+                    result: Some(resolved),
+                    error: None,
+                },
+                Err(e) => YoutubeResolution {
+                    spotify_id: id.clone(),
+                    resolved: false,
+                    result: None,
+                    error: Some(e.message().to_string()),
+                },
+            }
+        }
+    }))
+    .await
+}
+
+/// GET /spotify/track-to-youtube - Resolve a Spotify track to its YouTube video
+pub async fn spotify_track_to_youtube_route(
+    State(_database): State<Database>,
+    Query(params): Query<TrackToYoutubeQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<TrackToYoutubeResponse>, ApiError> {
+    check_spotify_ids([params.track_id.as_str()])?;
+    let market = resolve_market(params.market.as_deref(), &headers).await?;
+    let access_token = bearer_token(&headers)?;
+
+    let features = features_or_empty(&access_token, std::slice::from_ref(&params.track_id)).await;
+    resolve_track_to_youtube(
+        &access_token,
+        &params.track_id,
+        market.as_deref(),
+        features.get(&params.track_id),
+        params.stream,
+    )
+    .await
+    .map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Form, Router, routing::{get, post}};
+    use std::collections::HashMap;
+
+    /// Serve `router` on an ephemeral local port, returning its base URL
+    async fn fake_spotify(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn controller_for(base: &str) -> SpotifyController {
+        SpotifyController::with_base_urls(base, &format!("{}/v1", base))
+    }
+
+    #[test]
+    fn parses_scope_lists() {
+        assert_eq!(parse_scopes(DEFAULT_SPOTIFY_SCOPES).as_deref(), Ok(DEFAULT_SPOTIFY_SCOPES));
+        assert_eq!(
+            parse_scopes("  user-read-private\n streaming ").as_deref(),
+            Ok("user-read-private streaming")
+        );
+
+        let err = parse_scopes("user-read-private user-read-everything").unwrap_err();
+        assert!(err.contains("user-read-everything"), "{}", err);
+        assert!(!err.contains("user-read-private"), "{}", err);
+
+        assert!(parse_scopes("").is_err());
+        assert!(parse_scopes("   ").is_err());
+    }
+
+    #[test]
+    fn drops_explicit_tracks_from_raw_results() {
+        let mut tracks = serde_json::json!([
+            {"id": "a", "explicit": true},
+            {"id": "b", "explicit": false},
+            {"id": "c"}
+        ]);
+        retain_clean_tracks(Some(&mut tracks));
+        assert_eq!(tracks, serde_json::json!([{"id": "b", "explicit": false}, {"id": "c"}]));
+
+        // Missing or non-array track lists are left alone
+        retain_clean_tracks(None);
+        let mut not_a_list = serde_json::json!({"explicit": true});
+        retain_clean_tracks(Some(&mut not_a_list));
+        assert_eq!(not_a_list, serde_json::json!({"explicit": true}));
+    }
+
+    #[test]
+    fn profile_cache_key_hides_the_token() {
+        let key = user_profile_cache_key("BQD-secret-token");
+        assert!(key.starts_with("spotify:me:"));
+        assert!(!key.contains("secret"));
+        assert_eq!(key, user_profile_cache_key("BQD-secret-token"));
+        assert_ne!(key, user_profile_cache_key("another-token"));
+    }
+
+    async fn token_endpoint(Form(form): Form<HashMap<String, String>>) -> Response {
+        match (form.get("grant_type").map(String::as_str), form.get("code"), form.get("refresh_token")) {
+            (Some("authorization_code"), Some(code), _) if code == "good-code" => Json(serde_json::json!({
+                "access_token": "access-1",
+                "refresh_token": "refresh-1",
+                "expires_in": 3600,
+                "token_type": "Bearer",
+                "scope": "user-read-private"
+            }))
+            .into_response(),
+            // Spotify often omits the refresh token when refreshing
+            (Some("refresh_token"), _, Some(token)) if token == "refresh-1" => Json(serde_json::json!({
+                "access_token": "access-2",
+                "expires_in": 3600,
+                "token_type": "Bearer"
+            }))
+            .into_response(),
+            _ => (StatusCode::BAD_REQUEST, r#"{"error":"invalid_grant"}"#).into_response(),
+        }
+    }
+
+    async fn search_endpoint(
+        headers: axum::http::HeaderMap,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> Response {
+        if headers.get("Authorization").and_then(|h| h.to_str().ok()) != Some("Bearer access-1") {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        match params.get("q").map(String::as_str) {
+            Some("busy") => (StatusCode::TOO_MANY_REQUESTS, [("Retry-After", "7")]).into_response(),
+            Some("broken") => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            // Only licensed in one market
+            Some("regional") if params.get("market").map(String::as_str) != Some("GB") => {
+                Json(serde_json::json!({"tracks": {"items": []}})).into_response()
+            }
+            _ => Json(serde_json::json!({
+                "tracks": {"items": [
+                    {
+                        "id": "t1", "name": "Clean", "duration_ms": 200000, "explicit": false, "popularity": 60,
+                        "artists": [{"name": "A"}], "album": {"name": "Album", "images": []}
+                    },
+                    {
+                        "id": "t2", "name": "Explicit", "duration_ms": 180000, "explicit": true, "popularity": 40,
+                        "artists": [{"name": "B"}], "album": {"name": "Album", "images": []}
+                    }
+                ]}
+            }))
             .into_response(),
+        }
+    }
+
+    fn spotify_router() -> Router {
+        Router::new()
+            .route("/api/token", post(token_endpoint))
+            .route("/v1/search", get(search_endpoint))
+    }
+
+    #[tokio::test]
+    async fn exchanges_and_refreshes_tokens() {
+        let spotify = controller_for(&fake_spotify(spotify_router()).await);
+
+        let tokens = spotify.exchange_code("good-code").await.unwrap();
+        assert_eq!(tokens.access_token, "access-1");
+        assert_eq!(tokens.refresh_token.as_deref(), Some("refresh-1"));
+
+        let refreshed = spotify.refresh_token("refresh-1").await.unwrap();
+        assert_eq!(refreshed.access_token, "access-2");
+        assert_eq!(refreshed.refresh_token.as_deref(), Some("refresh-1"));
+
+        let err = spotify.exchange_code("bad-code").await.unwrap_err();
+        assert!(err.contains("invalid_grant"), "{}", err);
+        assert!(spotify.refresh_token("revoked").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_app_token_fetch() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route(
+            "/api/token",
+            post({
+                let fetches = fetches.clone();
+                move || async move {
+                    fetches.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    Json(serde_json::json!({
+                        "access_token": "app-1",
+                        "expires_in": 3600,
+                        "token_type": "Bearer"
+                    }))
+                }
+            }),
+        );
+        let spotify = controller_for(&fake_spotify(router).await);
+
+        let (a, b) = tokio::join!(spotify.app_tokens(), spotify.app_tokens());
+        assert_eq!(a.unwrap().access_token, "app-1");
+        assert_eq!(b.unwrap().access_token, "app-1");
+        assert_eq!(spotify.app_tokens().await.unwrap().access_token, "app-1");
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn searches_and_filters_explicit_tracks() {
+        let spotify = controller_for(&fake_spotify(spotify_router()).await);
+
+        let raw = spotify.search("access-1", "house", "track", 10, None).await.unwrap();
+        assert_eq!(raw["tracks"]["items"].as_array().unwrap().len(), 2);
+
+        let clean = spotify.search_tracks_normalized("access-1", "house", 10, false, None).await.unwrap();
+        assert_eq!(clean.len(), 1);
+        assert_eq!(clean[0].id, "t1");
+        assert_eq!(clean[0].artists, ["A"]);
+    }
+
+    #[tokio::test]
+    async fn passes_the_market_through() {
+        let spotify = controller_for(&fake_spotify(spotify_router()).await);
+
+        let anywhere = spotify.search_tracks_normalized("access-1", "regional", 10, true, None).await.unwrap();
+        assert!(anywhere.is_empty());
+        let gb = spotify.search_tracks_normalized("access-1", "regional", 10, true, Some("GB")).await.unwrap();
+        assert_eq!(gb.len(), 2);
+    }
+
+    #[test]
+    fn validates_country_codes() {
+        assert_eq!(country_code(None, "market").unwrap(), None);
+        assert_eq!(country_code(Some("gb"), "market").unwrap().as_deref(), Some("GB"));
+        for bad in ["GBR", "G", "1A", ""] {
+            let err = country_code(Some(bad), "market").unwrap_err();
+            assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_search_errors() {
+        let spotify = controller_for(&fake_spotify(spotify_router()).await);
+
+        assert_eq!(spotify.search("access-1", "busy", "track", 10, None).await.unwrap_err(), RATE_LIMITED);
+        assert_eq!(search_error(RATE_LIMITED.to_string()).into_response().status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(spotify.search("access-1", "broken", "track", 10, None).await.unwrap_err(), "Search failed");
+        assert_eq!(spotify.search("expired", "house", "track", 10, None).await.unwrap_err(), "Search failed");
+    }
+
+    fn tokens(refresh_token: Option<&str>, obtained_at: i64) -> SpotifyTokens {
+        SpotifyTokens {
+            access_token: "access-1".to_string(),
+            refresh_token: refresh_token.map(str::to_string),
+            expires_in: 3600,
+            token_type: "Bearer".to_string(),
+            scope: String::new(),
+            obtained_at,
+        }
+    }
+
+    #[test]
+    fn prunes_expired_and_idle_sessions() {
+        let now = 1_000_000_000;
+
+        // No refresh token: kept only while the access token works
+        assert!(session_is_live(&tokens(None, now - 60), now));
+        assert!(!session_is_live(&tokens(None, now - 3600), now));
+
+        // Renewable: kept past expiry, until it's been idle too long
+        assert!(session_is_live(&tokens(Some("refresh-1"), now - 3600 * 24), now));
+        assert!(!session_is_live(&tokens(Some("refresh-1"), now - SESSION_IDLE_TTL_SECS), now));
+    }
+
+    #[tokio::test]
+    async fn validates_stored_sessions() {
+        let spotify = controller_for(&fake_spotify(spotify_router()).await);
+        let now = unix_now();
+        let store = RwLock::new(HashMap::from([
+            ("live".to_string(), tokens(None, now)),
+            ("expired".to_string(), tokens(None, now - 4000)),
+            ("renewable".to_string(), tokens(Some("refresh-1"), now - 4000)),
+            ("revoked".to_string(), tokens(Some("revoked"), now - 4000)),
+        ]));
+        let validate = |id: &str| validate_session(&spotify, &store, id.to_string());
+
+        let live = validate("live").await;
+        assert!(live.valid);
+        assert!(live.expires_in.is_some_and(|secs| secs > 3500), "{:?}", live);
+
+        let expired = validate("expired").await;
+        assert!(!expired.valid);
+        assert_eq!(expired.expires_in, None);
+
+        assert!(!validate("unknown").await.valid);
+        assert!(!validate("revoked").await.valid);
+
+        // An expired session with a refresh token is renewed in place
+        let renewed = validate("renewable").await;
+        assert!(renewed.valid);
+        assert_eq!(renewed.expires_in, Some(3600));
+        assert_eq!(store.read().await["renewable"].access_token, "access-2");
+    }
+
+    #[tokio::test]
+    async fn caps_artwork_size_with_or_without_a_content_length() {
+        let chunked = || async {
+            let chunks = futures_util::stream::iter([Ok::<_, std::io::Error>(vec![0u8; 60]), Ok(vec![0u8; 60])]);
+            axum::body::Body::from_stream(chunks)
+        };
+        let base = fake_spotify(
+            Router::new()
+                .route("/sized", get(|| async { vec![0u8; 120] }))
+                .route("/chunked", get(chunked)),
+        )
+        .await;
+        let fetch = |path: &str| reqwest::get(format!("{}{}", base, path));
+
+        assert_eq!(read_capped(fetch("/sized").await.unwrap(), 200).await.unwrap().len(), 120);
+        assert_eq!(read_capped(fetch("/sized").await.unwrap(), 100).await.unwrap_err(), "Artwork too large");
+        assert_eq!(read_capped(fetch("/chunked").await.unwrap(), 200).await.unwrap().len(), 120);
+        assert_eq!(read_capped(fetch("/chunked").await.unwrap(), 100).await.unwrap_err(), "Artwork too large");
+    }
+
+    #[tokio::test]
+    async fn unreachable_spotify_is_an_error() {
+        let spotify = controller_for("http://127.0.0.1:1");
+        let err = spotify.search("access-1", "house", "track", 10, None).await.unwrap_err();
+        assert!(err.starts_with("Request failed"), "{}", err);
     }
 }
@@ -0,0 +1,203 @@
+// Concurrent Spotify enrichment for building mix tracks
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures_util::future::join_all;
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::camelot;
+use crate::controllers::spotify::SPOTIFY_CONTROLLER;
+use crate::models::mix::CreateTrackRequest;
+use crate::models::spotify::SearchResult;
+
+/// Maximum Spotify requests in flight for a single enrichment
+const ENRICH_CONCURRENCY: usize = 8;
+
+/// Spotify's limit on ids per `/audio-features` call
+const AUDIO_FEATURES_BATCH_SIZE: usize = 100;
+
+/// Fallback key used by the orchestrator when Spotify doesn't know the key
+const UNKNOWN_CAMELOT_KEY: &str = "1A";
+
+/// Fetch track metadata and audio features for `spotify_ids` concurrently and
+/// assemble `CreateTrackRequest`s whose `track_order` follows the input order.
+///
+/// Fails if any track can't be found; missing audio features fall back to
+/// neutral values so one unanalysed track doesn't sink the whole mix.
+pub async fn enrich_tracks(
+    access_token: &str,
+    spotify_ids: &[String],
+) -> Result<Vec<CreateTrackRequest>, String> {
+    let semaphore = Arc::new(Semaphore::new(ENRICH_CONCURRENCY));
+
+    let metadata = join_all(spotify_ids.iter().map(|id| {
+        let semaphore = semaphore.clone();
+        async move {
+            let _permit = semaphore.acquire().await.map_err(|e| e.to_string())?;
+            SPOTIFY_CONTROLLER
+                .get_track(access_token, id, None)
+                .await
+                .map_err(|e| format!("{}: {}", id, e))
+        }
+    }));
+
+    let features = features_by_id(access_token, spotify_ids, &semaphore);
+
+    let (metadata, features) = tokio::join!(metadata, features);
+    let mut features_by_id = features?;
+
+    metadata
+        .into_iter()
+        .enumerate()
+        .map(|(order, track)| {
+            let track = track?;
+            let features = features_by_id.remove(&track.id).unwrap_or_else(|| {
+                warn!("No audio features for track {}, using defaults", track.id);
+                serde_json::Value::Null
+            });
+            Ok(to_track_request(track, &features, order as i32))
+        })
+        .collect()
+}
+
+/// Audio features for `spotify_ids`, fetched in batches and keyed by track
+/// id. Tracks Spotify hasn't analysed are absent from the map.
+pub async fn fetch_audio_features(
+    access_token: &str,
+    spotify_ids: &[String],
+) -> Result<HashMap<String, serde_json::Value>, String> {
+    features_by_id(access_token, spotify_ids, &Arc::new(Semaphore::new(ENRICH_CONCURRENCY))).await
+}
+
+async fn features_by_id(
+    access_token: &str,
+    spotify_ids: &[String],
+    semaphore: &Arc<Semaphore>,
+) -> Result<HashMap<String, serde_json::Value>, String> {
+    let batches = join_all(spotify_ids.chunks(AUDIO_FEATURES_BATCH_SIZE).map(|batch| {
+        let semaphore = semaphore.clone();
+        async move {
+            let _permit = semaphore.acquire().await.map_err(|e| e.to_string())?;
+            SPOTIFY_CONTROLLER
+                .get_audio_features(access_token, &batch.join(","))
+                .await
+        }
+    }))
+    .await;
+
+    let mut features_by_id = HashMap::new();
+    for batch in batches {
+        index_features(&batch?, &mut features_by_id);
+    }
+    Ok(features_by_id)
+}
+
+/// Add each entry of an `/audio-features` response to `features_by_id`.
+/// Spotify returns `null` for tracks it hasn't analysed; those are skipped.
+fn index_features(batch: &serde_json::Value, features_by_id: &mut HashMap<String, serde_json::Value>) {
+    for feature in batch["audio_features"].as_array().into_iter().flatten() {
+        if let Some(id) = feature["id"].as_str() {
+            features_by_id.insert(id.to_string(), feature.clone());
+        }
+    }
+}
+
+/// Overwrite `track`'s key and audio features with Spotify's analysis, so
+/// a track supplied by a client is mixed on the same data as generated ones
+pub async fn apply_audio_features(access_token: &str, track: &mut CreateTrackRequest) -> Result<(), String> {
+    let body = SPOTIFY_CONTROLLER
+        .get_audio_features(access_token, &track.spotify_id)
+        .await?;
+    let features = body["audio_features"]
+        .as_array()
+        .and_then(|features| features.first())
+        .filter(|features| !features.is_null())
+        .ok_or_else(|| format!("No audio features for track {}", track.spotify_id))?;
+
+    let feature = |name: &str, current: f64| features[name].as_f64().unwrap_or(current);
+    if let Some(key) = camelot::to_camelot(
+        features["key"].as_i64().unwrap_or(-1) as i32,
+        features["mode"].as_i64().unwrap_or(-1) as i32,
+    ) {
+        track.key = key;
+    }
+    track.energy = feature("energy", track.energy);
+    track.danceability = feature("danceability", track.danceability);
+    track.valence = feature("valence", track.valence);
+    track.acousticness = feature("acousticness", track.acousticness);
+    track.instrumentalness = feature("instrumentalness", track.instrumentalness);
+    Ok(())
+}
+
+fn to_track_request(track: SearchResult, features: &serde_json::Value, track_order: i32) -> CreateTrackRequest {
+    let feature = |name: &str| features[name].as_f64().unwrap_or(0.5);
+    let key = camelot::to_camelot(
+        features["key"].as_i64().unwrap_or(-1) as i32,
+        features["mode"].as_i64().unwrap_or(-1) as i32,
+    )
+    .unwrap_or_else(|| UNKNOWN_CAMELOT_KEY.to_string());
+
+    CreateTrackRequest {
+        spotify_id: track.id,
+        title: track.title,
+        artist: track.artists.join(", "),
+        album: track.album,
+        duration_ms: track.duration_ms as i32,
+        key,
+        energy: feature("energy"),
+        danceability: feature("danceability"),
+        valence: feature("valence"),
+        acousticness: feature("acousticness"),
+        instrumentalness: feature("instrumentalness"),
+        popularity: track.popularity as i32,
+        track_order,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_result() -> SearchResult {
+        SearchResult {
+            id: "4uLU6hMCjMI75M1A2tKUQC".to_string(),
+            title: "One More Time".to_string(),
+            artists: vec!["Daft Punk".to_string(), "Romanthony".to_string()],
+            album: "Discovery".to_string(),
+            image_url: None,
+            duration_ms: 320_357,
+            preview_url: None,
+            explicit: false,
+            popularity: 81,
+        }
+    }
+
+    #[test]
+    fn indexes_features_by_track_id() {
+        let mut features_by_id = HashMap::new();
+        index_features(
+            &serde_json::json!({"audio_features": [{"id": "a", "energy": 0.9}, null, {"energy": 0.1}]}),
+            &mut features_by_id,
+        );
+        index_features(&serde_json::json!({"audio_features": [{"id": "b", "energy": 0.2}]}), &mut features_by_id);
+        index_features(&serde_json::json!({}), &mut features_by_id);
+
+        assert_eq!(features_by_id.len(), 2);
+        assert_eq!(features_by_id["a"]["energy"], 0.9);
+        assert_eq!(features_by_id["b"]["energy"], 0.2);
+    }
+
+    #[test]
+    fn builds_track_requests_from_metadata_and_features() {
+        let features = serde_json::json!({"key": 9, "mode": 0, "energy": 0.9, "danceability": 0.8});
+        let track = to_track_request(search_result(), &features, 3);
+        assert_eq!(track.artist, "Daft Punk, Romanthony");
+        assert_eq!(track.key, "8A");
+        assert_eq!((track.energy, track.danceability, track.valence), (0.9, 0.8, 0.5));
+        assert_eq!((track.duration_ms, track.popularity, track.track_order), (320_357, 81, 3));
+
+        let unanalysed = to_track_request(search_result(), &serde_json::Value::Null, 0);
+        assert_eq!(unanalysed.key, UNKNOWN_CAMELOT_KEY);
+        assert_eq!(unanalysed.energy, 0.5);
+    }
+}
@@ -0,0 +1,471 @@
+// Mix export as playlist files or a shareable JSON document
+use std::collections::HashSet;
+use std::sync::Arc;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::camelot::CamelotKey;
+use crate::controllers::song::SONG_CONTROLLER;
+use crate::controllers::spotify::YOUTUBE_RESOLVE_CONCURRENCY;
+use crate::models::mix::{
+    CreateMixRequest, CreateTrackRequest, CreateTransitionRequest, MixData, MixSession, MixTrack,
+    MixTransition, TransitionDirection, TransitionType,
+};
+
+/// Version of the `MixDocument` layout, bumped on incompatible changes
+pub const MIX_DOCUMENT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    M3u,
+    Pls,
+    /// A `MixDocument` that `POST /mix/import` accepts
+    Json,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::M3u => "audio/x-mpegurl",
+            ExportFormat::Pls => "audio/x-scpls",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::M3u => "m3u",
+            ExportFormat::Pls => "pls",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Where playlist entries point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportSource {
+    /// YouTube watch URLs. Direct stream URLs expire within hours, so the
+    /// page URL is used; players like VLC and mpv resolve it themselves.
+    #[default]
+    Youtube,
+    /// `spotify:track:` URIs
+    Spotify,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub source: ExportSource,
+}
+
+/// One track of an exported playlist; `location` is `None` if it couldn't be resolved
+#[derive(Debug, Clone)]
+pub struct PlaylistEntry {
+    pub title: String,
+    pub duration_secs: i64,
+    pub location: Option<String>,
+}
+
+fn display_title(track: &MixTrack) -> String {
+    format!("{} - {}", track.artist, track.title)
+}
+
+/// Best YouTube video id for each track, in track order
+async fn resolve_youtube_ids(tracks: &[MixTrack]) -> Vec<Option<String>> {
+    let semaphore = Arc::new(Semaphore::new(YOUTUBE_RESOLVE_CONCURRENCY));
+
+    join_all(tracks.iter().map(|track| {
+        let semaphore = semaphore.clone();
+        async move {
+            let _permit = semaphore.acquire().await.ok()?;
+            let query = display_title(track);
+            match SONG_CONTROLLER.resolve_best_match(&query, track.duration_ms as i64).await {
+                Ok(video) => Some(video.video_id),
+                Err(e) => {
+                    warn!("Failed to resolve '{}' for export: {}", query, e);
+                    None
+                }
+            }
+        }
+    }))
+    .await
+}
+
+/// Resolve every track of a mix to a playable location, in track order
+pub async fn resolve_entries(mix: &MixData, source: ExportSource) -> Vec<PlaylistEntry> {
+    let locations: Vec<Option<String>> = match source {
+        ExportSource::Spotify => mix
+            .tracks
+            .iter()
+            .map(|t| (!t.spotify_id.is_empty()).then(|| format!("spotify:track:{}", t.spotify_id)))
+            .collect(),
+        ExportSource::Youtube => resolve_youtube_ids(&mix.tracks)
+            .await
+            .into_iter()
+            .map(|id| id.map(|id| format!("https://www.youtube.com/watch?v={}", id)))
+            .collect(),
+    };
+
+    mix.tracks
+        .iter()
+        .zip(locations)
+        .map(|(track, location)| PlaylistEntry {
+            title: display_title(track),
+            duration_secs: (track.duration_ms as i64 + 500) / 1000,
+            location,
+        })
+        .collect()
+}
+
+/// A self-contained mix for sharing: everything `GET /mix/{id}` returns plus
+/// derived fields. `POST /mix/import` turns it back into a new session.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MixDocument {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub session: MixSession,
+    pub tracks: Vec<ExportedTrack>,
+    pub transitions: Vec<MixTransition>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedTrack {
+    /// Includes the derived `compatible_keys`
+    #[serde(flatten)]
+    pub track: MixTrack,
+    /// Best YouTube match at export time; ignored on import
+    #[serde(default)]
+    pub youtube_video_id: Option<String>,
+}
+
+/// Build the JSON export, resolving a YouTube id for each track
+pub async fn build_document(mix: MixData) -> MixDocument {
+    let youtube_ids = resolve_youtube_ids(&mix.tracks).await;
+    document_from(mix, youtube_ids)
+}
+
+/// The orchestrator adds a transition out of the final track, into one that
+/// doesn't exist; it has no place in a shared document
+fn is_trailing_transition(transition: &MixTransition, track_count: usize) -> bool {
+    transition.to_track_order >= track_count as i32
+        && transition.from_track_order == track_count as i32 - 1
+}
+
+fn document_from(mix: MixData, youtube_ids: Vec<Option<String>>) -> MixDocument {
+    let track_count = mix.tracks.len();
+    MixDocument {
+        version: MIX_DOCUMENT_VERSION,
+        exported_at: Utc::now(),
+        session: mix.session,
+        tracks: mix
+            .tracks
+            .into_iter()
+            .zip(youtube_ids)
+            .map(|(track, youtube_video_id)| ExportedTrack { track, youtube_video_id })
+            .collect(),
+        transitions: mix
+            .transitions
+            .into_iter()
+            .filter(|t| !is_trailing_transition(t, track_count))
+            .collect(),
+    }
+}
+
+fn unit_range(name: &str, value: f64, problems: &mut Vec<String>, track: usize) {
+    if !(0.0..=1.0).contains(&value) {
+        problems.push(format!("tracks[{}].{} must be between 0 and 1", track, name));
+    }
+}
+
+/// Check an imported document and convert it into a `CreateMixRequest`.
+/// Every problem found is returned, not just the first.
+pub fn validate_document(mut document: MixDocument, max_tracks: usize) -> Result<CreateMixRequest, Vec<String>> {
+    let mut problems = Vec::new();
+
+    // Documents exported before the trailing transition was dropped still carry it
+    let track_count = document.tracks.len();
+    document.transitions.retain(|t| !is_trailing_transition(t, track_count));
+
+    if document.version != MIX_DOCUMENT_VERSION {
+        problems.push(format!(
+            "Unsupported document version {}, expected {}",
+            document.version, MIX_DOCUMENT_VERSION
+        ));
+    }
+    if document.tracks.is_empty() {
+        problems.push("tracks must not be empty".to_string());
+    }
+    if document.tracks.len() > max_tracks {
+        problems.push(format!("tracks must have at most {} entries", max_tracks));
+    }
+    if document.session.estimated_duration_minutes.is_some_and(|d| d <= 0.0) {
+        problems.push("session.estimated_duration_minutes must be positive".to_string());
+    }
+
+    // Orders must be exactly 0..n so transitions can refer to them
+    let orders: HashSet<i32> = document.tracks.iter().map(|t| t.track.track_order).collect();
+    let expected: HashSet<i32> = (0..document.tracks.len() as i32).collect();
+    if orders != expected {
+        problems.push("track_order values must be unique and run from 0 to the number of tracks minus 1".to_string());
+    }
+
+    for (i, exported) in document.tracks.iter().enumerate() {
+        let track = &exported.track;
+        for (name, value) in [("spotify_id", &track.spotify_id), ("title", &track.title), ("artist", &track.artist)] {
+            if value.trim().is_empty() {
+                problems.push(format!("tracks[{}].{} must not be empty", i, name));
+            }
+        }
+        if track.duration_ms <= 0 {
+            problems.push(format!("tracks[{}].duration_ms must be positive", i));
+        }
+        if let Err(e) = track.key.parse::<CamelotKey>() {
+            problems.push(format!("tracks[{}].key: {}", i, e));
+        }
+        unit_range("energy", track.energy, &mut problems, i);
+        unit_range("danceability", track.danceability, &mut problems, i);
+        unit_range("valence", track.valence, &mut problems, i);
+        unit_range("acousticness", track.acousticness, &mut problems, i);
+        unit_range("instrumentalness", track.instrumentalness, &mut problems, i);
+        if !(0..=100).contains(&track.popularity) {
+            problems.push(format!("tracks[{}].popularity must be between 0 and 100", i));
+        }
+    }
+
+    for (i, transition) in document.transitions.iter().enumerate() {
+        if !orders.contains(&transition.from_track_order) || !orders.contains(&transition.to_track_order) {
+            problems.push(format!("transitions[{}] refers to a track that doesn't exist", i));
+        }
+        if transition.to_track_order != transition.from_track_order + 1 {
+            problems.push(format!("transitions[{}] must join consecutive tracks", i));
+        }
+        if transition.transition_bars <= 0 {
+            problems.push(format!("transitions[{}].transition_bars must be positive", i));
+        }
+        if let Err(e) = transition.transition_type.parse::<TransitionType>() {
+            problems.push(format!("transitions[{}]: {}", i, e));
+        }
+        if let Some(Err(e)) = transition.transition_direction.as_deref().map(str::parse::<TransitionDirection>) {
+            problems.push(format!("transitions[{}]: {}", i, e));
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    Ok(CreateMixRequest {
+        prompt: document.session.prompt,
+        estimated_duration_minutes: document.session.estimated_duration_minutes,
+        tracks: document
+            .tracks
+            .into_iter()
+            .map(|ExportedTrack { track, .. }| CreateTrackRequest {
+                spotify_id: track.spotify_id,
+                title: track.title,
+                artist: track.artist,
+                album: track.album,
+                duration_ms: track.duration_ms,
+                key: track.key,
+                energy: track.energy,
+                danceability: track.danceability,
+                valence: track.valence,
+                acousticness: track.acousticness,
+                instrumentalness: track.instrumentalness,
+                popularity: track.popularity,
+                track_order: track.track_order,
+            })
+            .collect(),
+        transitions: document
+            .transitions
+            .into_iter()
+            .map(|t| CreateTransitionRequest {
+                from_track_order: t.from_track_order,
+                to_track_order: t.to_track_order,
+                transition_type: t.transition_type,
+                transition_bars: t.transition_bars,
+                transition_direction: t.transition_direction,
+            })
+            .collect(),
+    })
+}
+
+/// Extended M3U; unresolved tracks are listed as comments
+pub fn render_m3u(name: &str, entries: &[PlaylistEntry]) -> String {
+    let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", single_line(name));
+    for entry in entries {
+        match &entry.location {
+            Some(location) => {
+                out.push_str(&format!("#EXTINF:{},{}\n{}\n", entry.duration_secs, single_line(&entry.title), location));
+            }
+            None => out.push_str(&format!("# Unresolved: {}\n", single_line(&entry.title))),
+        }
+    }
+    out
+}
+
+/// PLS v2; unresolved tracks are listed as `;` comments after the entries
+pub fn render_pls(entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from("[playlist]\n");
+    let resolved: Vec<_> = entries.iter().filter_map(|e| Some((e, e.location.as_ref()?))).collect();
+    for (i, (entry, location)) in resolved.iter().enumerate() {
+        let n = i + 1;
+        out.push_str(&format!(
+            "File{n}={}\nTitle{n}={}\nLength{n}={}\n",
+            location,
+            single_line(&entry.title),
+            entry.duration_secs
+        ));
+    }
+    out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", resolved.len()));
+    for entry in entries.iter().filter(|e| e.location.is_none()) {
+        out.push_str(&format!("; Unresolved: {}\n", single_line(&entry.title)));
+    }
+    out
+}
+
+/// Titles come from Spotify and may contain line breaks, which would
+/// corrupt line-based playlist formats
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<PlaylistEntry> {
+        vec![
+            PlaylistEntry {
+                title: "Daft Punk - One More Time".to_string(),
+                duration_secs: 320,
+                location: Some("https://www.youtube.com/watch?v=abc".to_string()),
+            },
+            PlaylistEntry {
+                title: "Unknown - Lost\nTrack".to_string(),
+                duration_secs: 200,
+                location: None,
+            },
+            PlaylistEntry {
+                title: "Justice - D.A.N.C.E.".to_string(),
+                duration_secs: 242,
+                location: Some("https://www.youtube.com/watch?v=def".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn renders_m3u_with_unresolved_comment() {
+        assert_eq!(
+            render_m3u("Sunset house", &entries()),
+            "#EXTM3U\n#PLAYLIST:Sunset house\n\
+             #EXTINF:320,Daft Punk - One More Time\nhttps://www.youtube.com/watch?v=abc\n\
+             # Unresolved: Unknown - Lost Track\n\
+             #EXTINF:242,Justice - D.A.N.C.E.\nhttps://www.youtube.com/watch?v=def\n"
+        );
+    }
+
+    #[test]
+    fn renders_pls_numbering_only_resolved_entries() {
+        assert_eq!(
+            render_pls(&entries()),
+            "[playlist]\n\
+             File1=https://www.youtube.com/watch?v=abc\nTitle1=Daft Punk - One More Time\nLength1=320\n\
+             File2=https://www.youtube.com/watch?v=def\nTitle2=Justice - D.A.N.C.E.\nLength2=242\n\
+             NumberOfEntries=2\nVersion=2\n\
+             ; Unresolved: Unknown - Lost Track\n"
+        );
+    }
+
+    fn document() -> serde_json::Value {
+        let session_id = uuid::Uuid::new_v4();
+        let track = |order: i32, key: &str| serde_json::json!({
+            "id": uuid::Uuid::new_v4(), "mix_session_id": session_id,
+            "spotify_id": format!("sp{}", order), "title": "Title", "artist": "Artist", "album": "Album",
+            "duration_ms": 200_000, "key": key, "energy": 0.7, "danceability": 0.8, "valence": 0.5,
+            "acousticness": 0.1, "instrumentalness": 0.2, "popularity": 60, "track_order": order,
+            "compatible_keys": [], "youtube_video_id": "abc",
+        });
+        serde_json::json!({
+            "version": 1,
+            "exported_at": "2026-01-01T00:00:00Z",
+            "session": {
+                "id": session_id, "prompt": "sunset house", "status": "completed",
+                "created_at": "2026-01-01T00:00:00Z", "completed_at": null, "error_message": null,
+                "estimated_duration_minutes": 7.0, "cdn_url": null, "user_id": null, "parent_session_id": null,
+            },
+            "tracks": [track(0, "8A"), track(1, "9A")],
+            "transitions": [{
+                "id": uuid::Uuid::new_v4(), "mix_session_id": session_id, "from_track_order": 0,
+                "to_track_order": 1, "transition_type": "crossfade", "transition_bars": 8,
+                "transition_direction": null,
+            }],
+        })
+    }
+
+    #[test]
+    fn imports_a_valid_document() {
+        let document: MixDocument = serde_json::from_value(document()).unwrap();
+        let request = validate_document(document, 60).unwrap();
+        assert_eq!(request.prompt, "sunset house");
+        assert_eq!(request.tracks.len(), 2);
+        assert_eq!(request.tracks[1].key, "9A");
+        assert_eq!(request.transitions[0].transition_type, "crossfade");
+    }
+
+    #[test]
+    fn round_trips_a_generated_mix() {
+        let mut raw = document();
+        // Generated mixes store a transition out of the final track
+        let mut trailing = raw["transitions"][0].clone();
+        trailing["id"] = serde_json::json!(uuid::Uuid::new_v4());
+        trailing["from_track_order"] = 1.into();
+        trailing["to_track_order"] = 2.into();
+        raw["transitions"].as_array_mut().unwrap().push(trailing);
+        let imported: MixDocument = serde_json::from_value(raw.clone()).unwrap();
+
+        // Importing an older export that still carries it works too
+        assert_eq!(validate_document(imported, 60).unwrap().transitions.len(), 1);
+
+        let imported: MixDocument = serde_json::from_value(raw).unwrap();
+        let youtube_ids = imported.tracks.iter().map(|t| t.youtube_video_id.clone()).collect();
+        let mix = MixData {
+            session: imported.session,
+            tracks: imported.tracks.into_iter().map(|t| t.track).collect(),
+            transitions: imported.transitions,
+        };
+        let exported = document_from(mix, youtube_ids);
+        assert_eq!(exported.transitions.len(), 1);
+
+        let json = serde_json::to_value(&exported).unwrap();
+        let request = validate_document(serde_json::from_value(json).unwrap(), 60).unwrap();
+        assert_eq!(request.tracks.len(), 2);
+        assert_eq!(request.transitions.len(), 1);
+        assert_eq!((request.transitions[0].from_track_order, request.transitions[0].to_track_order), (0, 1));
+    }
+
+    #[test]
+    fn reports_every_problem_in_a_document() {
+        let mut raw = document();
+        raw["tracks"][0]["key"] = "13Z".into();
+        raw["tracks"][1]["energy"] = 1.5.into();
+        raw["tracks"][1]["track_order"] = 0.into();
+        raw["transitions"][0]["transition_type"] = "scratch".into();
+        let document: MixDocument = serde_json::from_value(raw).unwrap();
+
+        let problems = validate_document(document, 60).unwrap_err();
+        // The duplicate order also leaves the transition pointing at a missing track
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert!(problems.iter().any(|p| p.contains("tracks[0].key")));
+        assert!(problems.iter().any(|p| p.contains("tracks[1].energy")));
+        assert!(problems.iter().any(|p| p.contains("track_order")));
+        assert!(problems.iter().any(|p| p.contains("doesn't exist")));
+        assert!(problems.iter().any(|p| p.contains("scratch")));
+    }
+}
@@ -0,0 +1,152 @@
+// In-flight mix generation tracking
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::secrets::SECRET_MANAGER;
+
+/// Generations dispatched by this instance, released when the progress
+/// recorder sees their `complete` or `error` message.
+///
+/// The count lives in process memory, so the limit is per instance: with N
+/// replicas behind a load balancer up to N times
+/// `MIX_MAX_CONCURRENT_GENERATIONS_PER_INSTANCE` can reach the orchestrator.
+pub static GENERATIONS: Lazy<GenerationTracker> = Lazy::new(|| {
+    let limit = SECRET_MANAGER
+        .get("MIX_MAX_CONCURRENT_GENERATIONS_PER_INSTANCE")
+        .parse::<usize>()
+        .unwrap_or(10);
+    let timeout_secs = SECRET_MANAGER
+        .get("MIX_GENERATION_TIMEOUT_SECS")
+        .parse::<u64>()
+        .unwrap_or(900);
+    GenerationTracker::new(limit, Duration::from_secs(timeout_secs))
+});
+
+/// Counts generations from dispatch until they finish. A slot is reserved
+/// before the orchestrator is called and becomes tied to the session id once
+/// the orchestrator returns one; sessions that never report back are dropped
+/// after `timeout`.
+#[derive(Debug)]
+pub struct GenerationTracker {
+    limit: usize,
+    timeout: Duration,
+    active: AtomicUsize,
+    sessions: Mutex<HashMap<Uuid, Instant>>,
+}
+
+/// A reserved generation; released on drop unless `track`ed
+#[derive(Debug)]
+pub struct GenerationSlot<'a> {
+    tracker: &'a GenerationTracker,
+    armed: bool,
+}
+
+impl GenerationSlot<'_> {
+    /// Keep the slot until `session_id` completes, errors or times out
+    pub fn track(mut self, session_id: Uuid) {
+        let mut sessions = self.tracker.sessions.lock().unwrap();
+        if sessions.insert(session_id, Instant::now()).is_none() {
+            self.armed = false;
+        }
+    }
+}
+
+impl Drop for GenerationSlot<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.tracker.active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl GenerationTracker {
+    pub fn new(limit: usize, timeout: Duration) -> Self {
+        Self {
+            limit,
+            timeout,
+            active: AtomicUsize::new(0),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Reserve a slot, or `None` when `limit` generations are already running
+    pub fn try_acquire(&self) -> Option<GenerationSlot<'_>> {
+        self.expire_stale(Instant::now());
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n < self.limit).then_some(n + 1))
+            .ok()
+            .map(|_| GenerationSlot { tracker: self, armed: true })
+    }
+
+    /// Release a tracked session; repeat calls and unknown ids are no-ops
+    pub fn finish(&self, session_id: &Uuid) {
+        if self.sessions.lock().unwrap().remove(session_id).is_some() {
+            self.active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Generations currently counted against the limit
+    pub fn active(&self) -> usize {
+        self.expire_stale(Instant::now());
+        self.active.load(Ordering::SeqCst)
+    }
+
+    fn expire_stale(&self, now: Instant) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|session_id, started| {
+            let alive = now.duration_since(*started) < self.timeout;
+            if !alive {
+                warn!("Mix generation {} never finished, releasing its slot", session_id);
+            }
+            alive
+        });
+        let expired = before - sessions.len();
+        if expired > 0 {
+            self.active.fetch_sub(expired, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_and_releases_generations() {
+        let tracker = GenerationTracker::new(2, Duration::from_secs(900));
+        let session = Uuid::new_v4();
+
+        tracker.try_acquire().unwrap().track(session);
+        let reserved = tracker.try_acquire().unwrap();
+        assert!(tracker.try_acquire().is_none());
+        assert_eq!(tracker.active(), 2);
+
+        // A reservation that never got a session id is released on drop
+        drop(reserved);
+        assert_eq!(tracker.active(), 1);
+
+        tracker.finish(&session);
+        tracker.finish(&session);
+        assert_eq!(tracker.active(), 0);
+    }
+
+    #[test]
+    fn expires_sessions_that_never_finish() {
+        let tracker = GenerationTracker::new(1, Duration::from_secs(60));
+        tracker.try_acquire().unwrap().track(Uuid::new_v4());
+        assert!(tracker.try_acquire().is_none());
+
+        tracker.expire_stale(Instant::now() + Duration::from_secs(61));
+        assert_eq!(tracker.active(), 0);
+    }
+}
@@ -1,88 +1,246 @@
-use tokio;
 use axum::{
     routing::get,
     routing::post,
-    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, Path, State},
-    response::IntoResponse,
+    routing::put,
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, ConnectInfo, Path, Query, State},
+    response::{IntoResponse, Response},
     Router,
     body::Bytes,
     Json,
 };
 use futures_util::{SinkExt, StreamExt};
 use tracing_subscriber::{fmt, EnvFilter};
-use tracing::{info, error, debug, warn, Level};
+use tracing::{info, error, debug, warn};
 use tower_http::trace::TraceLayer;
 use tower_http::cors::{CorsLayer, Any};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use crate::secrets::SECRET_MANAGER;
 mod models;
 mod controllers;
 mod routers;
 mod db;
-use routers::{health_check_route, root_route, spotify_routes};
+mod audio_profile;
+mod audio_proxy;
+mod audio_source;
+mod auth;
+mod cache;
+mod camelot;
+mod circuit_breaker;
+mod compression;
+mod enrich;
+mod error;
+mod export;
+mod generations;
+mod pagination;
+mod planner;
+mod prefetch;
+mod progress;
+mod redis_keys;
+mod redis_pool;
+mod request_id;
+mod retry;
+mod singleflight;
+mod webhooks;
+use routers::{admin_routes, health_check_route, liveness_route, metrics_route, root_route, session_routes, song_routes, spotify_routes};
+use controllers::spotify::{self, ResolveYoutubeRequest};
 use db::Database;
+use redis_pool::{RedisPool, REDIS_POOL};
+use state::AppState;
+use circuit_breaker::ORCHESTRATOR_BREAKER;
+use error::ApiError;
+use generations::GENERATIONS;
+use pagination::Pagination;
+use models::mix::{prompt_fingerprint, sanitize_prompt, MixFeedbackRequest, MixLengthRequest, MixSocketCommand, RegenerateMixRequest, MIX_STATUSES};
 use uuid::Uuid;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
 mod secrets;
+mod state;
+
+/// Whether this deployment accepts WebSocket upgrades (some proxies strip them)
+fn websocket_enabled() -> bool {
+    SECRET_MANAGER.get("WEBSOCKET_ENABLED").to_lowercase() != "false"
+}
+
+/// Token for progress streams, for clients that can't send headers
+#[derive(Debug, serde::Deserialize)]
+struct StreamAuthQuery {
+    token: Option<String>,
+}
 
 /// WebSocket handler for mix progress updates
 async fn ws_mix_handler(
-    State(_database): State<Database>,
+    State(database): State<Database>,
     ws: WebSocketUpgrade,
     Path(session_id): Path<String>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_mix_socket(socket, session_id))
+    Query(params): Query<StreamAuthQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    auth::check_stream_origin(&headers)?;
+    if !websocket_enabled() {
+        return Err(ApiError::service_unavailable("WebSocket transport disabled, use SSE"));
+    }
+
+    let token = auth::stream_token(&headers, params.token);
+    auth::authorize_session(&database, &session_id, token).await?;
+
+    // Echo the token subprotocol back, or browsers drop the connection
+    Ok(ws
+        .protocols([auth::WS_TOKEN_PROTOCOL])
+        .on_upgrade(move |socket| handle_mix_socket(socket, session_id, database, REDIS_POOL.clone())))
 }
 
-async fn handle_mix_socket(mut socket: WebSocket, session_id: String) {
-    info!("WebSocket connected for session: {}", session_id);
+/// Report which progress transports the client should use for a session.
+///
+/// Both transports deliver the same envelope, one JSON object per message:
+/// - `{"type": "connected", "session_id": "<id>"}` once the stream is open
+/// - `{"type": "progress" | "complete" | "error", "data": <orchestrator payload>}`
+///
+/// Payloads that are not valid JSON are delivered as `"data": {"raw": "<payload>"}`.
+/// The stream ends after a `complete` or `error` message. If its Redis
+/// connection drops, the SSE stream sends `{"type": "reconnecting", "attempt": n}`
+/// and resumes once resubscribed.
+///
+/// When Redis pub/sub is unavailable, either transport sends
+/// `{"type": "polling", "interval_secs": n}` and then reports status changes
+/// polled from the database, as `progress`/`complete`/`error` messages whose
+/// data has `"polled": true` and no stage percentage.
+///
+/// A WebSocket client that reads too slowly has intermediate progress frames
+/// dropped; it then gets `{"type": "lagging", "dropped": n}` before the next
+/// frame that goes through. `complete` and `error` are never dropped.
+///
+/// WebSocket clients may also send `{"type": "replay"}` to receive every event
+/// recorded so far as `{"type": "replay", "events": [...]}`, or
+/// `{"type": "ping"}` for a `{"type": "pong"}`.
+async fn mix_transport_handler(Path(session_id): Path<String>) -> impl IntoResponse {
+    let websocket = websocket_enabled();
 
-    // Initialize database connection
-    let database = match Database::new().await {
-        Ok(db) => db,
+    Json(serde_json::json!({
+        "session_id": session_id,
+        "preferred": if websocket { "websocket" } else { "sse" },
+        "websocket": {
+            "supported": websocket,
+            "url": format!("/ws/mix/{}", session_id),
+        },
+        "sse": {
+            "supported": true,
+            "url": format!("/sse/mix/{}", session_id),
+        },
+    }))
+}
+
+/// Issue a token for the session's progress streams, sent to `/ws/mix` as
+/// the `Sec-WebSocket-Protocol: bearer, <token>` pair or to `/sse/mix` as
+/// `?token=`. Owned sessions need the owner's `X-User-Token`; sessions
+/// created anonymously only need their (unguessable) id.
+async fn mix_stream_token_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    // Unknown sessions get the same 403 as foreign ones, like `authorize_session`
+    let forbidden = || ApiError::forbidden("Not allowed to access this session");
+    let session_uuid = Uuid::parse_str(&session_id).map_err(|_| forbidden())?;
+    let session = match database.get_mix_session(session_uuid).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err(forbidden()),
         Err(e) => {
-            error!("Failed to connect to database: {}", e);
-            let _ = socket.send(Message::Text(
-                format!("{{\"error\": \"Database connection failed: {}\"}}", e).into()
-            )).await;
-            return;
+            error!("Failed to look up session {} for stream token: {}", session_id, e);
+            return Err(ApiError::database(&e, "Failed to verify session ownership"));
         }
     };
 
-    // Connect to Redis and subscribe to progress channel
-    let redis_url = SECRET_MANAGER.get("REDIS_URL");
-    info!("Backend connecting to Redis at: {}", redis_url);
-    
-    let client = match redis::Client::open(redis_url.as_str()) {
-        Ok(c) => c,
-        Err(e) => {
-            error!("Failed to connect to Redis: {}", e);
-            let _ = socket.send(Message::Text(
-                format!("{{\"error\": \"Failed to connect to progress stream: {}\"}}", e).into()
-            )).await;
-            return;
+    let caller = auth::user_id_from_headers(&headers);
+    if let Some(owner) = session.user_id.as_deref()
+        && caller.as_deref() != Some(owner)
+    {
+        return Err(forbidden());
+    }
+
+    Ok(Json(serde_json::json!({
+        "token": auth::issue_stream_token(&session_id, caller.as_deref()),
+        "expires_in": auth::STREAM_TOKEN_TTL_SECS,
+    })))
+}
+
+/// Frames a mix socket can queue for its writer before progress gets dropped
+const WS_SEND_BUFFER: usize = 32;
+
+/// Queue between the Redis reader and a mix socket's writer task, so a slow
+/// client can't make buffered progress grow without bound
+struct MixSocketOutbox {
+    tx: tokio::sync::mpsc::Sender<Message>,
+    /// Progress frames dropped since the client last heard it was lagging
+    dropped: u64,
+}
+
+impl MixSocketOutbox {
+    fn lagging_notice(&self) -> Message {
+        Message::Text(serde_json::json!({"type": "lagging", "dropped": self.dropped}).to_string().into())
+    }
+
+    /// Queue a progress frame, dropping it if the client is behind.
+    /// Returns whether it was queued, or `None` once the writer is gone.
+    fn try_send_progress(&mut self, text: String) -> Option<bool> {
+        use tokio::sync::mpsc::error::TrySendError;
+
+        let mut frames = Vec::with_capacity(2);
+        if self.dropped > 0 {
+            frames.push(self.lagging_notice());
         }
-    };
-    
-    let mut pubsub = match client.get_async_pubsub().await {
+        frames.push(Message::Text(text.into()));
+
+        for frame in frames {
+            match self.tx.try_send(frame) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    if self.dropped == 0 {
+                        warn!("WebSocket client is lagging, dropping progress frames");
+                    }
+                    self.dropped += 1;
+                    return Some(false);
+                }
+                Err(TrySendError::Closed(_)) => return None,
+            }
+        }
+        self.dropped = 0;
+        Some(true)
+    }
+
+    /// Queue a frame that must not be dropped, waiting for room.
+    /// Returns false once the writer is gone.
+    async fn send(&mut self, message: Message) -> bool {
+        if self.dropped > 0 {
+            if self.tx.send(self.lagging_notice()).await.is_err() {
+                return false;
+            }
+            self.dropped = 0;
+        }
+        self.tx.send(message).await.is_ok()
+    }
+}
+
+async fn handle_mix_socket(mut socket: WebSocket, session_id: String, database: Database, redis: RedisPool) {
+    info!("WebSocket connected for session: {}", session_id);
+
+    // Subscribe to the session's progress channels
+    let mut pubsub = match redis_pool::pubsub().await {
         Ok(ps) => ps,
         Err(e) => {
-            error!("Failed to get pubsub connection: {}", e);
-            let _ = socket.send(Message::Text(
-                format!("{{\"error\": \"Failed to subscribe to progress: {}\"}}", e).into()
-            )).await;
+            warn!("No pubsub connection for {}, polling instead: {}", session_id, e);
+            poll_mix_socket(socket, session_id, database).await;
             return;
         }
     };
     
-    // Subscribe to multiple channels for this session
-    let _progress_channel = format!("mix:{}:progress", session_id);
-    let _complete_channel = format!("mix:{}:complete", session_id);
-    let _error_channel = format!("mix:{}:error", session_id);
-    
-    // Also subscribe to a wildcard pattern to catch all messages
-    let wildcard_pattern = "mix:*:*".to_string();
-    
-    if let Err(e) = pubsub.psubscribe(&wildcard_pattern).await {
-        error!("Failed to subscribe to wildcard pattern: {}", e);
+    // Subscribe to this session's channels only; other sessions' messages
+    // (and other environments sharing the Redis) never reach this socket
+    for kind in ["progress", "complete", "error"] {
+        let channel = redis_keys::mix_channel(&session_id, kind);
+        if let Err(e) = pubsub.subscribe(&channel).await {
+            error!("Failed to subscribe to {}: {}", channel, e);
+        }
     }
     
     info!("Subscribed to Redis channels for session: {}", session_id);
@@ -92,8 +250,18 @@ async fn handle_mix_socket(mut socket: WebSocket, session_id: String) {
         format!("{{\"type\": \"connected\", \"session_id\": \"{}\"}}", session_id).into()
     )).await;
     
-    // Split the WebSocket for concurrent read/write
+    // Split the WebSocket for concurrent read/write; a writer task drains the
+    // outbox so Redis messages keep being read while the client catches up
     let (mut ws_sender, mut ws_receiver) = socket.split();
+    let (out_tx, mut out_rx) = tokio::sync::mpsc::channel::<Message>(WS_SEND_BUFFER);
+    let mut outbox = MixSocketOutbox { tx: out_tx, dropped: 0 };
+    let mut writer = tokio::spawn(async move {
+        while let Some(message) = out_rx.recv().await {
+            if ws_sender.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
     
     // Handle incoming Redis messages
     let mut pubsub_stream = pubsub.on_message();
@@ -101,6 +269,14 @@ async fn handle_mix_socket(mut socket: WebSocket, session_id: String) {
     // Heartbeat interval (30 seconds)
     let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(30));
     heartbeat_interval.tick().await; // Skip first immediate tick
+
+    // Progress updates are coalesced and flushed at most once per interval;
+    // `interval` panics on zero, so 0 means "as often as possible"
+    let flush_ms = SECRET_MANAGER.get("WS_PROGRESS_FLUSH_MS").parse::<u64>().unwrap_or(200).max(1);
+    let mut flush_interval = tokio::time::interval(std::time::Duration::from_millis(flush_ms));
+    flush_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut pending_progress: Option<String> = None;
+    let mut last_sent_progress: Option<String> = None;
     
     loop {
         tokio::select! {
@@ -121,13 +297,7 @@ async fn handle_mix_socket(mut socket: WebSocket, session_id: String) {
                 info!("Received Redis message on channel {}: {}", channel, payload);
                 
                 // Determine message type based on channel suffix
-                let message_type = if channel.ends_with(":complete") {
-                    "complete"
-                } else if channel.ends_with(":error") {
-                    "error"
-                } else if channel.ends_with(":progress") {
-                    "progress"
-                } else {
+                let Some(message_type) = progress::message_type_for_channel(&channel) else {
                     warn!("Unknown channel type: {}", channel);
                     continue;
                 };
@@ -180,25 +350,23 @@ async fn handle_mix_socket(mut socket: WebSocket, session_id: String) {
                     }
                 }
                 
-                // Forward to WebSocket client using proper JSON serialization
-                // Parse the payload to ensure it's valid JSON, then wrap it
-                let ws_message = match serde_json::from_str::<serde_json::Value>(&payload) {
-                    Ok(data) => {
-                        serde_json::json!({
-                            "type": message_type,
-                            "data": data
-                        }).to_string()
-                    }
-                    Err(_) => {
-                        // Fallback: wrap as string if not valid JSON
-                        serde_json::json!({
-                            "type": message_type,
-                            "data": {"raw": payload}
-                        }).to_string()
-                    }
-                };
+                // Forward to WebSocket client using the shared envelope
+                let ws_message = progress::format_progress_message(&channel, &payload);
                 
-                if ws_sender.send(Message::Text(ws_message.into())).await.is_err() {
+                // Buffer progress, keeping only the latest value until the next flush
+                if message_type == "progress" {
+                    pending_progress = Some(ws_message);
+                    continue;
+                }
+
+                // Terminal messages go out immediately, after any buffered progress
+                if let Some(pending) = pending_progress.take()
+                    && last_sent_progress.as_ref() != Some(&pending)
+                    && outbox.try_send_progress(pending).is_none() {
+                    break;
+                }
+
+                if !outbox.send(Message::Text(ws_message.into())).await {
                     break;
                 }
                 
@@ -208,12 +376,31 @@ async fn handle_mix_socket(mut socket: WebSocket, session_id: String) {
                 }
             }
             
-            // Handle WebSocket messages from client (ping/pong, close)
+            // Handle WebSocket messages from client (commands, ping/pong, close)
             ws_msg = ws_receiver.next() => {
                 match ws_msg {
+                    Some(Ok(Message::Text(text))) => {
+                        // Unknown or malformed commands are ignored
+                        let reply = match serde_json::from_str::<MixSocketCommand>(&text) {
+                            Ok(MixSocketCommand::Ping) => serde_json::json!({"type": "pong"}).to_string(),
+                            Ok(MixSocketCommand::Replay) => {
+                                match progress::history(&redis, &session_id).await {
+                                    Ok(events) => progress::format_replay_message(&events),
+                                    Err(e) => {
+                                        warn!("Failed to load progress history for {}: {}", session_id, e);
+                                        continue;
+                                    }
+                                }
+                            }
+                            Err(_) => continue,
+                        };
+                        if !outbox.send(Message::Text(reply.into())).await {
+                            break;
+                        }
+                    }
                     Some(Ok(Message::Ping(data))) => {
                         debug!("Received ping from client for session: {}", session_id);
-                        let _ = ws_sender.send(Message::Pong(data)).await;
+                        outbox.send(Message::Pong(data)).await;
                     }
                     Some(Ok(Message::Pong(_))) => {
                         debug!("Received pong from client for session: {}", session_id);
@@ -234,100 +421,599 @@ async fn handle_mix_socket(mut socket: WebSocket, session_id: String) {
                 }
             }
             
+            // Flush the latest buffered progress update, skipping exact duplicates
+            _ = flush_interval.tick(), if pending_progress.is_some() => {
+                if let Some(pending) = pending_progress.take() {
+                    if last_sent_progress.as_ref() == Some(&pending) {
+                        continue;
+                    }
+                    match outbox.try_send_progress(pending.clone()) {
+                        Some(true) => last_sent_progress = Some(pending),
+                        Some(false) => {}
+                        None => break,
+                    }
+                }
+            }
+
             // Send heartbeat ping to keep connection alive
             _ = heartbeat_interval.tick() => {
                 debug!("Sending heartbeat ping to client for session: {}", session_id);
-                if ws_sender.send(Message::Ping(Bytes::new())).await.is_err() {
+                // A full queue already keeps the connection busy, so skip the ping
+                if let Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) =
+                    outbox.tx.try_send(Message::Ping(Bytes::new()))
+                {
                     error!("Failed to send heartbeat ping for session: {}", session_id);
                     break;
                 }
             }
         }
     }
+
+    // Let queued frames (e.g. the final `complete`) reach the client, but
+    // don't wait forever on one that stopped reading
+    drop(outbox);
+    if tokio::time::timeout(std::time::Duration::from_secs(5), &mut writer).await.is_err() {
+        writer.abort();
+    }
     
     info!("WebSocket disconnected for session: {}", session_id);
 }
 
+/// WebSocket handler for live "now playing" sync across devices. Takes the
+/// same session-scoped stream token as `/ws/mix`.
+async fn ws_playback_handler(
+    State(database): State<Database>,
+    ws: WebSocketUpgrade,
+    Path(session_id): Path<String>,
+    Query(params): Query<StreamAuthQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    auth::check_stream_origin(&headers)?;
+
+    // Commands reach every device in the session, so only its owner may connect
+    let token = auth::stream_token(&headers, params.token);
+    auth::authorize_session(&database, &session_id, token).await?;
+
+    Ok(ws
+        .protocols([auth::WS_TOKEN_PROTOCOL])
+        .on_upgrade(move |socket| handle_playback_socket(socket, session_id, REDIS_POOL.clone())))
+}
+
+async fn handle_playback_socket(mut socket: WebSocket, session_id: String, redis: RedisPool) {
+    use crate::models::playback::{PlaybackCommand, PlaybackEvent};
+    use redis::AsyncCommands;
+
+    info!("Playback WebSocket connected for session: {}", session_id);
+
+    let channel = redis_keys::key(&format!("playback:{}", session_id));
+    let connection_id = Uuid::new_v4().to_string();
+
+    // Pub/sub connections can't publish, so position updates go out on
+    // connections borrowed from the pool
+    let mut pubsub = match redis_pool::pubsub().await {
+        Ok(pubsub) => pubsub,
+        Err(e) => {
+            error!("Failed to open playback connections: {}", e);
+            let _ = socket.send(Message::Text(
+                format!("{{\"error\": \"Failed to subscribe to playback: {}\"}}", e).into()
+            )).await;
+            return;
+        }
+    };
+
+    if let Err(e) = pubsub.subscribe(&channel).await {
+        error!("Failed to subscribe to {}: {}", channel, e);
+        return;
+    }
+
+    let _ = socket.send(Message::Text(
+        format!("{{\"type\": \"connected\", \"session_id\": \"{}\"}}", session_id).into()
+    )).await;
+
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let mut pubsub_stream = pubsub.on_message();
+
+    let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(30));
+    heartbeat_interval.tick().await; // Skip first immediate tick
+
+    loop {
+        tokio::select! {
+            // Broadcast playback events from other devices
+            msg_opt = pubsub_stream.next() => {
+                let Some(msg) = msg_opt else { break };
+                let payload: String = match msg.get_payload() {
+                    Ok(p) => p,
+                    Err(_) => continue,
+                };
+
+                match serde_json::from_str::<PlaybackEvent>(&payload) {
+                    Ok(event) if event.origin == connection_id => continue,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Ignoring malformed playback event on {}: {}", channel, e);
+                        continue;
+                    }
+                }
+
+                if ws_sender.send(Message::Text(payload.into())).await.is_err() {
+                    break;
+                }
+            }
+
+            // Publish play/pause/seek/position updates from this device
+            ws_msg = ws_receiver.next() => {
+                match ws_msg {
+                    Some(Ok(Message::Text(text))) => {
+                        let command = match serde_json::from_str::<PlaybackCommand>(&text) {
+                            Ok(c) => c,
+                            Err(e) => {
+                                let _ = ws_sender.send(Message::Text(
+                                    serde_json::json!({"type": "error", "error": format!("Invalid playback command: {}", e)}).to_string().into()
+                                )).await;
+                                continue;
+                            }
+                        };
+
+                        let event = PlaybackEvent {
+                            action: command.action,
+                            track_index: command.track_index,
+                            position_ms: command.position_ms,
+                            origin: connection_id.clone(),
+                            sent_at: chrono::Utc::now().timestamp_millis(),
+                        };
+
+                        let payload = serde_json::to_string(&event).unwrap_or_default();
+                        let published = match redis.get().await {
+                            Ok(mut conn) => conn.publish::<_, _, ()>(&channel, payload).await,
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = published {
+                            error!("Failed to publish playback event for session {}: {}", session_id, e);
+                        }
+                    }
+                    Some(Ok(Message::Ping(data))) => {
+                        let _ = ws_sender.send(Message::Pong(data)).await;
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        info!("Playback WebSocket closed for session: {}", session_id);
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        error!("Playback WebSocket error for session {}: {}", session_id, e);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+
+            // Send heartbeat ping to keep connection alive
+            _ = heartbeat_interval.tick() => {
+                if ws_sender.send(Message::Ping(Bytes::new())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    info!("Playback WebSocket disconnected for session: {}", session_id);
+}
+
+/// Serve a mix socket from database polling while Redis is unavailable.
+/// `ping` is still answered; `replay` isn't, as the history lives in Redis.
+async fn poll_mix_socket(mut socket: WebSocket, session_id: String, database: Database) {
+    let Ok(session_uuid) = Uuid::parse_str(&session_id) else {
+        return;
+    };
+
+    for notice in [
+        serde_json::json!({"type": "connected", "session_id": session_id}).to_string(),
+        progress::polling_notice(),
+    ] {
+        if socket.send(Message::Text(notice.into())).await.is_err() {
+            return;
+        }
+    }
+
+    let mut updates = std::pin::pin!(progress::poll_session(database, session_uuid));
+    loop {
+        tokio::select! {
+            update = updates.next() => {
+                let Some(update) = update else { break };
+                if socket.send(Message::Text(update.into())).await.is_err() {
+                    break;
+                }
+            }
+            ws_msg = socket.recv() => {
+                match ws_msg {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Ok(MixSocketCommand::Ping) = serde_json::from_str::<MixSocketCommand>(&text)
+                            && socket.send(Message::Text(serde_json::json!({"type": "pong"}).to_string().into())).await.is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    info!("WebSocket disconnected for session: {} (polling)", session_id);
+}
+
+/// Consecutive failed reconnects before an SSE stream falls back to polling
+const SSE_MAX_RECONNECT_ATTEMPTS: u32 = 10;
+
 /// SSE (Server-Sent Events) fallback for mix progress
 async fn sse_mix_handler(
-    State(_database): State<Database>,
+    State(database): State<Database>,
     Path(session_id): Path<String>,
-) -> impl IntoResponse {
+    Query(params): Query<StreamAuthQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
     use axum::response::sse::{Event, KeepAlive, Sse};
     use std::convert::Infallible;
-    
-    let redis_url = SECRET_MANAGER.get("REDIS_URL");
+
+    auth::check_stream_origin(&headers)?;
+    let token = auth::stream_token(&headers, params.token);
+    auth::authorize_session(&database, &session_id, token).await?;
+
+    let redis = REDIS_POOL.clone();
+
+    // EventSource sends the last id it saw when it reconnects
+    let last_event_id: u64 = headers
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
     
     let stream = async_stream::stream! {
-        let client = match redis::Client::open(redis_url.as_str()) {
-            Ok(c) => c,
-            Err(e) => {
+        let mut last_sent = last_event_id;
+        let mut connected = false;
+        let mut attempt: u32 = 0;
+
+        // Each pass (re)subscribes; a dropped or unresponsive Redis connection
+        // sends the client a `reconnecting` event and starts the next pass.
+        // Leaving the loop means Redis is unusable and the database is polled.
+        'connect: loop {
+            if attempt > 0 {
+                if attempt > SSE_MAX_RECONNECT_ATTEMPTS {
+                    break 'connect;
+                }
                 yield Ok::<_, Infallible>(Event::default().data(
-                    format!("{{\"error\": \"Redis connection failed: {}\"}}", e)
+                    serde_json::json!({"type": "reconnecting", "attempt": attempt}).to_string()
                 ));
-                return;
+                tokio::time::sleep(progress::reconnect_backoff(attempt)).await;
             }
-        };
-        
-        let mut pubsub = match client.get_async_pubsub().await {
-            Ok(ps) => ps,
-            Err(e) => {
+            attempt += 1;
+
+            let (mut sink, mut messages) = match progress::subscribe_session(&session_id).await {
+                Ok(subscription) => subscription,
+                Err(e) => {
+                    warn!("Progress subscription failed for {}: {}", session_id, e);
+                    // Never subscribed: don't keep a new client waiting on retries
+                    if !connected {
+                        break 'connect;
+                    }
+                    continue 'connect;
+                }
+            };
+            attempt = 0;
+
+            if !connected {
+                connected = true;
                 yield Ok::<_, Infallible>(Event::default().data(
-                    format!("{{\"error\": \"Pubsub failed: {}\"}}", e)
+                    format!("{{\"type\": \"connected\", \"session_id\": \"{}\"}}", session_id)
                 ));
-                return;
             }
+
+            // Subscribed first, so nothing published from here on is missed;
+            // catch the client up with whatever it hasn't seen yet (including
+            // anything published while we were reconnecting)
+            if let Ok(Some(snapshot)) = progress::latest_snapshot(&redis, &session_id).await
+                && snapshot.id > last_sent {
+                last_sent = snapshot.id;
+                yield Ok::<_, Infallible>(Event::default()
+                    .id(snapshot.id.to_string())
+                    .data(progress::format_progress_message(&snapshot.channel, &snapshot.payload)));
+
+                if matches!(progress::message_type_for_channel(&snapshot.channel), Some("complete" | "error")) {
+                    return;
+                }
+            }
+
+            let mut health_check = tokio::time::interval(progress::HEALTH_CHECK_INTERVAL);
+            health_check.tick().await;
+
+            loop {
+                let msg = tokio::select! {
+                    msg = messages.next() => msg,
+                    _ = health_check.tick() => {
+                        if progress::is_alive(&mut sink).await {
+                            continue;
+                        }
+                        None
+                    }
+                };
+                let Some(msg) = msg else {
+                    warn!("Lost Redis connection for progress of {}", session_id);
+                    continue 'connect;
+                };
+
+                let payload: String = match msg.get_payload() {
+                    Ok(p) => p,
+                    Err(_) => continue,
+                };
+
+                let channel: String = msg.get_channel_name().to_string();
+                let Some(message_type) = progress::message_type_for_channel(&channel) else {
+                    continue;
+                };
+
+                let mut event = Event::default().data(progress::format_progress_message(&channel, &payload));
+                match progress::sequence_message(&database, &redis, &session_id, &channel, &payload).await {
+                    // Already delivered as the snapshot
+                    Ok(id) if id <= last_sent => continue,
+                    Ok(id) => {
+                        last_sent = id;
+                        event = event.id(id.to_string());
+                    }
+                    Err(e) => warn!("Failed to sequence progress for {}: {}", session_id, e),
+                }
+
+                yield Ok::<_, Infallible>(event);
+
+                if message_type == "complete" || message_type == "error" {
+                    return;
+                }
+            }
+        }
+
+        let Ok(session_uuid) = Uuid::parse_str(&session_id) else {
+            return;
         };
-        
-        let progress_channel = format!("mix:{}:progress", session_id);
-        let complete_channel = format!("mix:{}:complete", session_id);
-        let error_channel = format!("mix:{}:error", session_id);
-        
-        let _ = pubsub.subscribe(&progress_channel).await;
-        let _ = pubsub.subscribe(&complete_channel).await;
-        let _ = pubsub.subscribe(&error_channel).await;
-        
-        yield Ok::<_, Infallible>(Event::default().data(
-            format!("{{\"type\": \"connected\", \"session_id\": \"{}\"}}", session_id)
-        ));
-        
-        let mut pubsub_stream = pubsub.on_message();
-        
-        while let Some(msg) = pubsub_stream.next().await {
-            let payload: String = match msg.get_payload() {
-                Ok(p) => p,
-                Err(_) => continue,
-            };
-            
-            let channel: String = msg.get_channel_name().to_string();
-            let message_type = if channel.contains(":complete") {
-                "complete"
-            } else if channel.contains(":error") {
-                "error"
-            } else {
-                "progress"
-            };
-            
+        if !connected {
             yield Ok::<_, Infallible>(Event::default().data(
-                format!("{{\"type\": \"{}\", \"data\": {}}}", message_type, payload)
+                format!("{{\"type\": \"connected\", \"session_id\": \"{}\"}}", session_id)
             ));
-            
-            if message_type == "complete" || message_type == "error" {
-                break;
-            }
+        }
+        yield Ok::<_, Infallible>(Event::default().data(progress::polling_notice()));
+
+        let mut updates = std::pin::pin!(progress::poll_session(database, session_uuid));
+        while let Some(update) = updates.next().await {
+            yield Ok::<_, Infallible>(Event::default().data(update));
         }
     };
-    
-    Sse::new(stream).keep_alive(KeepAlive::default())
+
+    let keep_alive_secs = SECRET_MANAGER.get("SSE_KEEPALIVE_SECS").parse::<u64>().unwrap_or(15).max(1);
+    Ok(Sse::new(stream)
+        .keep_alive(KeepAlive::new().interval(std::time::Duration::from_secs(keep_alive_secs)))
+        .into_response())
+}
+
+/// Order a set of tracks by key compatibility, optionally following an energy curve
+async fn plan_mix_handler(
+    State(database): State<Database>,
+    Json(mut request): Json<planner::PlanMixRequest>,
+) -> Result<Json<planner::MixPlan>, ApiError> {
+    if request.tracks.is_empty() {
+        return Err(ApiError::bad_request("At least one track is required"));
+    }
+
+    // Fall back to the listener's preferred energy from onboarding
+    if request.target_energy.is_none()
+        && let Some(session_id) = request.session_id.as_deref() {
+        match database.get_session_profile(session_id).await {
+            Ok(profile) => request.target_energy = profile.map(|p| p.energy_level),
+            Err(e) => warn!("Failed to load profile for plan: {}", e),
+        }
+    }
+
+    Ok(Json(planner::plan_mix(request)))
+}
+
+/// Validate a prompt against `MIX_PROMPT_MAX_CHARS`, returning the cleaned text
+fn clean_prompt(prompt: &str) -> Result<String, ApiError> {
+    let max_chars = SECRET_MANAGER.get("MIX_PROMPT_MAX_CHARS").parse::<usize>().unwrap_or(2000);
+    sanitize_prompt(prompt, max_chars).map_err(ApiError::bad_request)
+}
+
+/// The whole-minute duration to generate, rejecting mixes longer than
+/// `MIX_MAX_DURATION_MINUTES`
+fn check_mix_length(length: &MixLengthRequest) -> Result<i64, ApiError> {
+    let max_minutes = SECRET_MANAGER.get("MIX_MAX_DURATION_MINUTES").parse::<i64>().unwrap_or(180);
+    length.resolve(max_minutes).map_err(|message| {
+        ApiError::bad_request(message).with_details(serde_json::json!({"max_duration_minutes": max_minutes}))
+    })
 }
 
+/// Set on a `/mix/generate` response that reuses an earlier identical request's session
+const DEDUPLICATED_HEADER: &str = "X-Mix-Deduplicated";
+
 /// Proxy endpoint to forward mix generation requests to orchestrator
 async fn generate_mix_handler(
     State(database): State<Database>,
+    ConnectInfo(client): ConnectInfo<std::net::SocketAddr>,
     headers: axum::http::HeaderMap,
     body: axum::body::Bytes,
-) -> impl IntoResponse {
-    let orchestrator_url = SECRET_MANAGER.get("ORCHESTRATOR_URL");
+) -> Result<Response, ApiError> {
+    let mut request: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::bad_request(format!("Invalid JSON body: {}", e)))?;
+    let prompt = request
+        .get("prompt")
+        .and_then(|p| p.as_str())
+        .ok_or_else(|| ApiError::bad_request("prompt is required"))?;
+    let prompt = clean_prompt(prompt)?;
+    let length = MixLengthRequest::deserialize(&request)
+        .map_err(|e| ApiError::bad_request(format!("Invalid mix length: {}", e)))?;
+    let duration_minutes = check_mix_length(&length)?;
+
+    let Some(request_object) = request.as_object_mut() else {
+        return Err(ApiError::bad_request("Body must be a JSON object"));
+    };
+
+    // Opt-in: an identical prompt from the same user (or, anonymously, the
+    // same address) within the window gets the existing session back instead
+    // of a new generation
+    let dedupe = request_object.remove("dedupe").and_then(|d| d.as_bool()).unwrap_or(false);
+    let dedupe_key = dedupe.then(|| {
+        let scope = match auth::user_id_from_headers(&headers) {
+            Some(user_id) => format!("user:{}", user_id),
+            None => format!("ip:{}", client.ip()),
+        };
+        format!("mix:dedupe:{}", prompt_fingerprint(&prompt, &scope))
+    });
+    let webhook_url = match request_object.remove("webhook_url") {
+        Some(serde_json::Value::String(url)) => Some(webhooks::check_webhook_url(&url).map_err(ApiError::bad_request)?),
+        Some(serde_json::Value::Null) | None => None,
+        Some(_) => return Err(ApiError::bad_request("webhook_url must be a string")),
+    };
+
+    // Same body as a fresh generation, flagged by a header. A session that
+    // errored or was cancelled since doesn't count.
+    if let Some(key) = dedupe_key.as_deref()
+        && let Some(existing) = cache::get_json::<models::mix::OrchestratorMixResponse>(key).await
+        && let Ok(Some(session)) = database.get_mix_session(existing.session_id).await
+        && matches!(session.status.as_str(), "generating" | "completed")
+    {
+        info!("Returning existing session {} for duplicate prompt", existing.session_id);
+        return Ok(([(DEDUPLICATED_HEADER, "true")], Json(existing)).into_response());
+    }
+
+    request_object.insert("prompt".to_string(), serde_json::Value::String(prompt.clone()));
+    request_object.remove("estimated_duration_minutes");
+    request_object.insert("duration_minutes".to_string(), duration_minutes.into());
+
+    let options = DispatchOptions {
+        dedupe_key: dedupe_key.as_deref(),
+        webhook_url: webhook_url.as_ref(),
+        ..Default::default()
+    };
+    dispatch_mix_generation(&database, &headers, &prompt, Bytes::from(request.to_string()), options).await
+}
+
+/// Regenerate a mix from the original prompt and profile with tweaks applied
+async fn regenerate_mix_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(overrides): Json<RegenerateMixRequest>,
+) -> Result<Response, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    if let Some(energy) = overrides.target_energy
+        && !(0.0..=1.0).contains(&energy) {
+        return Err(ApiError::bad_request("target_energy must be between 0.0 and 1.0"));
+    }
+    let prompt_override = overrides.prompt.as_deref().map(clean_prompt).transpose()?;
+    // The override, or the cap when there isn't one
+    let checked_minutes = check_mix_length(&MixLengthRequest {
+        duration_minutes: overrides.duration_minutes.map(i64::from),
+    })?;
+
+    let original = match database.get_mix_session(session_uuid).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err(ApiError::not_found("Mix session not found")),
+        Err(e) => {
+            error!("Failed to get mix session: {}", e);
+            return Err(ApiError::database(&e, "Failed to retrieve mix session"));
+        }
+    };
+
+    // Only the owner may regenerate their mix; anonymous mixes are open to anyone
+    if let Some(owner) = original.user_id.as_deref()
+        && auth::user_id_from_headers(&headers).as_deref() != Some(owner)
+    {
+        return Err(ApiError::forbidden("Not allowed to regenerate this mix"));
+    }
+
+    let profile = database.get_session_profile(&session_id).await.unwrap_or_else(|e| {
+        warn!("Failed to load profile for regeneration: {}", e);
+        None
+    });
+
+    let target_energy = overrides.target_energy.or(profile.map(|p| p.energy_level));
+    // Without an override keep the original's length, within today's cap
+    let duration_minutes = match (overrides.duration_minutes, original.estimated_duration_minutes) {
+        (None, Some(estimate)) => (estimate.round() as i64).clamp(1, checked_minutes),
+        _ => checked_minutes,
+    };
+
+    // The orchestrator interprets the prompt, so spell the tweaks out there
+    // as well as sending them as structured fields
+    let base_prompt = match prompt_override {
+        Some(prompt) => prompt,
+        None if original.prompt.trim().is_empty() => {
+            return Err(ApiError::bad_request("This mix has no prompt to regenerate from, send one in `prompt`"));
+        }
+        None => original.prompt,
+    };
+    let mut prompt = base_prompt.clone();
+    if let Some(energy) = target_energy {
+        prompt.push_str(&format!(". Target energy around {:.0}%", energy * 100.0));
+    }
+    if !overrides.exclude_artists.is_empty() {
+        prompt.push_str(&format!(". Avoid these artists: {}", overrides.exclude_artists.join(", ")));
+    }
+    // The tweaks count towards the length cap too
+    let prompt = clean_prompt(&prompt)?;
+
+    let body = serde_json::json!({
+        "prompt": prompt,
+        "duration_minutes": duration_minutes,
+        "target_energy": target_energy,
+        "exclude_artists": overrides.exclude_artists,
+    });
+
+    info!("Regenerating mix {}", session_id);
+    let options = DispatchOptions {
+        parent_session_id: Some(session_uuid),
+        ..Default::default()
+    };
+    dispatch_mix_generation(&database, &headers, &base_prompt, Bytes::from(body.to_string()), options).await
+}
+
+/// What to record alongside a newly generated session
+#[derive(Debug, Default)]
+struct DispatchOptions<'a> {
+    /// The session being regenerated
+    parent_session_id: Option<Uuid>,
+    /// Remember the orchestrator's response under this key for `MIX_DEDUPE_WINDOW_SECS`
+    dedupe_key: Option<&'a str>,
+    /// Notified when the session completes or errors
+    webhook_url: Option<&'a reqwest::Url>,
+}
+
+/// Forward a generation request to the orchestrator and record the new
+/// session under the listener's `prompt` (the orchestrator doesn't echo it)
+async fn dispatch_mix_generation(
+    database: &Database,
+    headers: &axum::http::HeaderMap,
+    prompt: &str,
+    body: Bytes,
+    options: DispatchOptions<'_>,
+) -> Result<Response, ApiError> {
+    let Some(orchestrator_url) = SECRET_MANAGER.get_url("ORCHESTRATOR_URL") else {
+        return Err(ApiError::service_unavailable("Orchestrator not configured"));
+    };
+
+    // Held until the orchestrator reports the new session finished. The
+    // limit is this instance's, not shared with other replicas.
+    let Some(slot) = GENERATIONS.try_acquire() else {
+        return Err(ApiError::service_unavailable("Too many mixes are generating, try again shortly")
+            .with_code("too_many_generations")
+            .with_details(serde_json::json!({"limit_per_instance": GENERATIONS.limit()}))
+            .with_retry_after(30));
+    };
+
+    // Fail fast while the orchestrator is known to be down
+    if let Err(retry_in) = ORCHESTRATOR_BREAKER.try_acquire() {
+        let retry_after = retry_in.as_secs().max(1) as i64;
+        return Err(ApiError::service_unavailable("Orchestrator is unavailable, try again shortly")
+            .with_code("orchestrator_unavailable")
+            .with_retry_after(retry_after));
+    }
 
     let client = reqwest::Client::new();
 
@@ -346,193 +1032,721 @@ async fn generate_mix_handler(
         request = request.header("Authorization", auth.to_str().unwrap_or(""));
     }
 
-    match request.send().await {
-        Ok(response) => {
-            let status = response.status();
-            let body_text = response.text().await.unwrap_or_default();
-
-            // If the orchestrator response is successful, try to save the initial mix data
-            if status.is_success() {
-                if let Ok(data) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                    if let Some(session_id_str) = data.get("session_id").and_then(|s| s.as_str()) {
-                        if let Ok(session_uuid) = Uuid::parse_str(session_id_str) {
-                            // Extract playlist data from the initial response
-                            if let Some(playlist) = data.get("playlist").and_then(|p| p.as_array()) {
-                                let mut tracks = Vec::new();
-                                let mut transitions = Vec::new();
-
-                                for (i, track) in playlist.iter().enumerate() {
-                                    if let (Some(spotify_id), Some(title), Some(artist)) = (
-                                        track.get("spotify_id").and_then(|s| s.as_str()),
-                                        track.get("title").and_then(|t| t.as_str()),
-                                        track.get("artist").and_then(|a| a.as_str()),
-                                    ) {
-                                        tracks.push(crate::models::mix::CreateTrackRequest {
-                                            spotify_id: spotify_id.to_string(),
-                                            title: title.to_string(),
-                                            artist: artist.to_string(),
-                                            album: track.get("album").and_then(|a| a.as_str()).unwrap_or("Unknown").to_string(),
-                                            duration_ms: track.get("duration_ms").and_then(|d| d.as_i64()).unwrap_or(0) as i32,
-                                            key: track.get("key").and_then(|k| k.as_str()).unwrap_or("Unknown").to_string(),
-                                            energy: track.get("energy").and_then(|e| e.as_f64()).unwrap_or(0.5),
-                                            danceability: track.get("danceability").and_then(|d| d.as_f64()).unwrap_or(0.5),
-                                            valence: track.get("valence").and_then(|v| v.as_f64()).unwrap_or(0.5),
-                                            acousticness: track.get("acousticness").and_then(|a| a.as_f64()).unwrap_or(0.1),
-                                            instrumentalness: track.get("instrumentalness").and_then(|i| i.as_f64()).unwrap_or(0.1),
-                                            popularity: track.get("popularity").and_then(|p| p.as_i64()).unwrap_or(50) as i32,
-                                            track_order: i as i32,
-                                        });
-                                    }
+    // Forward request id so orchestrator logs can be correlated with ours
+    if let Some(request_id) = request_id::request_id_from_headers(headers) {
+        request = request.header(request_id::REQUEST_ID_HEADER, request_id);
+    }
 
-                                    // Extract transition data
-                                    if let Some(transition) = track.get("transition") {
-                                        if let (Some(trans_type), Some(bars)) = (
-                                            transition.get("type").and_then(|t| t.as_str()),
-                                            transition.get("bars").and_then(|b| b.as_i64()),
-                                        ) {
-                                            transitions.push(crate::models::mix::CreateTransitionRequest {
-                                                from_track_order: i as i32,
-                                                to_track_order: (i + 1) as i32,
-                                                transition_type: trans_type.to_string(),
-                                                transition_bars: bars as i32,
-                                                transition_direction: transition.get("direction").and_then(|d| d.as_str()).map(|s| s.to_string()),
-                                            });
-                                        }
-                                    }
-                                }
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            ORCHESTRATOR_BREAKER.record_failure();
+            return Err(ApiError::bad_gateway(format!("Orchestrator request failed: {}", e)));
+        }
+    };
 
-                                let prompt = data.get("prompt").and_then(|p| p.as_str()).unwrap_or("").to_string();
-                                let mix_request = crate::models::mix::CreateMixRequest {
-                                    prompt,
-                                    tracks,
-                                    transitions,
-                                    estimated_duration_minutes: data.get("estimated_duration_minutes").and_then(|d| d.as_f64()),
-                                };
-
-                                // Create the mix session first
-                                if let Err(e) = database.create_mix_session(session_uuid, &mix_request.prompt).await {
-                                    error!("Failed to create mix session: {}", e);
-                                }
+    // 5xx means the orchestrator itself is unhealthy; 4xx is the caller's problem
+    if response.status().is_server_error() {
+        ORCHESTRATOR_BREAKER.record_failure();
+    } else {
+        ORCHESTRATOR_BREAKER.record_success();
+    }
 
-                                // Then save the mix data
-                                if let Err(e) = database.save_mix_data(session_uuid, mix_request).await {
-                                    error!("Failed to save initial mix data: {}", e);
-                                } else {
-                                    info!("Successfully saved initial mix data for session: {}", session_id_str);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    // A successful body is always buffered: it's parsed to record the session,
+    // register the webhook and dedupe. Large or unsized error bodies have
+    // nothing to record, so they're relayed as they arrive.
+    if !response.status().is_success() && !is_small_json(response.headers()) {
+        debug!("Streaming orchestrator error response ({}) through", response.status());
+        return Ok(stream_orchestrator_response(response));
+    }
+
+    let status = response.status();
+    let body_text = response.text().await.unwrap_or_default();
+
+    let status = axum::http::StatusCode::from_u16(status.as_u16())
+        .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+    if !status.is_success() {
+        return Err(orchestrator_error(status, &body_text));
+    }
+
+    let data = serde_json::from_str::<models::mix::OrchestratorMixResponse>(&body_text).map_err(|e| {
+        error!("Unexpected orchestrator response: {}", e);
+        ApiError::bad_gateway(format!("Orchestrator response did not match the expected schema: {}", e))
+            .with_code("bad_orchestrator_response")
+    })?;
+    let session_uuid = data.session_id;
+    slot.track(session_uuid);
+
+    if let Some(url) = options.webhook_url {
+        webhooks::register(session_uuid, url).await;
+    }
+    if let Some(key) = options.dedupe_key {
+        let window_secs = SECRET_MANAGER.get("MIX_DEDUPE_WINDOW_SECS").parse::<u64>().unwrap_or(300);
+        cache::set_json(key, &data, window_secs).await;
+    }
+
+    // Save the initial mix data, creating the mix session first
+    let mix_request = data.to_create_request();
+    let user_id = auth::user_id_from_headers(headers);
+    if let Err(e) = database
+        .create_mix_session(session_uuid, prompt, user_id.as_deref(), options.parent_session_id)
+        .await
+    {
+        error!("Failed to create mix session: {}", e);
+    }
+    if let Err(e) = database.save_mix_data(session_uuid, mix_request).await {
+        error!("Failed to save initial mix data: {}", e);
+    } else {
+        info!("Successfully saved initial mix data for session: {}", session_uuid);
+    }
+
+    Ok((status, Json(data)).into_response())
+}
+
+/// Orchestrator responses up to this size are buffered and parsed
+const ORCHESTRATOR_BUFFER_LIMIT: u64 = 256 * 1024;
+
+/// A JSON body with a `Content-Length` within `ORCHESTRATOR_BUFFER_LIMIT`
+fn is_small_json(headers: &reqwest::header::HeaderMap) -> bool {
+    let is_json = headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    let length = headers
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    is_json && length.is_some_and(|len| len <= ORCHESTRATOR_BUFFER_LIMIT)
+}
 
-            (
-                axum::http::StatusCode::from_u16(status.as_u16()).unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR),
-                axum::Json(serde_json::from_str::<serde_json::Value>(&body_text).unwrap_or(serde_json::json!({"error": body_text})))
-            ).into_response()
+/// Relay an orchestrator response without buffering, keeping its status and content headers
+fn stream_orchestrator_response(upstream: reqwest::Response) -> Response {
+    let status = axum::http::StatusCode::from_u16(upstream.status().as_u16())
+        .unwrap_or(axum::http::StatusCode::BAD_GATEWAY);
+    let mut response = Response::builder().status(status);
+    for name in [axum::http::header::CONTENT_TYPE, axum::http::header::CONTENT_LENGTH] {
+        if let Some(value) = upstream.headers().get(&name) {
+            response = response.header(name, value);
         }
+    }
+
+    response
+        .body(axum::body::Body::from_stream(upstream.bytes_stream()))
+        .unwrap_or_else(|_| ApiError::bad_gateway("Orchestrator response could not be relayed").into_response())
+}
+
+/// Re-wrap an orchestrator error in our envelope, keeping its status and
+/// message (FastAPI puts it in `detail`)
+fn orchestrator_error(status: axum::http::StatusCode, body: &str) -> ApiError {
+    let parsed = serde_json::from_str::<serde_json::Value>(body).ok();
+    let message = parsed
+        .as_ref()
+        .and_then(|v| v.get("detail").or_else(|| v.get("error")))
+        .and_then(|m| m.as_str())
+        .map(|m| m.to_string())
+        .unwrap_or_else(|| body.to_string());
+    ApiError::new(status, message)
+}
+
+/// Parse a `{session_id}` path segment as a mix session UUID
+fn parse_session_id(session_id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(session_id).map_err(|_| ApiError::bad_request("Invalid session ID format"))
+}
+
+async fn list_mixes_handler(
+    State(database): State<Database>,
+    pagination: Pagination,
+) -> Result<Json<Vec<models::mix::MixSession>>, ApiError> {
+    database.list_mix_sessions(pagination.limit, pagination.offset).await.map(Json).map_err(|e| {
+        error!("Failed to list mix sessions: {}", e);
+        ApiError::database(&e, "Failed to retrieve mix sessions")
+    })
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct MixHistoryQuery {
+    status: Option<String>,
+    /// Read directly when streaming, where `Pagination`'s cap doesn't apply
+    limit: Option<i64>,
+}
+
+/// Media type of the streamed mix history, one session per line
+const NDJSON: &str = "application/x-ndjson";
+/// Most sessions one streamed history response will return
+const HISTORY_STREAM_MAX_LIMIT: i64 = 10_000;
+
+/// The caller's mix history, newest first, so `?status=completed` gives "my
+/// completed mixes". Requires an `X-User-Token`; without one this is `401`
+/// rather than everyone's sessions.
+///
+/// With `Accept: application/x-ndjson` sessions are streamed one per line
+/// as they come out of Postgres. `limit` still defaults to the usual page
+/// size, but may be raised up to 10,000.
+async fn mix_history_handler(
+    State(database): State<Database>,
+    Query(params): Query<MixHistoryQuery>,
+    Pagination { limit, offset }: Pagination,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    if let Some(status) = params.status.as_deref()
+        && !MIX_STATUSES.contains(&status) {
+        return Err(ApiError::bad_request(format!("Unknown status '{}'", status))
+            .with_details(serde_json::json!({"allowed": MIX_STATUSES})));
+    }
+
+    let user_id = auth::user_id_from_headers(&headers)
+        .ok_or_else(|| ApiError::unauthorized("Missing or invalid X-User-Token"))?;
+
+    let wants_ndjson = headers
+        .get(axum::http::header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.split(',').any(|t| t.trim().starts_with(NDJSON)));
+    if wants_ndjson {
+        let limit = params.limit.map_or(limit, |l| l.clamp(1, HISTORY_STREAM_MAX_LIMIT));
+        let lines = database
+            .stream_mix_sessions_filtered(user_id, params.status, limit, offset)
+            .map(|row| {
+                let session = row.inspect_err(|e| error!("Mix history stream failed: {}", e))?;
+                let mut line = serde_json::to_vec(&session).map_err(|e| sqlx::Error::Decode(e.into()))?;
+                line.push(b'\n');
+                Ok::<_, sqlx::Error>(Bytes::from(line))
+            });
+        return Response::builder()
+            .header(axum::http::header::CONTENT_TYPE, NDJSON)
+            .body(axum::body::Body::from_stream(lines))
+            .map_err(|_| ApiError::internal("Failed to stream mix history"));
+    }
+
+    let sessions = database
+        .list_mix_sessions_filtered(&user_id, params.status.as_deref(), limit, offset)
+        .await
+        .map_err(|e| {
+            error!("Failed to list mix history: {}", e);
+            ApiError::database(&e, "Failed to retrieve mix history")
+        })?;
+
+    Ok(Json(serde_json::json!({
+        "sessions": sessions,
+        "limit": limit,
+        "offset": offset,
+    }))
+    .into_response())
+}
+
+async fn get_mix_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+) -> Result<Json<models::mix::MixData>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    match database.get_mix_data(session_uuid).await {
+        Ok(Some(mix_data)) => Ok(Json(mix_data)),
+        Ok(None) => Err(ApiError::not_found("Mix session not found")),
         Err(e) => {
-            (
-                axum::http::StatusCode::BAD_GATEWAY,
-                axum::Json(serde_json::json!({"error": format!("Orchestrator request failed: {}", e)}))
-            ).into_response()
+            error!("Failed to get mix data: {}", e);
+            Err(ApiError::database(&e, "Failed to retrieve mix data"))
         }
     }
 }
 
-async fn list_mixes_handler(
+/// A single track's metadata, e.g. for the player to refresh after
+/// re-resolving its stream
+async fn get_mix_track_handler(
     State(database): State<Database>,
-) -> impl IntoResponse {
-    match database.list_mix_sessions(50, 0).await {
-        Ok(sessions) => Json(sessions).into_response(),
+    Path(track_id): Path<String>,
+) -> Result<Json<models::mix::MixTrack>, ApiError> {
+    let track_uuid = Uuid::parse_str(&track_id).map_err(|_| ApiError::bad_request("Invalid track ID format"))?;
+
+    match database.get_mix_track(track_uuid).await {
+        Ok(Some(track)) => Ok(Json(track)),
+        Ok(None) => Err(ApiError::not_found("Mix track not found")),
         Err(e) => {
-            error!("Failed to list mix sessions: {}", e);
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": "Failed to retrieve mix sessions"}))
-            ).into_response()
+            error!("Failed to get mix track: {}", e);
+            Err(ApiError::database(&e, "Failed to retrieve mix track"))
         }
     }
 }
 
-async fn get_mix_handler(
+/// Manually reorder a mix's tracks; each track keeps its outgoing transition
+/// where it still has a next track, and any gap gets a suggested one
+async fn reorder_mix_tracks_handler(
     State(database): State<Database>,
     Path(session_id): Path<String>,
-) -> impl IntoResponse {
-    let session_uuid = match Uuid::parse_str(&session_id) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"error": "Invalid session ID format"}))
-            ).into_response();
+    Json(request): Json<models::mix::ReorderTracksRequest>,
+) -> Result<Json<models::mix::MixData>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    let track_ids: Vec<Uuid> = match database.get_mix_data(session_uuid).await {
+        Ok(Some(mix)) => mix.tracks.iter().map(|t| t.id).collect(),
+        Ok(None) => return Err(ApiError::not_found("Mix session not found")),
+        Err(e) => {
+            error!("Failed to get mix data: {}", e);
+            return Err(ApiError::database(&e, "Failed to reorder tracks"));
         }
     };
+    let new_order = request.validate(&track_ids).map_err(ApiError::bad_request)?;
 
-    match database.get_mix_data(session_uuid).await {
-        Ok(Some(mix_data)) => Json(mix_data).into_response(),
-        Ok(None) => {
-            (
-                axum::http::StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Mix session not found"}))
-            ).into_response()
+    database.reorder_mix_tracks(session_uuid, &new_order).await.map_err(|e| match e {
+        sqlx::Error::RowNotFound => {
+            ApiError::new(axum::http::StatusCode::CONFLICT, "The mix's tracks changed, reload and try again")
+        }
+        e => {
+            error!("Failed to reorder tracks for {}: {}", session_id, e);
+            ApiError::database(&e, "Failed to reorder tracks")
         }
+    })?;
+
+    match database.get_mix_data(session_uuid).await {
+        Ok(Some(mix)) => Ok(Json(mix)),
+        Ok(None) => Err(ApiError::not_found("Mix session not found")),
+        Err(e) => Err(ApiError::database(&e, "Failed to retrieve mix data")),
+    }
+}
+
+/// Swap the track at `track_order` for another without regenerating. The
+/// new track's audio features come from Spotify and the transitions into
+/// and out of it are re-suggested to fit.
+async fn replace_mix_track_handler(
+    State(database): State<Database>,
+    Path((session_id, track_order)): Path<(String, i32)>,
+    headers: axum::http::HeaderMap,
+    Json(mut track): Json<models::mix::CreateTrackRequest>,
+) -> Result<Json<models::mix::MixData>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    let mix = match database.get_mix_data(session_uuid).await {
+        Ok(Some(mix)) => mix,
+        Ok(None) => return Err(ApiError::not_found("Mix session not found")),
         Err(e) => {
             error!("Failed to get mix data: {}", e);
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": "Failed to retrieve mix data"}))
-            ).into_response()
+            return Err(ApiError::database(&e, "Failed to replace track"));
+        }
+    };
+    if !mix.tracks.iter().any(|t| t.track_order == track_order) {
+        return Err(ApiError::not_found(format!("Mix has no track at position {}", track_order)));
+    }
+    controllers::spotify::check_spotify_ids([track.spotify_id.as_str()])?;
+    track.track_order = track_order;
+
+    // Without features the transitions would be picked on the client's guesses
+    let access_token = controllers::spotify::bearer_or_app_token(&headers).await?;
+    if let Err(e) = enrich::apply_audio_features(&access_token, &mut track).await {
+        warn!("Using client-supplied features for {}: {}", track.spotify_id, e);
+    }
+
+    let last_order = mix.tracks.iter().map(|t| t.track_order).max().unwrap_or(track_order);
+    let energy_at = |order: i32| mix.tracks.iter().find(|t| t.track_order == order).map(|t| t.energy);
+    let mut transitions = Vec::new();
+    if let Some(previous) = energy_at(track_order - 1) {
+        transitions.push(planner::suggest_transition(
+            track_order - 1,
+            previous,
+            track.energy,
+            track_order == last_order,
+        ));
+    }
+    if let Some(next) = energy_at(track_order + 1) {
+        transitions.push(planner::suggest_transition(
+            track_order,
+            track.energy,
+            next,
+            track_order + 1 == last_order,
+        ));
+    }
+
+    match database.replace_mix_track(session_uuid, &track, &transitions).await {
+        Ok(true) => {}
+        Ok(false) => return Err(ApiError::not_found(format!("Mix has no track at position {}", track_order))),
+        Err(e) => {
+            error!("Failed to replace track {} in {}: {}", track_order, session_id, e);
+            return Err(ApiError::database(&e, "Failed to replace track"));
         }
     }
+
+    info!("Replaced track {} in mix {} with {}", track_order, session_id, track.spotify_id);
+    match database.get_mix_data(session_uuid).await {
+        Ok(Some(mix)) => Ok(Json(mix)),
+        Ok(None) => Err(ApiError::not_found("Mix session not found")),
+        Err(e) => Err(ApiError::database(&e, "Failed to retrieve mix data")),
+    }
 }
 
-async fn create_mix_session_handler(
+/// How long the orchestrator may take to write a mix's narration
+const NARRATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Write DJ voiceover scripts for each transition via the orchestrator's
+/// LLM, replacing any stored for the session
+async fn generate_mix_narration_handler(
     State(database): State<Database>,
     Path(session_id): Path<String>,
-    Json(payload): Json<serde_json::Value>,
-) -> impl IntoResponse {
-    let session_uuid = match Uuid::parse_str(&session_id) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"error": "Invalid session ID format"}))
-            ).into_response();
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<models::mix::MixNarration>>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+    let data = match database.get_mix_data(session_uuid).await {
+        Ok(Some(data)) => data,
+        Ok(None) => return Err(ApiError::not_found("Mix session not found")),
+        Err(e) => {
+            error!("Failed to get mix data for narration: {}", e);
+            return Err(ApiError::database(&e, "Failed to retrieve mix data"));
         }
     };
 
-    let prompt = payload.get("prompt")
-        .and_then(|p| p.as_str())
-        .unwrap_or("")
-        .to_string();
+    let narration_request = models::mix::NarrationRequest::from_mix(&data);
+    if narration_request.transitions.is_empty() {
+        return Err(ApiError::bad_request("Mix has no transitions to narrate"));
+    }
+
+    let Some(orchestrator_url) = SECRET_MANAGER.get_url("ORCHESTRATOR_URL") else {
+        return Err(ApiError::service_unavailable("Orchestrator not configured"));
+    };
+    if let Err(retry_in) = ORCHESTRATOR_BREAKER.try_acquire() {
+        return Err(ApiError::service_unavailable("Orchestrator is unavailable, try again shortly")
+            .with_code("orchestrator_unavailable")
+            .with_retry_after(retry_in.as_secs().max(1) as i64));
+    }
+
+    let mut request = reqwest::Client::new()
+        .post(format!("{}/narration", orchestrator_url))
+        .timeout(NARRATION_TIMEOUT)
+        .json(&narration_request);
+    if let Some(key) = headers.get("X-OpenAI-Key") {
+        request = request.header("X-OpenAI-Key", key.to_str().unwrap_or(""));
+    }
+    if let Some(request_id) = request_id::request_id_from_headers(&headers) {
+        request = request.header(request_id::REQUEST_ID_HEADER, request_id);
+    }
 
-    match database.create_mix_session(session_uuid, &prompt).await {
-        Ok(_) => {
-            info!("Created mix session: {}", session_id);
-            Json(serde_json::json!({"status": "created", "session_id": session_id})).into_response()
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            ORCHESTRATOR_BREAKER.record_failure();
+            return Err(ApiError::bad_gateway(format!("Orchestrator request failed: {}", e)));
         }
+    };
+    if response.status().is_server_error() {
+        ORCHESTRATOR_BREAKER.record_failure();
+    } else {
+        ORCHESTRATOR_BREAKER.record_success();
+    }
+
+    let status = axum::http::StatusCode::from_u16(response.status().as_u16())
+        .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+    let body_text = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(orchestrator_error(status, &body_text));
+    }
+
+    let scripts = serde_json::from_str::<models::mix::NarrationResponse>(&body_text)
+        .map_err(|e| e.to_string())
+        .and_then(|r| {
+            if r.scripts.len() == narration_request.transitions.len() {
+                Ok(r.scripts)
+            } else {
+                Err(format!(
+                    "expected {} scripts, got {}",
+                    narration_request.transitions.len(),
+                    r.scripts.len()
+                ))
+            }
+        })
+        .map_err(|e| {
+            error!("Unexpected orchestrator narration response: {}", e);
+            ApiError::bad_gateway(format!("Orchestrator response did not match the expected schema: {}", e))
+                .with_code("bad_orchestrator_response")
+        })?;
+
+    let rows: Vec<(i32, i32, String)> = narration_request
+        .transitions
+        .iter()
+        .zip(scripts)
+        .map(|(t, script)| (t.from_track_order, t.to_track_order, script))
+        .collect();
+    database
+        .replace_mix_narration(session_uuid, &rows)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to save narration for {}: {}", session_uuid, e);
+            ApiError::database(&e, "Failed to save narration")
+        })
+}
+
+/// Narration scripts written for the session so far, in play order
+async fn get_mix_narration_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<models::mix::MixNarration>>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+    database.get_mix_narration(session_uuid).await.map(Json).map_err(|e| {
+        error!("Failed to get narration for {}: {}", session_uuid, e);
+        ApiError::database(&e, "Failed to retrieve narration")
+    })
+}
+
+/// Resolve and cache the stream for every track of a mix ahead of playback,
+/// reporting which are ready. Failed tracks don't fail the request.
+async fn prefetch_mix_streams_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+    let tracks = match database.get_mix_data(session_uuid).await {
+        Ok(Some(data)) => data.tracks,
+        Ok(None) => return Err(ApiError::not_found("Mix session not found")),
         Err(e) => {
-            error!("Failed to create mix session: {}", e);
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": "Failed to create mix session"}))
-            ).into_response()
+            error!("Failed to get mix tracks for prefetch: {}", e);
+            return Err(ApiError::database(&e, "Failed to retrieve mix data"));
         }
+    };
+    if tracks.is_empty() {
+        return Err(ApiError::bad_request("Mix has no tracks to prefetch"));
     }
+
+    let streams = prefetch::prefetch_streams(&tracks).await;
+    let ready = streams.iter().filter(|s| s.ready).count();
+    info!("Prefetched {}/{} streams for mix {}", ready, streams.len(), session_uuid);
+
+    Ok(Json(serde_json::json!({
+        "session_id": session_uuid,
+        "ready": ready,
+        "failed": streams.len() - ready,
+        "tracks": streams,
+    })))
+}
+
+/// Rate a generated mix and flag liked transitions / skipped tracks
+async fn submit_mix_feedback_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+    Json(feedback): Json<MixFeedbackRequest>,
+) -> Result<(axum::http::StatusCode, Json<serde_json::Value>), ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    if !(1..=5).contains(&feedback.rating) {
+        return Err(ApiError::bad_request("rating must be between 1 and 5"));
+    }
+
+    match database.get_mix_session(session_uuid).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ApiError::not_found("Mix session not found")),
+        Err(e) => {
+            error!("Failed to get mix session: {}", e);
+            return Err(ApiError::database(&e, "Failed to save feedback"));
+        }
+    }
+
+    let foreign = database
+        .foreign_feedback_ids(session_uuid, &feedback.skipped_tracks, &feedback.liked_transitions)
+        .await
+        .map_err(|e| {
+            error!("Failed to validate feedback ids: {}", e);
+            ApiError::database(&e, "Failed to save feedback")
+        })?;
+    if !foreign.is_empty() {
+        return Err(ApiError::bad_request("Ids don't belong to this mix")
+            .with_details(serde_json::json!({"ids": foreign})));
+    }
+
+    let id = database
+        .insert_mix_feedback(session_uuid, &feedback)
+        .await
+        .map_err(|e| {
+            error!("Failed to save feedback: {}", e);
+            ApiError::database(&e, "Failed to save feedback")
+        })?;
+
+    info!("Saved feedback for mix {} (rating {})", session_id, feedback.rating);
+    Ok((
+        axum::http::StatusCode::CREATED,
+        Json(serde_json::json!({"id": id, "session_id": session_id})),
+    ))
+}
+
+/// Resolve a planned mix's Spotify tracks to YouTube videos before playback.
+/// Per-track failures are reported alongside the successes.
+async fn resolve_mix_youtube_handler(
+    headers: axum::http::HeaderMap,
+    Json(request): Json<ResolveYoutubeRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let access_token = spotify::bearer_token(&headers)?;
+
+    if request.spotify_ids.is_empty() {
+        return Err(ApiError::bad_request("At least one track is required"));
+    }
+    if request.spotify_ids.len() > spotify::MAX_YOUTUBE_RESOLVE_TRACKS {
+        return Err(ApiError::bad_request(format!(
+            "At most {} tracks can be resolved at once",
+            spotify::MAX_YOUTUBE_RESOLVE_TRACKS
+        )));
+    }
+    spotify::check_spotify_ids(request.spotify_ids.iter().map(String::as_str))?;
+
+    let tracks = spotify::resolve_tracks_to_youtube(&access_token, &request.spotify_ids).await;
+    let resolved = tracks.iter().filter(|t| t.resolved).count();
+    info!("Resolved {}/{} mix tracks to YouTube", resolved, tracks.len());
+
+    Ok(Json(serde_json::json!({
+        "resolved": resolved,
+        "failed": tracks.len() - resolved,
+        "tracks": tracks,
+    })))
+}
+
+/// Every progress message logged for a mix, oldest first
+async fn mix_progress_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<models::mix::MixProgressEntry>>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    database
+        .get_progress_history(session_uuid)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to get progress history: {}", e);
+            ApiError::database(&e, "Failed to retrieve progress history")
+        })
+}
+
+/// Download a mix as an M3U or PLS playlist, or as a JSON `MixDocument`.
+/// Playlist tracks that can't be resolved are left out and listed in a comment.
+async fn mix_export_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+    Query(params): Query<export::ExportQuery>,
+) -> Result<Response, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    let mix = match database.get_mix_data(session_uuid).await {
+        Ok(Some(mix)) => mix,
+        Ok(None) => return Err(ApiError::not_found("Mix session not found")),
+        Err(e) => {
+            error!("Failed to get mix data for export: {}", e);
+            return Err(ApiError::database(&e, "Failed to retrieve mix data"));
+        }
+    };
+
+    let body = match params.format {
+        export::ExportFormat::M3u => {
+            let entries = export::resolve_entries(&mix, params.source).await;
+            export::render_m3u(&mix.session.prompt, &entries)
+        }
+        export::ExportFormat::Pls => {
+            let entries = export::resolve_entries(&mix, params.source).await;
+            export::render_pls(&entries)
+        }
+        export::ExportFormat::Json => {
+            let document = export::build_document(mix).await;
+            serde_json::to_string_pretty(&document)
+                .map_err(|e| ApiError::internal(format!("Failed to serialize mix: {}", e)))?
+        }
+    };
+
+    let disposition = format!("attachment; filename=\"mix-{}.{}\"", session_uuid, params.format.extension());
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, params.format.content_type().to_string()),
+            (axum::http::header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Recreate a mix from a `MixDocument` (see `/mix/{id}/export?format=json`)
+/// as a new session owned by the caller
+async fn import_mix_handler(
+    State(database): State<Database>,
+    headers: axum::http::HeaderMap,
+    body: Bytes,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let document: export::MixDocument = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::bad_request(format!("Invalid mix document: {}", e)))?;
+    let max_tracks = SECRET_MANAGER.get("MIX_MAX_TRACKS").parse::<usize>().unwrap_or(60);
+
+    let mut mix_request = export::validate_document(document, max_tracks).map_err(|problems| {
+        ApiError::bad_request("Invalid mix document").with_details(serde_json::json!({"problems": problems}))
+    })?;
+    mix_request.prompt = clean_prompt(&mix_request.prompt)?;
+
+    let session_uuid = Uuid::new_v4();
+    let user_id = auth::user_id_from_headers(&headers);
+    database
+        .create_mix_session(session_uuid, &mix_request.prompt, user_id.as_deref(), None)
+        .await
+        .map_err(|e| {
+            error!("Failed to create imported mix session: {}", e);
+            ApiError::database(&e, "Failed to create mix session")
+        })?;
+    database.save_mix_data(session_uuid, mix_request).await.map_err(|e| {
+        error!("Failed to save imported mix {}: {}", session_uuid, e);
+        ApiError::database(&e, "Failed to save imported mix")
+    })?;
+
+    info!("Imported mix as session {}", session_uuid);
+    Ok(Json(serde_json::json!({"status": "imported", "session_id": session_uuid})))
+}
+
+/// Aggregated feedback for a mix
+async fn get_mix_feedback_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+) -> Result<Json<models::mix::MixFeedbackSummary>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    database
+        .get_mix_feedback_summary(session_uuid)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to get feedback summary: {}", e);
+            ApiError::database(&e, "Failed to retrieve feedback")
+        })
+}
+
+async fn create_mix_session_handler(
+    State(database): State<Database>,
+    Path(session_id): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(payload): Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let session_uuid = parse_session_id(&session_id)?;
+
+    let prompt = clean_prompt(payload.get("prompt").and_then(|p| p.as_str()).unwrap_or(""))?;
+
+    let user_id = auth::user_id_from_headers(&headers);
+    database
+        .create_mix_session(session_uuid, &prompt, user_id.as_deref(), None)
+        .await
+        .map_err(|e| {
+            error!("Failed to create mix session: {}", e);
+            ApiError::database(&e, "Failed to create mix session")
+        })?;
+
+    info!("Created mix session: {}", session_id);
+    Ok(Json(serde_json::json!({"status": "created", "session_id": session_id})))
 }
 
 #[tokio::main]
 async fn main() {
+    // RUST_LOG wins when set; otherwise use the LOG_LEVEL secret (info in prod, debug in dev)
+    let env_filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(SECRET_MANAGER.get("LOG_LEVEL")))
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+
     fmt()
-        .with_env_filter(EnvFilter::from_default_env().add_directive(Level::DEBUG.into()))
+        .with_env_filter(env_filter)
         .with_target(false)
         .init();
 
+    Lazy::force(&controllers::root::STARTED_AT);
+
     // Initialize database
     let database = match Database::new().await {
         Ok(db) => {
-            info!("📊 Connected to PostgreSQL database");
+            info!("📊 Connected to PostgreSQL database and applied migrations");
             db
         }
         Err(e) => {
@@ -541,12 +1755,35 @@ async fn main() {
         }
     };
 
-    // Run migrations
-    if let Err(e) = sqlx::migrate!("./migrations").run(database.pool()).await {
-        error!("❌ Failed to run database migrations: {}", e);
-        panic!("Database migrations failed");
+    // Mix generation needs the orchestrator; flag a bad URL now rather than on first use
+    if SECRET_MANAGER.get_url("ORCHESTRATOR_URL").is_none() {
+        error!(
+            "❌ ORCHESTRATOR_URL {:?} is not a valid http(s) URL; mix generation will return 503",
+            SECRET_MANAGER.get("ORCHESTRATOR_URL")
+        );
     }
-    info!("📊 Database migrations completed");
+
+    // A typo in SPOTIFY_SCOPES would otherwise only surface at the first login
+    if let Err(e) = controllers::spotify::parse_scopes(&SECRET_MANAGER.get("SPOTIFY_SCOPES")) {
+        error!("❌ SPOTIFY_SCOPES is invalid ({}); Spotify login will ask for the default scopes", e);
+    }
+
+    // A typo'd client id/secret would otherwise only surface at the first search
+    tokio::spawn(async {
+        match controllers::root::check_spotify_credentials().await {
+            Ok(()) => info!("✅ Spotify credentials accepted"),
+            Err(e) => error!("❌ Spotify credentials were rejected ({}); search and browse will fail", e),
+        }
+    });
+
+    // Expire stale OAuth states and token sessions held in memory
+    controllers::spotify::spawn_session_sweeper();
+
+    // Keep the latest progress per session so SSE clients can resume
+    progress::spawn_progress_recorder(database.clone(), REDIS_POOL.clone());
+
+    // Fail sessions the orchestrator never finished
+    progress::spawn_stale_session_sweeper(database.clone(), REDIS_POOL.clone());
 
     let port = SECRET_MANAGER.get("PORT");
     let backend_url = SECRET_MANAGER.get("BACKEND_URL");
@@ -562,27 +1799,61 @@ async fn main() {
         // Core routes
         .route("/", get(root_route))
         .route("/health", get(health_check_route))
+        .route("/livez", get(liveness_route))
+        .route("/metrics", get(metrics_route))
         // Spotify OAuth routes
         .nest("/spotify", spotify_routes())
+        // YouTube search and stream extraction
+        .nest("/song", song_routes())
+        // Listener preferences from onboarding
+        .nest("/session", session_routes())
+        // Operational endpoints (guarded by ADMIN_TOKEN)
+        .nest("/admin", admin_routes())
         // Mix generation and progress
         .route("/mix/generate", post(generate_mix_handler))
+        .route("/mix/plan", post(plan_mix_handler))
+        .route("/mix/resolve-youtube", post(resolve_mix_youtube_handler))
+        .route("/mix/history", get(mix_history_handler))
+        .route("/mix/import", post(import_mix_handler))
+        .route("/mix/track/{track_id}", get(get_mix_track_handler))
         .route("/ws/mix/{session_id}", get(ws_mix_handler))
         .route("/sse/mix/{session_id}", get(sse_mix_handler))
+        .route("/mix/{session_id}/transport", get(mix_transport_handler))
+        .route("/mix/{session_id}/stream-token", post(mix_stream_token_handler))
+        .route("/mix/{session_id}/regenerate", post(regenerate_mix_handler))
+        .route("/mix/{session_id}/feedback", get(get_mix_feedback_handler).post(submit_mix_feedback_handler))
+        .route("/mix/{session_id}/progress", get(mix_progress_handler))
+        .route("/mix/{session_id}/export", get(mix_export_handler))
+        .route("/mix/{session_id}/order", put(reorder_mix_tracks_handler))
+        .route("/mix/{session_id}/track/{track_order}", put(replace_mix_track_handler))
+        .route("/mix/{session_id}/narration", get(get_mix_narration_handler).post(generate_mix_narration_handler))
+        .route("/mix/{session_id}/prefetch", post(prefetch_mix_streams_handler))
+        // Shared listening sessions
+        .route("/ws/playback/{session_id}", get(ws_playback_handler))
         // Mix data API
         .route("/api/mixes", get(list_mixes_handler))
         .route("/api/mixes/{session_id}", get(get_mix_handler))
         .route("/api/mixes/{session_id}/create", post(create_mix_session_handler))
         // Middleware
         .layer(cors)
-        .layer(TraceLayer::new_for_http())
+        .layer(axum::middleware::from_fn(request_id::attach_request_id))
+        // Outside attach_request_id so it edits the body before it's compressed
+        .layer(compression::compression_layer())
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(request_id::make_request_span))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
         // Add database to request extensions
-        .with_state(database);
+        .with_state(AppState { database });
 
     info!("🎧 AI DJ Backend listening on {}", backend_url);
     info!("📡 WebSocket endpoint: /ws/mix/{{session_id}}");
     info!("📡 SSE endpoint: /sse/mix/{{session_id}}");
+    info!("📡 Transport negotiation: /mix/{{session_id}}/transport");
     info!("📊 Mix API endpoints: /api/mixes/*");
 
-    axum::serve(listener, app).await.unwrap();
+    // Peer addresses scope anonymous mix dedupe
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .await
+        .unwrap();
 }
 
@@ -1,18 +1,25 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::types::chrono::{DateTime, Utc};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+/// Every status a mix session can be in
+pub const MIX_STATUSES: [&str; 4] = ["generating", "completed", "error", "cancelled"];
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct MixSession {
     pub id: Uuid,
     pub prompt: String,
-    pub status: String, // "generating", "completed", "error"
+    pub status: String, // one of MIX_STATUSES
     pub created_at: DateTime<Utc>,
     pub completed_at: Option<DateTime<Utc>>,
     pub error_message: Option<String>,
     pub estimated_duration_minutes: Option<f64>,
     pub cdn_url: Option<String>,
+    pub user_id: Option<String>,
+    /// The session this one was regenerated from
+    pub parent_session_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -32,6 +39,10 @@ pub struct MixTrack {
     pub instrumentalness: f64,
     pub popularity: i32,
     pub track_order: i32,
+    /// Camelot keys that mix cleanly from `key`, derived on read
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub compatible_keys: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -81,7 +92,626 @@ pub struct CreateTrackRequest {
 pub struct CreateTransitionRequest {
     pub from_track_order: i32,
     pub to_track_order: i32,
+    /// Must parse as a `TransitionType`; stored in its canonical form
     pub transition_type: String,
     pub transition_bars: i32,
+    /// Must parse as a `TransitionDirection`; stored as the filter the
+    /// renderer sweeps (see [`TransitionDirection::filter`])
     pub transition_direction: Option<String>,
-}
\ No newline at end of file
+}
+
+/// Transitions the renderer knows how to apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionType {
+    Cut,
+    Crossfade,
+    EchoOut,
+    FilterSweep,
+    Beatmatch,
+    Backspin,
+}
+
+impl TransitionType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TransitionType::Cut => "cut",
+            TransitionType::Crossfade => "crossfade",
+            TransitionType::EchoOut => "echo_out",
+            TransitionType::FilterSweep => "filter_sweep",
+            TransitionType::Beatmatch => "beatmatch",
+            TransitionType::Backspin => "backspin",
+        }
+    }
+}
+
+impl std::str::FromStr for TransitionType {
+    type Err = String;
+
+    /// Case-insensitive, with `-` or spaces in place of `_`, plus a few
+    /// common synonyms (`fade`, `echo`, `sweep`, `blend`, ...)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_lowercase().replace(['-', ' '], "_");
+        match normalized.as_str() {
+            "cut" | "hard_cut" | "drop" => Ok(TransitionType::Cut),
+            "crossfade" | "cross_fade" | "fade" => Ok(TransitionType::Crossfade),
+            "echo_out" | "echoout" | "echo" => Ok(TransitionType::EchoOut),
+            "filter_sweep" | "filtersweep" | "filter" | "sweep" => Ok(TransitionType::FilterSweep),
+            "beatmatch" | "beat_match" | "blend" => Ok(TransitionType::Beatmatch),
+            "backspin" | "back_spin" | "spinback" => Ok(TransitionType::Backspin),
+            _ => Err(format!(
+                "Unknown transition type '{}', expected cut, crossfade, echo_out, filter_sweep, beatmatch or backspin",
+                s
+            )),
+        }
+    }
+}
+
+/// Energy direction of a transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransitionDirection {
+    Up,
+    Down,
+    Neutral,
+}
+
+impl TransitionDirection {
+    /// The renderer's name for the filter sweep in this direction, which is
+    /// what gets stored: it only knows `highpass` and `lowpass`, and
+    /// renders a missing direction with its default
+    pub fn filter(self) -> Option<&'static str> {
+        match self {
+            TransitionDirection::Up => Some("highpass"),
+            TransitionDirection::Down => Some("lowpass"),
+            TransitionDirection::Neutral => None,
+        }
+    }
+}
+
+impl std::str::FromStr for TransitionDirection {
+    type Err = String;
+
+    /// Case-insensitive. The orchestrator's filter sweep directions are
+    /// accepted too: a highpass sweep builds energy, a lowpass one drops it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "up" | "higher" | "highpass" => Ok(TransitionDirection::Up),
+            "down" | "lower" | "lowpass" => Ok(TransitionDirection::Down),
+            "neutral" => Ok(TransitionDirection::Neutral),
+            _ => Err(format!(
+                "Unknown transition direction '{}', expected up, down or neutral",
+                s
+            )),
+        }
+    }
+}
+
+/// Body of `POST /mix/{session_id}/feedback`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MixFeedbackRequest {
+    /// 1 (bad) to 5 (great)
+    pub rating: i32,
+    /// `MixTransition` ids the listener liked
+    #[serde(default)]
+    pub liked_transitions: Vec<Uuid>,
+    /// `MixTrack` ids the listener skipped
+    #[serde(default)]
+    pub skipped_tracks: Vec<Uuid>,
+}
+
+/// New positions for every track in a mix
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReorderTracksRequest {
+    pub tracks: Vec<TrackPosition>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackPosition {
+    pub track_id: Uuid,
+    pub track_order: i32,
+}
+
+impl ReorderTracksRequest {
+    /// The new order must name each of the mix's tracks exactly once and
+    /// place them at positions `0..n`, so transitions stay contiguous
+    pub fn validate(&self, existing: &[Uuid]) -> Result<Vec<(Uuid, i32)>, String> {
+        let existing: std::collections::HashSet<&Uuid> = existing.iter().collect();
+        let mut seen_ids = std::collections::HashSet::new();
+        let mut seen_orders = std::collections::HashSet::new();
+
+        for position in &self.tracks {
+            if !existing.contains(&position.track_id) {
+                return Err(format!("track {} is not part of this mix", position.track_id));
+            }
+            if !seen_ids.insert(position.track_id) {
+                return Err(format!("track {} is listed more than once", position.track_id));
+            }
+            if !(0..existing.len() as i32).contains(&position.track_order) {
+                return Err(format!("track_order must be between 0 and {}", existing.len() as i32 - 1));
+            }
+            if !seen_orders.insert(position.track_order) {
+                return Err(format!("track_order {} is used more than once", position.track_order));
+            }
+        }
+        if seen_ids.len() != existing.len() {
+            return Err(format!(
+                "expected all {} tracks, got {}",
+                existing.len(),
+                seen_ids.len()
+            ));
+        }
+        Ok(self.tracks.iter().map(|p| (p.track_id, p.track_order)).collect())
+    }
+}
+
+/// How often a track or transition was mentioned in feedback
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct FeedbackCount {
+    pub id: Uuid,
+    pub count: i64,
+}
+
+/// Feedback for one mix, aggregated across every submission
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MixFeedbackSummary {
+    pub session_id: Uuid,
+    pub feedback_count: i64,
+    pub average_rating: Option<f64>,
+    pub liked_transitions: Vec<FeedbackCount>,
+    pub skipped_tracks: Vec<FeedbackCount>,
+}
+
+/// One logged progress message, as returned by `GET /mix/{id}/progress`
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MixProgressEntry {
+    pub seq: i64,
+    pub stage: String,
+    pub percent: Option<i32>,
+    pub message: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Clean up a user-supplied mix prompt: control characters are dropped
+/// (newlines and tabs become spaces), surrounding whitespace is trimmed, and
+/// the result must be non-empty and at most `max_chars` characters.
+pub fn sanitize_prompt(prompt: &str, max_chars: usize) -> Result<String, String> {
+    let cleaned: String = prompt
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    let cleaned = cleaned.trim();
+
+    if cleaned.is_empty() {
+        return Err("prompt must not be empty".to_string());
+    }
+    if cleaned.chars().count() > max_chars {
+        return Err(format!("prompt must be at most {} characters", max_chars));
+    }
+    Ok(cleaned.to_string())
+}
+
+/// Hash of a prompt and who sent it (`scope`: a user id or, for anonymous
+/// callers, their address), used to spot repeat submissions. Case and
+/// whitespace differences don't count as a different prompt.
+pub fn prompt_fingerprint(prompt: &str, scope: &str) -> String {
+    let normalized = prompt.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ");
+    let digest = Sha256::digest(format!("{}\n{}", scope, normalized).as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Length a generation request asks for, checked against the configured
+/// cap before anything is sent to the orchestrator
+#[derive(Debug, Default, Deserialize)]
+pub struct MixLengthRequest {
+    /// Whole minutes, as the orchestrator takes them
+    #[serde(default, alias = "estimated_duration_minutes")]
+    pub duration_minutes: Option<i64>,
+}
+
+impl MixLengthRequest {
+    /// The duration to send the orchestrator. Without one it would size the
+    /// mix from the prompt, so the cap is sent instead and a prompt asking
+    /// for more can't get past it.
+    pub fn resolve(&self, max_minutes: i64) -> Result<i64, String> {
+        match self.duration_minutes {
+            None => Ok(max_minutes),
+            Some(minutes) if minutes <= 0 => Err("duration_minutes must be positive".to_string()),
+            Some(minutes) if minutes > max_minutes => {
+                Err(format!("duration_minutes must be at most {}", max_minutes))
+            }
+            Some(minutes) => Ok(minutes),
+        }
+    }
+}
+
+/// Overrides applied on top of the original session when regenerating
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegenerateMixRequest {
+    /// Replaces the original prompt
+    pub prompt: Option<String>,
+    /// 0.0-1.0; defaults to the session profile's energy level
+    pub target_energy: Option<f64>,
+    #[serde(default)]
+    pub exclude_artists: Vec<String>,
+    pub duration_minutes: Option<i32>,
+}
+
+/// Body of the orchestrator's `POST /generate-mix` response. Mirrors
+/// `GenerateMixResponse` in the orchestrator; fields it may omit have the
+/// defaults used when the mix is saved.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrchestratorMixResponse {
+    pub session_id: Uuid,
+    pub status: String,
+    pub message: String,
+    #[serde(default)]
+    pub playlist: Vec<OrchestratorTrack>,
+    #[serde(default)]
+    pub estimated_duration_minutes: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+}
+
+/// A playlist entry in `OrchestratorMixResponse`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrchestratorTrack {
+    pub spotify_id: String,
+    pub title: String,
+    pub artist: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    pub duration_ms: i32,
+    pub key: String,
+    pub energy: f64,
+    pub danceability: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valence: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acousticness: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instrumentalness: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub popularity: Option<i32>,
+    /// Transition into the next track
+    pub transition: OrchestratorTransition,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrchestratorTransition {
+    #[serde(rename = "type")]
+    pub transition_type: String,
+    #[serde(default = "default_transition_bars")]
+    pub bars: i32,
+    #[serde(default)]
+    pub direction: Option<String>,
+}
+
+fn default_transition_bars() -> i32 {
+    8
+}
+
+impl OrchestratorMixResponse {
+    /// The tracks and transitions to save for this session
+    pub fn to_create_request(&self) -> CreateMixRequest {
+        let mut tracks = Vec::with_capacity(self.playlist.len());
+        let mut transitions = Vec::with_capacity(self.playlist.len());
+
+        for (i, track) in self.playlist.iter().enumerate() {
+            tracks.push(CreateTrackRequest {
+                spotify_id: track.spotify_id.clone(),
+                title: track.title.clone(),
+                artist: track.artist.clone(),
+                album: track.album.clone().unwrap_or_else(|| "Unknown".to_string()),
+                duration_ms: track.duration_ms,
+                key: track.key.clone(),
+                energy: track.energy,
+                danceability: track.danceability,
+                valence: track.valence.unwrap_or(0.5),
+                acousticness: track.acousticness.unwrap_or(0.1),
+                instrumentalness: track.instrumentalness.unwrap_or(0.1),
+                popularity: track.popularity.unwrap_or(50),
+                track_order: i as i32,
+            });
+            transitions.push(CreateTransitionRequest {
+                from_track_order: i as i32,
+                to_track_order: (i + 1) as i32,
+                transition_type: track.transition.transition_type.clone(),
+                transition_bars: track.transition.bars,
+                transition_direction: track.transition.direction.clone(),
+            });
+        }
+
+        CreateMixRequest {
+            prompt: self.prompt.clone().unwrap_or_default(),
+            tracks,
+            transitions,
+            estimated_duration_minutes: Some(self.estimated_duration_minutes),
+        }
+    }
+}
+
+/// A short spoken DJ line for the transition out of `from_track_order`, read
+/// out by the player with TTS
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MixNarration {
+    pub id: Uuid,
+    pub mix_session_id: Uuid,
+    pub from_track_order: i32,
+    pub to_track_order: i32,
+    pub script: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of the orchestrator's `POST /narration`
+#[derive(Debug, Serialize)]
+pub struct NarrationRequest {
+    pub prompt: String,
+    pub transitions: Vec<NarrationTransition>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NarrationTransition {
+    pub from_track_order: i32,
+    pub to_track_order: i32,
+    pub from_track: NarrationTrack,
+    pub to_track: NarrationTrack,
+    #[serde(rename = "type")]
+    pub transition_type: String,
+    pub bars: i32,
+    pub direction: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NarrationTrack {
+    pub title: String,
+    pub artist: String,
+    pub key: String,
+    pub energy: f64,
+}
+
+impl From<&MixTrack> for NarrationTrack {
+    fn from(track: &MixTrack) -> Self {
+        Self {
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            key: track.key.clone(),
+            energy: track.energy,
+        }
+    }
+}
+
+/// The orchestrator's scripts, one per requested transition and in order
+#[derive(Debug, Deserialize)]
+pub struct NarrationResponse {
+    pub scripts: Vec<String>,
+}
+
+impl NarrationRequest {
+    /// One entry per transition, in play order. Transitions pointing past the
+    /// last track (the orchestrator adds one after the final song) are skipped.
+    pub fn from_mix(data: &MixData) -> Self {
+        let by_order: std::collections::HashMap<i32, &MixTrack> =
+            data.tracks.iter().map(|t| (t.track_order, t)).collect();
+
+        let mut transitions: Vec<NarrationTransition> = data
+            .transitions
+            .iter()
+            .filter_map(|t| {
+                let from = by_order.get(&t.from_track_order)?;
+                let to = by_order.get(&t.to_track_order)?;
+                Some(NarrationTransition {
+                    from_track_order: t.from_track_order,
+                    to_track_order: t.to_track_order,
+                    from_track: (*from).into(),
+                    to_track: (*to).into(),
+                    transition_type: t.transition_type.clone(),
+                    bars: t.transition_bars,
+                    direction: t.transition_direction.clone(),
+                })
+            })
+            .collect();
+        transitions.sort_by_key(|t| t.from_track_order);
+
+        Self {
+            prompt: data.session.prompt.clone(),
+            transitions,
+        }
+    }
+}
+
+/// Commands a client can send over the mix progress WebSocket
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MixSocketCommand {
+    /// Resend every progress event recorded so far
+    Replay,
+    Ping,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn mix_track(order: i32, title: &str) -> MixTrack {
+        MixTrack {
+            id: Uuid::new_v4(),
+            mix_session_id: Uuid::nil(),
+            spotify_id: String::new(),
+            title: title.to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            duration_ms: 180_000,
+            key: "8A".to_string(),
+            energy: 0.5,
+            danceability: 0.5,
+            valence: 0.5,
+            acousticness: 0.1,
+            instrumentalness: 0.1,
+            popularity: 50,
+            track_order: order,
+            compatible_keys: vec![],
+        }
+    }
+
+    fn mix_transition(from: i32, to: i32) -> MixTransition {
+        MixTransition {
+            id: Uuid::new_v4(),
+            mix_session_id: Uuid::nil(),
+            from_track_order: from,
+            to_track_order: to,
+            transition_type: "crossfade".to_string(),
+            transition_bars: 8,
+            transition_direction: None,
+        }
+    }
+
+    #[test]
+    fn narration_covers_transitions_between_existing_tracks_in_order() {
+        let data = MixData {
+            session: MixSession {
+                id: Uuid::nil(),
+                prompt: "sunset house".to_string(),
+                status: "completed".to_string(),
+                created_at: Utc::now(),
+                completed_at: None,
+                error_message: None,
+                estimated_duration_minutes: None,
+                cdn_url: None,
+                user_id: None,
+                parent_session_id: None,
+            },
+            tracks: vec![mix_track(0, "One"), mix_track(1, "Two"), mix_track(2, "Three")],
+            transitions: vec![mix_transition(1, 2), mix_transition(0, 1), mix_transition(2, 3)],
+        };
+
+        let request = NarrationRequest::from_mix(&data);
+        assert_eq!(request.prompt, "sunset house");
+        let pairs: Vec<(&str, &str)> = request
+            .transitions
+            .iter()
+            .map(|t| (t.from_track.title.as_str(), t.to_track.title.as_str()))
+            .collect();
+        assert_eq!(pairs, [("One", "Two"), ("Two", "Three")]);
+    }
+
+    #[test]
+    fn sanitizes_prompt() {
+        assert_eq!(sanitize_prompt("  deep house\nfor\tcoding\u{0}\u{7} ", 100).unwrap(), "deep house for coding");
+        assert!(sanitize_prompt(" \u{1b}\n ", 100).is_err());
+        assert!(sanitize_prompt("ééé", 3).is_ok());
+        assert!(sanitize_prompt("éééé", 3).is_err());
+    }
+
+    #[test]
+    fn prompt_fingerprint_ignores_case_and_spacing() {
+        let a = prompt_fingerprint("Deep  House for\ta sunset", "user:user-1");
+        assert_eq!(a, prompt_fingerprint("deep house for a sunset ", "user:user-1"));
+        assert_ne!(a, prompt_fingerprint("deep house for a sunset", "user:user-2"));
+        assert_ne!(a, prompt_fingerprint("deep house for a sunset", "ip:203.0.113.7"));
+    }
+
+    #[test]
+    fn validates_mix_length() {
+        let length = |duration_minutes| MixLengthRequest { duration_minutes };
+        assert_eq!(length(None).resolve(180), Ok(180));
+        assert_eq!(length(Some(180)).resolve(180), Ok(180));
+        assert_eq!(length(Some(45)).resolve(180), Ok(45));
+        assert!(length(Some(181)).resolve(180).is_err());
+        assert!(length(Some(0)).resolve(180).is_err());
+        assert!(length(Some(-3)).resolve(180).is_err());
+
+        let parsed: MixLengthRequest =
+            serde_json::from_value(serde_json::json!({"prompt": "x", "estimated_duration_minutes": 45})).unwrap();
+        assert_eq!(parsed.duration_minutes, Some(45));
+    }
+
+    #[test]
+    fn parses_transition_directions() {
+        assert_eq!("Up".parse::<TransitionDirection>(), Ok(TransitionDirection::Up));
+        assert_eq!("HIGHER".parse::<TransitionDirection>(), Ok(TransitionDirection::Up));
+        assert_eq!("highpass".parse::<TransitionDirection>(), Ok(TransitionDirection::Up));
+        assert_eq!(" lowpass ".parse::<TransitionDirection>(), Ok(TransitionDirection::Down));
+        assert_eq!("neutral".parse::<TransitionDirection>(), Ok(TransitionDirection::Neutral));
+        assert!("sideways".parse::<TransitionDirection>().is_err());
+        assert_eq!(serde_json::to_value(TransitionDirection::Down).unwrap(), "down");
+    }
+
+    #[test]
+    fn stored_directions_round_trip_through_the_renderer_filter() {
+        for direction in [TransitionDirection::Up, TransitionDirection::Down] {
+            let stored = direction.filter().unwrap();
+            assert!(["highpass", "lowpass"].contains(&stored));
+            assert_eq!(stored.parse::<TransitionDirection>(), Ok(direction));
+        }
+        assert_eq!("higher".parse::<TransitionDirection>().unwrap().filter(), Some("highpass"));
+        assert_eq!(TransitionDirection::Neutral.filter(), None);
+    }
+
+    #[test]
+    fn parses_transition_types() {
+        assert_eq!("crossfade".parse::<TransitionType>(), Ok(TransitionType::Crossfade));
+        assert_eq!("Cross-Fade".parse::<TransitionType>(), Ok(TransitionType::Crossfade));
+        assert_eq!("echo out".parse::<TransitionType>(), Ok(TransitionType::EchoOut));
+        assert_eq!("FILTER_SWEEP".parse::<TransitionType>(), Ok(TransitionType::FilterSweep));
+        assert_eq!("beat-match".parse::<TransitionType>(), Ok(TransitionType::Beatmatch));
+        assert_eq!("backspin".parse::<TransitionType>(), Ok(TransitionType::Backspin));
+        assert!("scratch".parse::<TransitionType>().is_err());
+        assert_eq!(TransitionType::EchoOut.as_str(), "echo_out");
+    }
+
+    #[test]
+    fn validates_track_reorder() {
+        let ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let request = |positions: &[(Uuid, i32)]| ReorderTracksRequest {
+            tracks: positions
+                .iter()
+                .map(|&(track_id, track_order)| TrackPosition { track_id, track_order })
+                .collect(),
+        };
+
+        let valid = request(&[(ids[2], 0), (ids[0], 1), (ids[1], 2)]);
+        assert_eq!(valid.validate(&ids).unwrap(), [(ids[2], 0), (ids[0], 1), (ids[1], 2)]);
+
+        // Missing, foreign and duplicated tracks, and gaps or repeats in the order
+        assert!(request(&[(ids[0], 0), (ids[1], 1)]).validate(&ids).is_err());
+        assert!(request(&[(ids[0], 0), (ids[1], 1), (Uuid::new_v4(), 2)]).validate(&ids).is_err());
+        assert!(request(&[(ids[0], 0), (ids[0], 1), (ids[1], 2)]).validate(&ids).is_err());
+        assert!(request(&[(ids[0], 0), (ids[1], 1), (ids[2], 3)]).validate(&ids).is_err());
+        assert!(request(&[(ids[0], 0), (ids[1], 0), (ids[2], 2)]).validate(&ids).is_err());
+    }
+
+    #[test]
+    fn parses_orchestrator_response() {
+        let body = serde_json::json!({
+            "session_id": "7d3b1c9e-2f4a-4b8e-9c1d-5e6f7a8b9c0d",
+            "status": "processing",
+            "message": "Generating your 4 minute mix...",
+            "playlist": [{
+                "spotify_id": "abc", "title": "One", "artist": "A", "duration_ms": 240000,
+                "key": "8A", "energy": 0.7, "danceability": 0.8,
+                "transition": {"type": "crossfade", "bars": 16, "direction": null}
+            }],
+            "estimated_duration_minutes": 4.0
+        });
+        let response: OrchestratorMixResponse = serde_json::from_value(body).unwrap();
+        let request = response.to_create_request();
+        assert_eq!(request.tracks[0].album, "Unknown");
+        assert_eq!(request.tracks[0].popularity, 50);
+        assert_eq!(request.transitions[0].transition_bars, 16);
+        assert_eq!(request.estimated_duration_minutes, Some(4.0));
+
+        // Contract drift: a missing session id or a mistyped field is rejected
+        assert!(serde_json::from_value::<OrchestratorMixResponse>(
+            serde_json::json!({"status": "processing", "message": "x"})
+        ).is_err());
+        assert!(serde_json::from_value::<OrchestratorMixResponse>(serde_json::json!({
+            "session_id": "7d3b1c9e-2f4a-4b8e-9c1d-5e6f7a8b9c0d", "status": "processing",
+            "message": "x", "playlist": [{"spotify_id": "abc"}]
+        })).is_err());
+    }
+}
@@ -1 +1,6 @@
-pub mod mix;
\ No newline at end of file
+pub mod mix;
+pub mod playback;
+pub mod session;
+pub mod song;
+pub mod spotify;
+pub mod track;
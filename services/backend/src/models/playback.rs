@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackAction {
+    Play,
+    Pause,
+    Seek,
+    Position,
+}
+
+/// Playback state change sent by a client in a shared listening session
+#[derive(Debug, Deserialize)]
+pub struct PlaybackCommand {
+    #[serde(rename = "type")]
+    pub action: PlaybackAction,
+    pub track_index: i32,
+    pub position_ms: i64,
+}
+
+/// Playback event broadcast to every device in the session
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlaybackEvent {
+    #[serde(rename = "type")]
+    pub action: PlaybackAction,
+    pub track_index: i32,
+    pub position_ms: i64,
+    /// Connection that published the event, so it isn't echoed back to itself
+    pub origin: String,
+    pub sent_at: i64,
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+/// A listener's "vibe" preferences, used to seed recommendations and plans
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SessionProfile {
+    pub session_id: String,
+    pub mood: String,
+    pub genres: Vec<String>,
+    /// 0.0 (chill) to 1.0 (peak time)
+    pub energy_level: f64,
+    pub explicit_ok: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SessionProfile {
+    /// Map the free-form mood to Spotify's valence (musical positivity)
+    pub fn target_valence(&self) -> Option<f64> {
+        let mood = self.mood.to_lowercase();
+        let valence = match mood.as_str() {
+            "happy" | "joyful" | "euphoric" | "uplifting" | "party" => 0.85,
+            "upbeat" | "energetic" | "hype" | "confident" => 0.7,
+            "chill" | "relaxed" | "mellow" | "focused" => 0.5,
+            "moody" | "dark" | "melancholic" => 0.3,
+            "sad" | "heartbroken" => 0.15,
+            _ => return None,
+        };
+        Some(valence)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionProfileRequest {
+    pub mood: String,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    pub energy_level: f64,
+    #[serde(default = "default_explicit_ok")]
+    pub explicit_ok: bool,
+}
+
+fn default_explicit_ok() -> bool {
+    true
+}
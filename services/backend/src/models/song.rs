@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+/// A YouTube search hit, before any stream extraction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackValueResponse {
+    pub video_id: String,
+    pub title: String,
+    pub channel_title: String,
+    pub thumbnail_url: Option<String>,
+    /// Only known when durations were looked up for duration matching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+}
+
+/// A playable YouTube track with its resolved audio stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Track {
+    pub video_id: String,
+    pub title: String,
+    pub channel_title: String,
+    pub thumbnail_url: Option<String>,
+    pub stream_url: String,
+}
+
+impl Track {
+    pub fn from_search(result: TrackValueResponse, stream_url: String) -> Self {
+        Self {
+            video_id: result.video_id,
+            title: result.title,
+            channel_title: result.channel_title,
+            thumbnail_url: result.thumbnail_url,
+            stream_url,
+        }
+    }
+}
+
+/// Video metadata from `yt-dlp -J`, fetched without resolving a stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SongMetadata {
+    pub video_id: String,
+    pub title: String,
+    /// Duration in seconds
+    pub duration: Option<f64>,
+    pub uploader: Option<String>,
+    pub view_count: Option<i64>,
+    pub is_live: bool,
+}
+
+impl SongMetadata {
+    /// Map yt-dlp's info JSON into the fields we care about
+    pub fn from_info_json(info: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            video_id: info.get("id")?.as_str()?.to_string(),
+            title: info.get("title").and_then(|t| t.as_str()).unwrap_or_default().to_string(),
+            duration: info.get("duration").and_then(|d| d.as_f64()),
+            uploader: info.get("uploader").and_then(|u| u.as_str()).map(|u| u.to_string()),
+            view_count: info.get("view_count").and_then(|v| v.as_i64()),
+            is_live: info.get("is_live").and_then(|l| l.as_bool()).unwrap_or(false),
+        })
+    }
+}
+
+/// One audio-only stream from yt-dlp's `formats` list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioFormat {
+    pub format_id: String,
+    pub url: String,
+    pub ext: String,
+    /// Average bitrate in kbit/s
+    pub abr: Option<f64>,
+    /// Size in bytes; yt-dlp's estimate when the exact size isn't known
+    pub filesize: Option<i64>,
+}
+
+impl AudioFormat {
+    /// Every audio-only format in yt-dlp's info JSON, highest bitrate first
+    pub fn list_from_info_json(info: &serde_json::Value) -> Vec<Self> {
+        let mut formats: Vec<Self> = info
+            .get("formats")
+            .and_then(|f| f.as_array())
+            .map(|formats| {
+                formats
+                    .iter()
+                    .filter(|f| {
+                        f.get("vcodec").and_then(|v| v.as_str()) == Some("none")
+                            && f.get("acodec").and_then(|a| a.as_str()).is_some_and(|a| a != "none")
+                    })
+                    .filter_map(|f| {
+                        Some(Self {
+                            format_id: f.get("format_id")?.as_str()?.to_string(),
+                            url: f.get("url")?.as_str()?.to_string(),
+                            ext: f.get("ext").and_then(|e| e.as_str()).unwrap_or_default().to_string(),
+                            abr: f.get("abr").and_then(|a| a.as_f64()),
+                            filesize: f
+                                .get("filesize")
+                                .and_then(|s| s.as_i64())
+                                .or_else(|| f.get("filesize_approx").and_then(|s| s.as_i64())),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        formats.sort_by(|a, b| b.abr.unwrap_or(0.0).total_cmp(&a.abr.unwrap_or(0.0)));
+        formats
+    }
+}
+
+/// A search hit with all of its audio-only streams, so the player can pick
+/// one its device supports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackFormats {
+    pub video_id: String,
+    pub title: String,
+    pub channel_title: String,
+    pub thumbnail_url: Option<String>,
+    pub formats: Vec<AudioFormat>,
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether `id` looks like a Spotify track/artist/album id: 22 base62 characters
+pub fn is_valid_spotify_id(id: &str) -> bool {
+    id.len() == 22 && id.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// Flattened Spotify track, so clients don't need to know Spotify's nested shape
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub id: String,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub image_url: Option<String>,
+    pub duration_ms: i64,
+    pub preview_url: Option<String>,
+    pub explicit: bool,
+    #[serde(default)]
+    pub popularity: i64,
+}
+
+impl SearchResult {
+    /// Map a Spotify track object; returns `None` when the id or name is missing
+    pub fn from_spotify_track(track: &serde_json::Value) -> Option<Self> {
+        let id = track.get("id")?.as_str()?.to_string();
+        let title = track.get("name")?.as_str()?.to_string();
+
+        let artists = track
+            .get("artists")
+            .and_then(|a| a.as_array())
+            .map(|artists| {
+                artists
+                    .iter()
+                    .filter_map(|a| a.get("name").and_then(|n| n.as_str()))
+                    .map(|n| n.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let album = track.get("album");
+
+        Some(Self {
+            id,
+            title,
+            artists,
+            album: album
+                .and_then(|a| a.get("name"))
+                .and_then(|n| n.as_str())
+                .unwrap_or("Unknown")
+                .to_string(),
+            // Spotify lists images largest first
+            image_url: album
+                .and_then(|a| a.get("images"))
+                .and_then(|i| i.as_array())
+                .and_then(|i| i.first())
+                .and_then(|i| i.get("url"))
+                .and_then(|u| u.as_str())
+                .map(|u| u.to_string()),
+            duration_ms: track.get("duration_ms").and_then(|d| d.as_i64()).unwrap_or(0),
+            preview_url: track
+                .get("preview_url")
+                .and_then(|p| p.as_str())
+                .map(|p| p.to_string()),
+            explicit: track.get("explicit").and_then(|e| e.as_bool()).unwrap_or(false),
+            popularity: track.get("popularity").and_then(|p| p.as_i64()).unwrap_or(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_spotify_ids() {
+        assert!(is_valid_spotify_id("4uLU6hMCjMI75M1A2tKUQC"));
+        assert!(!is_valid_spotify_id("4uLU6hMCjMI75M1A2tKUQ"));
+        assert!(!is_valid_spotify_id("4uLU6hMCjMI75M1A2tKUQC1"));
+        assert!(!is_valid_spotify_id("4uLU6hMCjMI75M1A2tK-QC"));
+        assert!(!is_valid_spotify_id("spotify:track:4uLU6hMC"));
+        assert!(!is_valid_spotify_id(""));
+    }
+
+    #[test]
+    fn flattens_spotify_tracks() {
+        let track = serde_json::json!({
+            "id": "4uLU6hMCjMI75M1A2tKUQC",
+            "name": "One More Time",
+            "artists": [{"name": "Daft Punk"}, {"name": "Romanthony"}],
+            "album": {
+                "name": "Discovery",
+                "images": [{"url": "https://i.scdn.co/large"}, {"url": "https://i.scdn.co/small"}]
+            },
+            "duration_ms": 320_357,
+            "preview_url": null,
+            "explicit": true,
+            "popularity": 81
+        });
+        let result = SearchResult::from_spotify_track(&track).unwrap();
+        assert_eq!(result.title, "One More Time");
+        assert_eq!(result.artists, ["Daft Punk", "Romanthony"]);
+        assert_eq!(result.album, "Discovery");
+        assert_eq!(result.image_url.as_deref(), Some("https://i.scdn.co/large"));
+        assert_eq!(result.duration_ms, 320_357);
+        assert_eq!(result.preview_url, None);
+        assert!(result.explicit);
+        assert_eq!(result.popularity, 81);
+    }
+
+    #[test]
+    fn fills_in_missing_track_fields() {
+        let bare = SearchResult::from_spotify_track(&serde_json::json!({"id": "abc", "name": "Untitled"})).unwrap();
+        assert!(bare.artists.is_empty());
+        assert_eq!(bare.album, "Unknown");
+        assert_eq!(bare.image_url, None);
+        assert_eq!((bare.duration_ms, bare.explicit, bare.popularity), (0, false, 0));
+
+        assert!(SearchResult::from_spotify_track(&serde_json::json!({"name": "No id"})).is_none());
+        assert!(SearchResult::from_spotify_track(&serde_json::json!({"id": "abc"})).is_none());
+    }
+}
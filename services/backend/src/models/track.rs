@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+
+use crate::audio_source::StreamInfo;
+use crate::camelot;
+use crate::models::mix::MixTrack;
+use crate::models::song::TrackValueResponse;
+use crate::models::spotify::SearchResult;
+
+/// Spotify's analysis of a track, with the key already in Camelot notation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackFeatures {
+    pub tempo: Option<f64>,
+    pub key: Option<String>,
+    pub energy: Option<f64>,
+    pub danceability: Option<f64>,
+    pub valence: Option<f64>,
+    pub acousticness: Option<f64>,
+    pub instrumentalness: Option<f64>,
+}
+
+impl TrackFeatures {
+    /// Map a Spotify audio-features object; `None` for tracks Spotify
+    /// hasn't analysed (a `null` entry)
+    pub fn from_spotify(features: &serde_json::Value) -> Option<Self> {
+        if !features.is_object() {
+            return None;
+        }
+        let feature = |name: &str| features[name].as_f64();
+        Some(Self {
+            tempo: feature("tempo"),
+            key: camelot::to_camelot(
+                features["key"].as_i64().unwrap_or(-1) as i32,
+                features["mode"].as_i64().unwrap_or(-1) as i32,
+            ),
+            energy: feature("energy"),
+            danceability: feature("danceability"),
+            valence: feature("valence"),
+            acousticness: feature("acousticness"),
+            instrumentalness: feature("instrumentalness"),
+        })
+    }
+}
+
+/// A Spotify track joined with the YouTube video that plays it: what the
+/// player needs in one object. Every field is always serialized, `null`
+/// when that half hasn't been resolved, so clients see a single shape.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnifiedTrack {
+    pub spotify_id: String,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub image_url: Option<String>,
+    pub duration_ms: i64,
+    pub explicit: bool,
+    pub popularity: i64,
+    pub preview_url: Option<String>,
+    pub features: Option<TrackFeatures>,
+    pub video_id: Option<String>,
+    pub video_title: Option<String>,
+    pub video_duration_ms: Option<i64>,
+    pub stream_url: Option<String>,
+}
+
+impl UnifiedTrack {
+    pub fn builder(spotify: SearchResult) -> UnifiedTrackBuilder {
+        UnifiedTrackBuilder {
+            track: Self {
+                spotify_id: spotify.id,
+                title: spotify.title,
+                artists: spotify.artists,
+                album: spotify.album,
+                image_url: spotify.image_url,
+                duration_ms: spotify.duration_ms,
+                explicit: spotify.explicit,
+                popularity: spotify.popularity,
+                preview_url: spotify.preview_url,
+                features: None,
+                video_id: None,
+                video_title: None,
+                video_duration_ms: None,
+                stream_url: None,
+            },
+        }
+    }
+
+    /// Start from a stored mix track, whose features were captured when the
+    /// mix was generated. Tempo, artwork and the explicit flag aren't stored.
+    pub fn from_mix_track(track: &MixTrack) -> UnifiedTrackBuilder {
+        UnifiedTrackBuilder {
+            track: Self {
+                spotify_id: track.spotify_id.clone(),
+                title: track.title.clone(),
+                artists: track.artist.split(", ").map(str::to_string).collect(),
+                album: track.album.clone(),
+                image_url: None,
+                duration_ms: track.duration_ms as i64,
+                explicit: false,
+                popularity: track.popularity as i64,
+                preview_url: None,
+                features: Some(TrackFeatures {
+                    tempo: None,
+                    key: Some(track.key.clone()),
+                    energy: Some(track.energy),
+                    danceability: Some(track.danceability),
+                    valence: Some(track.valence),
+                    acousticness: Some(track.acousticness),
+                    instrumentalness: Some(track.instrumentalness),
+                }),
+                video_id: None,
+                video_title: None,
+                video_duration_ms: None,
+                stream_url: None,
+            },
+        }
+    }
+}
+
+/// Fills a `UnifiedTrack` in from each source as it's resolved
+#[derive(Debug)]
+pub struct UnifiedTrackBuilder {
+    track: UnifiedTrack,
+}
+
+impl UnifiedTrackBuilder {
+    /// Raw Spotify audio features; ignored when `None` or `null`
+    pub fn audio_features(mut self, features: Option<&serde_json::Value>) -> Self {
+        self.track.features = features.and_then(TrackFeatures::from_spotify);
+        self
+    }
+
+    /// The YouTube video matched to the track
+    pub fn youtube(mut self, video: &TrackValueResponse) -> Self {
+        self.track.video_id = Some(video.video_id.clone());
+        self.track.video_title = Some(video.title.clone());
+        self.track.video_duration_ms = video.duration_ms;
+        self
+    }
+
+    /// A resolved stream. It is dropped if it belongs to a different video
+    /// than the one matched, since it would play the wrong audio.
+    pub fn stream(mut self, stream: StreamInfo) -> Self {
+        match &self.track.video_id {
+            Some(video_id) if *video_id != stream.id => {}
+            _ => {
+                self.track.video_id = Some(stream.id);
+                self.track.stream_url = Some(stream.stream_url);
+            }
+        }
+        self
+    }
+
+    pub fn build(self) -> UnifiedTrack {
+        self.track
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spotify_track() -> SearchResult {
+        SearchResult {
+            id: "4uLU6hMCjMI75M1A2tKUQC".to_string(),
+            title: "Strobe".to_string(),
+            artists: vec!["deadmau5".to_string()],
+            album: "For Lack of a Better Name".to_string(),
+            image_url: None,
+            duration_ms: 634_000,
+            preview_url: None,
+            explicit: false,
+            popularity: 70,
+        }
+    }
+
+    fn video(id: &str) -> TrackValueResponse {
+        TrackValueResponse {
+            video_id: id.to_string(),
+            title: "deadmau5 - Strobe".to_string(),
+            channel_title: "deadmau5".to_string(),
+            thumbnail_url: None,
+            duration_ms: Some(633_000),
+        }
+    }
+
+    #[test]
+    fn merges_both_sources() {
+        let features = serde_json::json!({"tempo": 128.0, "key": 9, "mode": 1, "energy": 0.7});
+        let track = UnifiedTrack::builder(spotify_track())
+            .audio_features(Some(&features))
+            .youtube(&video("tKi9Z-f6qX4"))
+            .stream(StreamInfo {
+                id: "tKi9Z-f6qX4".to_string(),
+                stream_url: "https://example.com/audio".to_string(),
+            })
+            .build();
+
+        assert_eq!(track.spotify_id, "4uLU6hMCjMI75M1A2tKUQC");
+        assert_eq!(track.video_id.as_deref(), Some("tKi9Z-f6qX4"));
+        assert_eq!(track.video_duration_ms, Some(633_000));
+        assert_eq!(track.stream_url.as_deref(), Some("https://example.com/audio"));
+        let features = track.features.unwrap();
+        assert_eq!(features.tempo, Some(128.0));
+        assert_eq!(features.key.as_deref(), Some("11B"));
+        assert_eq!(features.danceability, None);
+    }
+
+    #[test]
+    fn ignores_a_stream_for_another_video() {
+        let track = UnifiedTrack::builder(spotify_track())
+            .youtube(&video("tKi9Z-f6qX4"))
+            .stream(StreamInfo {
+                id: "other".to_string(),
+                stream_url: "https://example.com/audio".to_string(),
+            })
+            .build();
+        assert_eq!(track.video_id.as_deref(), Some("tKi9Z-f6qX4"));
+        assert_eq!(track.stream_url, None);
+    }
+
+    #[test]
+    fn unresolved_fields_serialize_as_null() {
+        let track = UnifiedTrack::builder(spotify_track())
+            .audio_features(Some(&serde_json::Value::Null))
+            .build();
+        let json = serde_json::to_value(&track).unwrap();
+        assert!(json["features"].is_null());
+        assert!(json["video_id"].is_null());
+        assert!(json.get("stream_url").is_some_and(|s| s.is_null()));
+    }
+}
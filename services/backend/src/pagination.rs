@@ -0,0 +1,85 @@
+// Shared limit/offset pagination
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::Deserialize;
+
+use crate::error::ApiError;
+
+/// Page size used when `limit` is omitted
+pub const DEFAULT_LIMIT: i64 = 20;
+/// Largest page any list endpoint will return
+pub const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+struct PaginationQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// `?limit=&offset=` query params, validated and clamped.
+///
+/// `limit` defaults to 20 and is capped at 100; negative values are
+/// rejected with a 400 rather than silently clamped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Pagination {
+    fn from_query(query: PaginationQuery) -> Result<Self, ApiError> {
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        let offset = query.offset.unwrap_or(0);
+        if limit < 0 {
+            return Err(ApiError::bad_request("limit must not be negative"));
+        }
+        if offset < 0 {
+            return Err(ApiError::bad_request("offset must not be negative"));
+        }
+
+        Ok(Self {
+            limit: limit.clamp(1, MAX_LIMIT),
+            offset,
+        })
+    }
+
+    /// Tighten the limit for upstreams with a smaller page size (e.g. Spotify's 50)
+    pub fn capped(self, max: i64) -> Self {
+        Self {
+            limit: self.limit.min(max),
+            ..self
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Pagination {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<PaginationQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::bad_request("limit and offset must be integers"))?;
+        Self::from_query(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paginate(limit: Option<i64>, offset: Option<i64>) -> Result<Pagination, ApiError> {
+        Pagination::from_query(PaginationQuery { limit, offset })
+    }
+
+    #[test]
+    fn defaults_clamps_and_rejects_negatives() {
+        assert_eq!(paginate(None, None).unwrap(), Pagination { limit: 20, offset: 0 });
+        assert_eq!(paginate(Some(500), Some(40)).unwrap(), Pagination { limit: 100, offset: 40 });
+        assert_eq!(paginate(Some(0), None).unwrap().limit, 1);
+        assert_eq!(paginate(Some(80), None).unwrap().capped(50).limit, 50);
+        assert!(paginate(Some(-1), None).is_err());
+        assert!(paginate(None, Some(-5)).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+// Mix planning: order tracks by harmonic compatibility and an energy arc
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::camelot;
+use crate::models::mix::{CreateTrackRequest, CreateTransitionRequest, TransitionDirection, TransitionType};
+
+/// Energy change between tracks that calls for something other than a crossfade
+const ENERGY_JUMP: f64 = 0.3;
+
+/// Relative weight of key compatibility vs. fitting the energy curve
+const KEY_WEIGHT: f64 = 0.4;
+const ENERGY_WEIGHT: f64 = 0.6;
+
+/// Largest distance `camelot::distance` returns for valid keys
+const MAX_KEY_DISTANCE: f64 = 6.0;
+
+/// Shape of the set's energy over time
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyCurve {
+    /// Steady build from warm-up to high energy
+    Ascending,
+    /// Build to a peak halfway through, then cool down
+    Peak,
+    /// Two build/release cycles
+    Wave,
+}
+
+impl EnergyCurve {
+    /// Target energy (0.0-1.0) at `position` in a set of `len` tracks
+    pub fn target(&self, position: usize, len: usize) -> f64 {
+        let t = if len > 1 {
+            position as f64 / (len - 1) as f64
+        } else {
+            0.0
+        };
+
+        match self {
+            EnergyCurve::Ascending => 0.3 + 0.6 * t,
+            EnergyCurve::Peak => 0.3 + 0.6 * (1.0 - (2.0 * t - 1.0).abs()),
+            EnergyCurve::Wave => 0.6 - 0.25 * (4.0 * PI * t).cos(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlanMixRequest {
+    pub tracks: Vec<CreateTrackRequest>,
+    /// Without a curve, tracks are ordered purely by key compatibility
+    #[serde(default)]
+    pub energy_curve: Option<EnergyCurve>,
+    /// Flat energy target used when no curve is given
+    #[serde(default)]
+    pub target_energy: Option<f64>,
+    /// Session whose profile supplies `target_energy` when it isn't set
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnergyDiagnostic {
+    pub position: usize,
+    pub spotify_id: String,
+    pub target_energy: f64,
+    pub actual_energy: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MixPlan {
+    /// Tracks in play order, with `track_order` renumbered to match
+    pub tracks: Vec<CreateTrackRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub energy_curve: Option<EnergyCurve>,
+    /// Per-position target vs. actual energy; empty without an energy target
+    pub energy: Vec<EnergyDiagnostic>,
+}
+
+/// Lower is better: key distance from the previous track plus how far the
+/// track's energy is from the curve's target at this position
+fn score(previous: Option<&CreateTrackRequest>, track: &CreateTrackRequest, target: Option<f64>) -> f64 {
+    let key = previous
+        .map(|p| camelot::distance(&p.key, &track.key).min(MAX_KEY_DISTANCE as u32) as f64 / MAX_KEY_DISTANCE)
+        .unwrap_or(0.0);
+
+    match target {
+        Some(target) => KEY_WEIGHT * key + ENERGY_WEIGHT * (track.energy - target).abs(),
+        None => key,
+    }
+}
+
+/// Greedily pick the best-scoring remaining track for each position
+pub fn plan_mix(request: PlanMixRequest) -> MixPlan {
+    let PlanMixRequest { tracks: mut remaining, energy_curve, target_energy, .. } = request;
+    let len = remaining.len();
+    let mut ordered: Vec<CreateTrackRequest> = Vec::with_capacity(len);
+    let mut energy = Vec::new();
+
+    for position in 0..len {
+        let target = energy_curve
+            .map(|curve| curve.target(position, len))
+            .or(target_energy);
+
+        // With no curve, keep the caller's opening track
+        let best = if target.is_none() && position == 0 {
+            0
+        } else {
+            remaining
+                .iter()
+                .enumerate()
+                .map(|(i, track)| (i, score(ordered.last(), track, target)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(i, _)| i)
+                .unwrap_or(0)
+        };
+
+        let mut track = remaining.remove(best);
+        track.track_order = position as i32;
+
+        if let Some(target) = target {
+            energy.push(EnergyDiagnostic {
+                position,
+                spotify_id: track.spotify_id.clone(),
+                target_energy: target,
+                actual_energy: track.energy,
+            });
+        }
+        ordered.push(track);
+    }
+
+    MixPlan { tracks: ordered, energy_curve, energy }
+}
+
+/// Pick the transition from track `from_order` into the next one, using the
+/// same rules as the orchestrator's rule-based fallback: echo out of a big
+/// energy drop, filter sweep into a big build, a backspin into the final
+/// track, and a crossfade otherwise
+pub fn suggest_transition(
+    from_order: i32,
+    from_energy: f64,
+    to_energy: f64,
+    into_final_track: bool,
+) -> CreateTransitionRequest {
+    let delta = to_energy - from_energy;
+    let (transition_type, bars, direction) = if delta < -ENERGY_JUMP {
+        (TransitionType::EchoOut, 4, None)
+    } else if delta > ENERGY_JUMP {
+        (TransitionType::FilterSweep, 8, Some(TransitionDirection::Up))
+    } else if into_final_track {
+        (TransitionType::Backspin, 2, None)
+    } else {
+        (TransitionType::Crossfade, 8, None)
+    };
+
+    CreateTransitionRequest {
+        from_track_order: from_order,
+        to_track_order: from_order + 1,
+        transition_type: transition_type.as_str().to_string(),
+        transition_bars: bars,
+        transition_direction: direction.and_then(TransitionDirection::filter).map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: &str, key: &str, energy: f64) -> CreateTrackRequest {
+        CreateTrackRequest {
+            spotify_id: id.to_string(),
+            title: id.to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            duration_ms: 180_000,
+            key: key.to_string(),
+            energy,
+            danceability: 0.5,
+            valence: 0.5,
+            acousticness: 0.1,
+            instrumentalness: 0.0,
+            popularity: 50,
+            track_order: 0,
+        }
+    }
+
+    fn ids(plan: &MixPlan) -> Vec<&str> {
+        plan.tracks.iter().map(|t| t.spotify_id.as_str()).collect()
+    }
+
+    #[test]
+    fn ascending_curve_builds_energy() {
+        let plan = plan_mix(PlanMixRequest {
+            tracks: vec![track("high", "8A", 0.9), track("low", "8A", 0.3), track("mid", "8A", 0.6)],
+            energy_curve: Some(EnergyCurve::Ascending),
+            target_energy: None,
+            session_id: None,
+        });
+
+        assert_eq!(ids(&plan), vec!["low", "mid", "high"]);
+        assert_eq!(plan.energy.len(), 3);
+        assert_eq!(plan.tracks[2].track_order, 2);
+    }
+
+    #[test]
+    fn peak_curve_puts_highest_energy_in_the_middle() {
+        let plan = plan_mix(PlanMixRequest {
+            tracks: vec![track("a", "8A", 0.9), track("b", "8A", 0.3), track("c", "8A", 0.35)],
+            energy_curve: Some(EnergyCurve::Peak),
+            target_energy: None,
+            session_id: None,
+        });
+
+        assert_eq!(plan.tracks[1].spotify_id, "a");
+    }
+
+    #[test]
+    fn without_a_curve_orders_by_key_compatibility() {
+        let plan = plan_mix(PlanMixRequest {
+            tracks: vec![track("start", "8A", 0.5), track("far", "2A", 0.5), track("near", "9A", 0.5)],
+            energy_curve: None,
+            target_energy: None,
+            session_id: None,
+        });
+
+        assert_eq!(ids(&plan), vec!["start", "near", "far"]);
+        assert!(plan.energy.is_empty());
+    }
+
+    #[test]
+    fn suggests_transitions_from_energy_changes() {
+        let drop = suggest_transition(2, 0.9, 0.4, false);
+        assert_eq!((drop.transition_type.as_str(), drop.transition_bars), ("echo_out", 4));
+        assert_eq!((drop.from_track_order, drop.to_track_order), (2, 3));
+
+        let build = suggest_transition(0, 0.3, 0.8, false);
+        assert_eq!(build.transition_type, "filter_sweep");
+        assert_eq!(build.transition_direction.as_deref(), Some("highpass"));
+
+        assert_eq!(suggest_transition(0, 0.5, 0.6, true).transition_type, "backspin");
+        assert_eq!(suggest_transition(0, 0.5, 0.6, false).transition_type, "crossfade");
+    }
+
+    #[test]
+    fn curves_stay_within_energy_range() {
+        for curve in [EnergyCurve::Ascending, EnergyCurve::Peak, EnergyCurve::Wave] {
+            for position in 0..10 {
+                let target = curve.target(position, 10);
+                assert!((0.0..=1.0).contains(&target), "{:?} at {} = {}", curve, position, target);
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Warm the stream cache for a mix before playback
+use std::sync::Arc;
+use futures_util::future::join_all;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::audio_source::AudioSource;
+use crate::controllers::song::SONG_CONTROLLER;
+use crate::models::mix::MixTrack;
+use crate::models::track::UnifiedTrack;
+
+/// yt-dlp extractions run at once for one prefetch; each is a subprocess
+const PREFETCH_CONCURRENCY: usize = 4;
+
+/// Whether one mix track's stream is ready to play
+#[derive(Debug, Serialize)]
+pub struct StreamPrefetch {
+    pub track_order: i32,
+    pub spotify_id: String,
+    pub ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<UnifiedTrack>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// YouTube search for a stored track: lead artist and title, like
+/// `resolve_track_to_youtube` builds from Spotify
+fn prefetch_query(track: &MixTrack) -> String {
+    match track.artist.split(", ").next().filter(|a| !a.is_empty()) {
+        Some(artist) => format!("{} - {}", artist, track.title),
+        None => track.title.clone(),
+    }
+}
+
+/// Match each track to a YouTube video and resolve its stream, which leaves
+/// the stream URL cached for the player. Runs concurrently (bounded) and keeps
+/// the input order; a failed track is reported in its slot.
+pub async fn prefetch_streams(tracks: &[MixTrack]) -> Vec<StreamPrefetch> {
+    let semaphore = Arc::new(Semaphore::new(PREFETCH_CONCURRENCY));
+
+    join_all(tracks.iter().map(|track| {
+        let semaphore = semaphore.clone();
+        async move {
+            let query = prefetch_query(track);
+            let resolved = async {
+                let _permit = semaphore.acquire().await?;
+                let video = SONG_CONTROLLER
+                    .resolve_best_match(&query, track.duration_ms as i64)
+                    .await?;
+                let stream = SONG_CONTROLLER.resolve_stream(&video.video_id).await?;
+                anyhow::Ok(UnifiedTrack::from_mix_track(track).youtube(&video).stream(stream).build())
+            }
+            .await;
+
+            match resolved {
+                Ok(unified) => StreamPrefetch {
+                    track_order: track.track_order,
+                    spotify_id: track.spotify_id.clone(),
+                    ready: true,
+                    track: Some(unified),
+                    error: None,
+                },
+                Err(e) => {
+                    warn!("Failed to prefetch stream for '{}': {}", query, e);
+                    StreamPrefetch {
+                        track_order: track.track_order,
+                        spotify_id: track.spotify_id.clone(),
+                        ready: false,
+                        track: None,
+                        error: Some(e.to_string()),
+                    }
+                }
+            }
+        }
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::mix::tests::mix_track;
+
+    fn track(artist: &str, title: &str) -> MixTrack {
+        MixTrack {
+            artist: artist.to_string(),
+            ..mix_track(0, title)
+        }
+    }
+
+    #[test]
+    fn queries_with_the_lead_artist() {
+        assert_eq!(prefetch_query(&track("Daft Punk, Romanthony", "One More Time")), "Daft Punk - One More Time");
+        assert_eq!(prefetch_query(&track("", "One More Time")), "One More Time");
+    }
+
+    #[test]
+    fn mix_tracks_carry_their_stored_features() {
+        let unified = UnifiedTrack::from_mix_track(&track("Daft Punk, Romanthony", "One More Time")).build();
+        assert_eq!(unified.artists, ["Daft Punk", "Romanthony"]);
+        assert_eq!(unified.features.and_then(|f| f.key).as_deref(), Some("8A"));
+        assert_eq!(unified.stream_url, None);
+    }
+}
@@ -0,0 +1,541 @@
+// Mix progress messages shared by the WebSocket and SSE transports
+use futures_util::{Stream, StreamExt};
+use redis::AsyncCommands;
+use redis::aio::{PubSubSink, PubSubStream};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::db::{Database, STALE_GENERATION_MESSAGE};
+use crate::generations::GENERATIONS;
+use crate::models::mix::MixSession;
+use crate::redis_keys;
+use crate::redis_pool::{self, RedisPool};
+use crate::secrets::SECRET_MANAGER;
+use crate::webhooks;
+
+/// How long sequence numbers and snapshots outlive the last message
+const SNAPSHOT_TTL_SECS: u64 = 60 * 60 * 24;
+
+/// How often live streams check their pub/sub connection still answers
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most recent messages kept per session for `replay`
+const HISTORY_MAX_EVENTS: i64 = 500;
+
+/// How long a message's hash maps to its sequence number. Long enough for
+/// the recorder and every stream to see one publish, short enough that the
+/// orchestrator repeating an identical payload later is a new event.
+const EVENT_DEDUPE_TTL_SECS: u64 = 10;
+
+/// Assign the next sequence number to a message, store it as the session's
+/// snapshot and append it to the session's history. Keyed on a hash of the
+/// message (for `EVENT_DEDUPE_TTL_SECS`), so the recorder and every SSE
+/// stream (on any replica) agree on the id of a given publish and it's only
+/// appended once.
+///
+/// A missing counter (a new session, or Redis lost its data) returns -1
+/// unless ARGV[5] gives the number to resume after, so numbering never
+/// restarts below what's already in `dj_mix_progress`.
+const SEQUENCE_SCRIPT: &str = r#"
+local existing = redis.call('GET', KEYS[1])
+if existing then return tonumber(existing) end
+if redis.call('EXISTS', KEYS[2]) == 0 then
+  if ARGV[5] == '' then return -1 end
+  redis.call('SET', KEYS[2], ARGV[5])
+end
+local seq = redis.call('INCR', KEYS[2])
+redis.call('EXPIRE', KEYS[2], ARGV[3])
+redis.call('SET', KEYS[1], seq, 'EX', ARGV[6])
+redis.call('HSET', KEYS[3], 'id', seq, 'channel', ARGV[1], 'payload', ARGV[2])
+redis.call('EXPIRE', KEYS[3], ARGV[3])
+redis.call('RPUSH', KEYS[4], cjson.encode({id = seq, channel = ARGV[1], payload = ARGV[2]}))
+redis.call('LTRIM', KEYS[4], -tonumber(ARGV[4]), -1)
+redis.call('EXPIRE', KEYS[4], ARGV[3])
+return seq
+"#;
+
+/// A message published for a session, with its sequence number
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Snapshot {
+    pub id: u64,
+    pub channel: String,
+    pub payload: String,
+}
+
+/// Determine the message type from a `mix:{id}:{kind}` Redis channel name
+pub fn message_type_for_channel(channel: &str) -> Option<&'static str> {
+    if channel.ends_with(":complete") {
+        Some("complete")
+    } else if channel.ends_with(":error") {
+        Some("error")
+    } else if channel.ends_with(":progress") {
+        Some("progress")
+    } else {
+        None
+    }
+}
+
+/// Wrap a Redis payload in the `{"type": ..., "data": ...}` envelope.
+///
+/// Payloads that are not valid JSON are wrapped as `{"raw": payload}` so the
+/// output is always a valid JSON document.
+pub fn format_progress_message(channel: &str, payload: &str) -> String {
+    let message_type = message_type_for_channel(channel).unwrap_or("progress");
+
+    let data = match serde_json::from_str::<serde_json::Value>(payload) {
+        Ok(data) => data,
+        Err(_) => serde_json::json!({"raw": payload}),
+    };
+
+    serde_json::json!({
+        "type": message_type,
+        "data": data
+    })
+    .to_string()
+}
+
+/// Session id from a `mix:{id}:{kind}` channel name (under `REDIS_PREFIX`)
+pub fn session_id_for_channel(channel: &str) -> Option<&str> {
+    let rest = redis_keys::unprefixed(channel).strip_prefix("mix:")?;
+    let (session_id, _kind) = rest.rsplit_once(':')?;
+    Some(session_id)
+}
+
+/// Sequence a published message, returning its event id. When the session
+/// has no counter in Redis it's resumed from the highest `seq` logged in
+/// Postgres.
+pub async fn sequence_message(
+    database: &Database,
+    redis: &RedisPool,
+    session_id: &str,
+    channel: &str,
+    payload: &str,
+) -> redis::RedisResult<u64> {
+    let digest = Sha256::digest(format!("{}\n{}", channel, payload).as_bytes());
+    let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    let mut conn = redis.get().await?;
+
+    let script = redis::Script::new(SEQUENCE_SCRIPT);
+    let invoke = |floor: Option<i64>| {
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(redis_keys::key(&format!("mix:{}:event:{}", session_id, hash)))
+            .key(redis_keys::key(&format!("mix:{}:seq", session_id)))
+            .key(redis_keys::key(&format!("mix:{}:snapshot", session_id)))
+            .key(redis_keys::key(&format!("mix:{}:history", session_id)))
+            .arg(channel)
+            .arg(payload)
+            .arg(SNAPSHOT_TTL_SECS)
+            .arg(HISTORY_MAX_EVENTS)
+            .arg(floor.map(|f| f.to_string()).unwrap_or_default())
+            .arg(EVENT_DEDUPE_TTL_SECS);
+        invocation
+    };
+
+    let seq: i64 = invoke(None).invoke_async(&mut conn).await?;
+    if let Ok(seq) = u64::try_from(seq) {
+        return Ok(seq);
+    }
+
+    let floor = match Uuid::parse_str(session_id) {
+        Ok(session_uuid) => database.max_progress_seq(session_uuid).await.unwrap_or_else(|e| {
+            warn!("Failed to read logged progress for {}: {}", session_id, e);
+            None
+        }),
+        Err(_) => None,
+    };
+    let seq: i64 = invoke(Some(floor.unwrap_or(0))).invoke_async(&mut conn).await?;
+    Ok(seq.max(0) as u64)
+}
+
+/// The session's latest message, if any was recorded
+pub async fn latest_snapshot(redis: &RedisPool, session_id: &str) -> redis::RedisResult<Option<Snapshot>> {
+    let mut conn = redis.get().await?;
+    let fields: std::collections::HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(redis_keys::key(&format!("mix:{}:snapshot", session_id)))
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(Snapshot::from_fields(&fields))
+}
+
+/// Subscribe to a session's progress channels. Returns the pub/sub sink (for
+/// health checks) and its message stream.
+pub async fn subscribe_session(session_id: &str) -> redis::RedisResult<(PubSubSink, PubSubStream)> {
+    let (mut sink, stream) = redis_pool::pubsub().await?.split();
+    for kind in ["progress", "complete", "error"] {
+        sink.subscribe(redis_keys::mix_channel(session_id, kind)).await?;
+    }
+    Ok((sink, stream))
+}
+
+/// Whether the pub/sub connection answers a PING in time. A restarted Redis
+/// can leave the message stream open but silent, so this is the only sign.
+pub async fn is_alive(sink: &mut PubSubSink) -> bool {
+    matches!(
+        tokio::time::timeout(HEALTH_CHECK_TIMEOUT, sink.ping::<redis::Value>()).await,
+        Ok(Ok(_))
+    )
+}
+
+/// How often streams poll Postgres while Redis pub/sub is unavailable
+pub fn poll_interval() -> Duration {
+    let secs = SECRET_MANAGER.get("PROGRESS_POLL_INTERVAL_SECS").parse::<u64>().unwrap_or(3);
+    Duration::from_secs(secs.max(1))
+}
+
+/// Tells the client its stream is now polled and less granular
+pub fn polling_notice() -> String {
+    serde_json::json!({"type": "polling", "interval_secs": poll_interval().as_secs()}).to_string()
+}
+
+/// A progress envelope synthesized from the session's row, with its type.
+/// Payloads carry `"polled": true` since there's no stage or percentage.
+///
+/// A session is `completed` once its tracklist is saved, before the audio is
+/// rendered, so it's only `complete` once it has a `cdn_url`.
+pub fn polled_message(session: &MixSession) -> (&'static str, String) {
+    let (message_type, mut data) = match session.status.as_str() {
+        "completed" => match &session.cdn_url {
+            Some(cdn_url) => ("complete", serde_json::json!({"cdn_url": cdn_url})),
+            None => ("progress", serde_json::json!({"stage": "rendering"})),
+        },
+        "error" => (
+            "error",
+            serde_json::json!({"error": session.error_message.as_deref().unwrap_or("Unknown error")}),
+        ),
+        "cancelled" => ("error", serde_json::json!({"error": "Mix generation was cancelled"})),
+        status => ("progress", serde_json::json!({"stage": status})),
+    };
+    data["session_id"] = serde_json::json!(session.id);
+    data["polled"] = serde_json::json!(true);
+
+    let message = serde_json::json!({"type": message_type, "data": data}).to_string();
+    (message_type, message)
+}
+
+/// Progress for a session polled from Postgres, for when Redis is down. Each
+/// status change yields one envelope; the stream ends after `complete` or `error`.
+pub fn poll_session(database: Database, session_id: Uuid) -> impl Stream<Item = String> {
+    async_stream::stream! {
+        let mut interval = tokio::time::interval(poll_interval());
+        let mut last_sent: Option<String> = None;
+        loop {
+            interval.tick().await;
+            let session = match database.get_mix_session(session_id).await {
+                Ok(Some(session)) => session,
+                Ok(None) => {
+                    yield serde_json::json!({"type": "error", "data": {"error": "Mix session not found"}}).to_string();
+                    return;
+                }
+                Err(e) => {
+                    warn!("Failed to poll mix session {}: {}", session_id, e);
+                    continue;
+                }
+            };
+
+            let (message_type, message) = polled_message(&session);
+            if last_sent.as_ref() != Some(&message) {
+                last_sent = Some(message.clone());
+                yield message;
+            }
+            if message_type != "progress" {
+                return;
+            }
+        }
+    }
+}
+
+/// Delay before reconnect `attempt` (1-based): 1s, doubling up to 30s
+pub fn reconnect_backoff(attempt: u32) -> Duration {
+    let secs = 1u64 << attempt.saturating_sub(1).min(5);
+    Duration::from_secs(secs.min(30))
+}
+
+/// Every recorded message for the session, oldest first
+pub async fn history(redis: &RedisPool, session_id: &str) -> redis::RedisResult<Vec<Snapshot>> {
+    let mut conn = redis.get().await?;
+    let entries: Vec<String> = redis::cmd("LRANGE")
+        .arg(redis_keys::key(&format!("mix:{}:history", session_id)))
+        .arg(0)
+        .arg(-1)
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| serde_json::from_str(entry).ok())
+        .collect())
+}
+
+/// `{"type": "replay", "events": [...]}`, each event being the usual
+/// envelope plus its `id`
+pub fn format_replay_message(events: &[Snapshot]) -> String {
+    let events: Vec<serde_json::Value> = events
+        .iter()
+        .map(|event| {
+            let mut message: serde_json::Value =
+                serde_json::from_str(&format_progress_message(&event.channel, &event.payload))
+                    .unwrap_or_default();
+            message["id"] = event.id.into();
+            message
+        })
+        .collect();
+
+    serde_json::json!({"type": "replay", "events": events}).to_string()
+}
+
+impl Snapshot {
+    fn from_fields(fields: &std::collections::HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            id: fields.get("id")?.parse().ok()?,
+            channel: fields.get("channel")?.clone(),
+            payload: fields.get("payload")?.clone(),
+        })
+    }
+}
+
+/// Stage, percent and message to log for a published message. Progress
+/// payloads are `{"stage", "progress", "detail"}`; completions and errors are
+/// logged under their own stage.
+pub fn progress_log_fields(channel: &str, payload: &str) -> Option<(String, Option<i32>, Option<String>)> {
+    let message_type = message_type_for_channel(channel)?;
+    let data = serde_json::from_str::<serde_json::Value>(payload).unwrap_or_default();
+    let text = |key: &str| data.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+    Some(match message_type {
+        "progress" => (
+            text("stage").unwrap_or_else(|| "progress".to_string()),
+            data.get("progress").and_then(|p| p.as_i64()).map(|p| p as i32),
+            text("detail"),
+        ),
+        "complete" => ("complete".to_string(), Some(100), None),
+        _ => ("error".to_string(), None, text("error").or_else(|| Some(payload.to_string()))),
+    })
+}
+
+/// Record every session's progress in Redis, so streams that reconnect (or
+/// connect late) can be sent the current state even if no client was
+/// listening when it was published, and log it to Postgres as a durable
+/// trail. Reconnects with a delay on failure.
+pub fn spawn_progress_recorder(database: Database, redis: RedisPool) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = record_progress(&database, &redis).await {
+                warn!("Progress recorder disconnected: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    });
+}
+
+/// Fail sessions stuck in `generating` past `MIX_GENERATION_TIMEOUT_SECS`,
+/// e.g. because the orchestrator died mid-mix, and publish an error for each
+/// so connected streams close and the recorder cleans up after them
+pub fn spawn_stale_session_sweeper(database: Database, redis: RedisPool) {
+    let max_age = Duration::from_secs(
+        SECRET_MANAGER
+            .get("MIX_GENERATION_TIMEOUT_SECS")
+            .parse::<u64>()
+            .unwrap_or(900),
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweep_stale_sessions(&database, &redis, max_age).await {
+                warn!("Stale session sweep failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn sweep_stale_sessions(database: &Database, redis: &RedisPool, max_age: Duration) -> Result<(), String> {
+    let expired = database
+        .expire_stale_generating(max_age)
+        .await
+        .map_err(|e| e.to_string())?;
+    if expired.is_empty() {
+        return Ok(());
+    }
+    warn!("Marked {} stuck mix sessions as timed out", expired.len());
+
+    let mut conn = redis.get().await.map_err(|e| e.to_string())?;
+    let payload = serde_json::json!({ "error": STALE_GENERATION_MESSAGE }).to_string();
+    for session_id in expired {
+        let channel = redis_keys::mix_channel(&session_id.to_string(), "error");
+        if let Err(e) = conn.publish::<_, _, ()>(&channel, &payload).await {
+            warn!("Failed to publish timeout for {}: {}", session_id, e);
+        }
+    }
+    Ok(())
+}
+
+async fn record_progress(database: &Database, redis: &RedisPool) -> redis::RedisResult<()> {
+    let mut pubsub = redis_pool::pubsub().await?;
+    for kind in ["progress", "complete", "error"] {
+        pubsub.psubscribe(redis_keys::mix_channel("*", kind)).await?;
+    }
+    info!("Recording mix progress snapshots");
+
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let channel = msg.get_channel_name().to_string();
+        let (Some(session_id), Ok(payload)) = (session_id_for_channel(&channel), msg.get_payload::<String>()) else {
+            continue;
+        };
+        let seq = match sequence_message(database, redis, session_id, &channel, &payload).await {
+            Ok(seq) => seq,
+            Err(e) => {
+                warn!("Failed to record progress for {}: {}", session_id, e);
+                continue;
+            }
+        };
+
+        if let Some(event @ ("complete" | "error")) = message_type_for_channel(&channel)
+            && let Ok(session_uuid) = Uuid::parse_str(session_id) {
+            GENERATIONS.finish(&session_uuid);
+            webhooks::notify(database.clone(), session_uuid, event, payload.clone());
+        }
+
+        if let (Ok(session_uuid), Some((stage, percent, message))) =
+            (Uuid::parse_str(session_id), progress_log_fields(&channel, &payload))
+            && let Err(e) = database
+                .append_progress(session_uuid, seq as i64, &stage, percent, message.as_deref())
+                .await
+        {
+            warn!("Failed to log progress for {}: {}", session_id, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_valid_json_payload() {
+        let message = format_progress_message("mix:abc:progress", r#"{"percent": 40}"#);
+        let parsed: serde_json::Value = serde_json::from_str(&message).unwrap();
+
+        assert_eq!(parsed["type"], "progress");
+        assert_eq!(parsed["data"]["percent"], 40);
+    }
+
+    #[test]
+    fn wraps_invalid_json_payload_as_raw() {
+        let message = format_progress_message("mix:abc:error", "not json {");
+        let parsed: serde_json::Value = serde_json::from_str(&message).unwrap();
+
+        assert_eq!(parsed["type"], "error");
+        assert_eq!(parsed["data"]["raw"], "not json {");
+    }
+
+    #[test]
+    fn output_is_identical_for_identical_input() {
+        let a = format_progress_message("mix:abc:complete", r#"{"cdn_url": "https://x"}"#);
+        let b = format_progress_message("mix:abc:complete", r#"{"cdn_url": "https://x"}"#);
+
+        assert_eq!(a, b);
+        assert_eq!(a, r#"{"data":{"cdn_url":"https://x"},"type":"complete"}"#);
+    }
+
+    #[test]
+    fn extracts_session_id_from_channel() {
+        assert_eq!(session_id_for_channel("mix:abc-123:progress"), Some("abc-123"));
+        assert_eq!(session_id_for_channel("playback:abc"), None);
+    }
+
+    #[test]
+    fn replay_lists_events_in_order_with_ids() {
+        // As stored by SEQUENCE_SCRIPT
+        let stored = [
+            r#"{"id":1,"channel":"mix:abc:progress","payload":"{\"percent\":10}"}"#,
+            r#"{"id":2,"channel":"mix:abc:complete","payload":"done"}"#,
+        ];
+        let events: Vec<Snapshot> = stored.iter().map(|e| serde_json::from_str(e).unwrap()).collect();
+
+        let parsed: serde_json::Value = serde_json::from_str(&format_replay_message(&events)).unwrap();
+        assert_eq!(parsed["type"], "replay");
+        assert_eq!(parsed["events"][0]["id"], 1);
+        assert_eq!(parsed["events"][0]["data"]["percent"], 10);
+        assert_eq!(parsed["events"][1]["type"], "complete");
+        assert_eq!(parsed["events"][1]["data"]["raw"], "done");
+    }
+
+    #[test]
+    fn synthesizes_messages_from_session_status() {
+        let mut session = MixSession {
+            id: Uuid::nil(),
+            prompt: "deep house".to_string(),
+            status: "generating".to_string(),
+            created_at: chrono::Utc::now(),
+            completed_at: None,
+            error_message: None,
+            estimated_duration_minutes: None,
+            cdn_url: None,
+            user_id: None,
+            parent_session_id: None,
+        };
+        let parse = |message: String| serde_json::from_str::<serde_json::Value>(&message).unwrap();
+
+        let (kind, message) = polled_message(&session);
+        assert_eq!(kind, "progress");
+        let message = parse(message);
+        assert_eq!(message["data"]["stage"], "generating");
+        assert_eq!(message["data"]["polled"], true);
+
+        session.status = "completed".to_string();
+        let (kind, message) = polled_message(&session);
+        assert_eq!(kind, "progress");
+        assert_eq!(parse(message)["data"]["stage"], "rendering");
+
+        session.cdn_url = Some("https://cdn.example/mix.mp3".to_string());
+        let (kind, message) = polled_message(&session);
+        assert_eq!(kind, "complete");
+        assert_eq!(parse(message)["data"]["cdn_url"], "https://cdn.example/mix.mp3");
+
+        session.status = "error".to_string();
+        session.error_message = Some("orchestrator timed out".to_string());
+        let (kind, message) = polled_message(&session);
+        assert_eq!(kind, "error");
+        assert_eq!(parse(message)["data"]["error"], "orchestrator timed out");
+
+        session.status = "cancelled".to_string();
+        assert_eq!(polled_message(&session).0, "error");
+    }
+
+    #[test]
+    fn extracts_progress_log_fields() {
+        let progress = progress_log_fields(
+            "mix:abc:progress",
+            r#"{"stage": "searching", "progress": 25, "detail": "Querying Spotify"}"#,
+        );
+        assert_eq!(progress, Some(("searching".into(), Some(25), Some("Querying Spotify".into()))));
+
+        assert_eq!(progress_log_fields("mix:abc:complete", "{}"), Some(("complete".into(), Some(100), None)));
+        assert_eq!(
+            progress_log_fields("mix:abc:error", r#"{"error": "boom"}"#),
+            Some(("error".into(), None, Some("boom".into())))
+        );
+        assert_eq!(progress_log_fields("mix:abc:other", "{}"), None);
+    }
+
+    #[test]
+    fn reconnect_backoff_doubles_up_to_a_cap() {
+        let delays: Vec<u64> = (1..=8).map(|a| reconnect_backoff(a).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30, 30]);
+    }
+
+    #[test]
+    fn classifies_channels() {
+        assert_eq!(message_type_for_channel("mix:abc:progress"), Some("progress"));
+        assert_eq!(message_type_for_channel("mix:abc:complete"), Some("complete"));
+        assert_eq!(message_type_for_channel("mix:abc:error"), Some("error"));
+        assert_eq!(message_type_for_channel("mix:abc:other"), None);
+    }
+}
@@ -0,0 +1,64 @@
+// Redis key and channel names, namespaced per environment
+use once_cell::sync::Lazy;
+
+use crate::secrets::SECRET_MANAGER;
+
+/// `REDIS_PREFIX` without trailing colons; empty keeps the historical names
+static PREFIX: Lazy<String> = Lazy::new(|| SECRET_MANAGER.get("REDIS_PREFIX").trim_end_matches(':').to_string());
+
+fn with_prefix(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}:{}", prefix, name)
+    }
+}
+
+fn without_prefix<'a>(prefix: &str, name: &'a str) -> &'a str {
+    if prefix.is_empty() {
+        return name;
+    }
+    name.strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .unwrap_or(name)
+}
+
+/// `name` under the configured prefix, e.g. `staging:spotify:genre_seeds`
+pub fn key(name: &str) -> String {
+    with_prefix(&PREFIX, name)
+}
+
+/// A session's pub/sub channel, `{prefix}:mix:{id}:{kind}`. Pass `"*"` as
+/// the session id for a pattern matching every session.
+pub fn mix_channel(session_id: &str, kind: &str) -> String {
+    key(&format!("mix:{}:{}", session_id, kind))
+}
+
+/// A received channel name with the prefix removed, e.g. `mix:{id}:progress`
+pub fn unprefixed(name: &str) -> &str {
+    without_prefix(&PREFIX, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_prefix_keeps_names() {
+        assert_eq!(with_prefix("", "mix:abc:progress"), "mix:abc:progress");
+        assert_eq!(without_prefix("", "mix:abc:progress"), "mix:abc:progress");
+    }
+
+    #[test]
+    fn prefix_round_trips() {
+        let name = with_prefix("staging", "mix:abc:progress");
+        assert_eq!(name, "staging:mix:abc:progress");
+        assert_eq!(without_prefix("staging", &name), "mix:abc:progress");
+    }
+
+    #[test]
+    fn other_prefixes_are_left_alone() {
+        assert_eq!(without_prefix("prod", "staging:mix:abc:progress"), "staging:mix:abc:progress");
+        assert_eq!(without_prefix("stag", "staging:mix:abc:progress"), "staging:mix:abc:progress");
+    }
+}
@@ -0,0 +1,80 @@
+// Shared Redis connection pool
+use deadpool_redis::{Config, Connection, Pool, PoolConfig, PoolError, Runtime, Timeouts};
+use once_cell::sync::Lazy;
+use redis::{ErrorKind, RedisError, RedisResult};
+use std::time::Duration;
+use tracing::error;
+
+use crate::secrets::SECRET_MANAGER;
+
+/// Longest a caller waits for a free connection, or for a new one to connect
+const POOL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A connection borrowed from [`RedisPool`], returned when dropped
+pub type PooledConnection = Connection;
+
+/// Pooled Redis connections for commands. Pub/sub needs a connection of its
+/// own per subscriber, so subscriptions go through [`pubsub`] instead.
+#[derive(Clone)]
+pub struct RedisPool {
+    /// `None` when `REDIS_URL` couldn't be parsed; every checkout then fails
+    pool: Option<Pool>,
+}
+
+impl RedisPool {
+    /// A pool for `REDIS_URL` holding up to `REDIS_POOL_SIZE` connections
+    fn from_secrets() -> Self {
+        let max_size = SECRET_MANAGER.get("REDIS_POOL_SIZE").parse::<usize>().unwrap_or(16).max(1);
+        let mut config = Config::from_url(SECRET_MANAGER.get("REDIS_URL"));
+        config.pool = Some(PoolConfig {
+            max_size,
+            timeouts: Timeouts {
+                wait: Some(POOL_TIMEOUT),
+                create: Some(POOL_TIMEOUT),
+                recycle: Some(POOL_TIMEOUT),
+            },
+            ..PoolConfig::default()
+        });
+
+        match config.create_pool(Some(Runtime::Tokio1)) {
+            Ok(pool) => Self { pool: Some(pool) },
+            Err(e) => {
+                error!("❌ Failed to create Redis pool: {}", e);
+                Self { pool: None }
+            }
+        }
+    }
+
+    /// Borrow a connection, connecting a new one if none is idle
+    pub async fn get(&self) -> RedisResult<PooledConnection> {
+        let Some(pool) = self.pool.as_ref() else {
+            return Err(RedisError::from((ErrorKind::IoError, "Redis pool unavailable")));
+        };
+        pool.get().await.map_err(|e| match e {
+            PoolError::Backend(e) => e,
+            other => RedisError::from((ErrorKind::IoError, "Redis pool unavailable", other.to_string())),
+        })
+    }
+
+    /// Connections currently open and how many of them are idle
+    pub fn status(&self) -> (usize, usize) {
+        self.pool
+            .as_ref()
+            .map(|pool| {
+                let status = pool.status();
+                (status.size, status.available)
+            })
+            .unwrap_or((0, 0))
+    }
+}
+
+/// The process-wide pool and the only one: routes, background tasks and the
+/// cache all borrow from it. Connections open on demand, up to `REDIS_POOL_SIZE`.
+pub static REDIS_POOL: Lazy<RedisPool> = Lazy::new(RedisPool::from_secrets);
+
+/// A dedicated pub/sub connection; these can't be shared or pooled
+pub async fn pubsub() -> RedisResult<redis::aio::PubSub> {
+    redis::Client::open(SECRET_MANAGER.get("REDIS_URL").as_str())?
+        .get_async_pubsub()
+        .await
+}
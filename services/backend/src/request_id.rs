@@ -0,0 +1,116 @@
+// Request id middleware
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderName},
+    middleware::Next,
+    response::Response,
+};
+use tower_http::request_id::RequestId;
+
+/// Header used to correlate a request across the backend and the orchestrator
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Read the request id assigned by `SetRequestIdLayer` (or sent by the client)
+pub fn request_id_from_headers(headers: &axum::http::HeaderMap) -> Option<String> {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+/// Build the tracing span for an incoming request, tagged with its request id.
+/// Only the path is logged: query strings can carry credentials (`?token=`).
+pub fn make_request_span(request: &Request<Body>) -> tracing::Span {
+    let request_id = request_id_from_headers(request.headers()).unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri().path(),
+        request_id = %request_id,
+    )
+}
+
+/// Error bodies up to this size get the request id added; larger or
+/// streamed ones (e.g. relayed orchestrator responses) pass through untouched
+const REWRITE_LIMIT_BYTES: u64 = 64 * 1024;
+
+/// The body's size, from `Content-Length` or a body that knows its length
+fn known_length(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .or_else(|| response.body().size_hint().exact())
+}
+
+/// Add the request id to JSON error bodies so users can quote it in bug reports
+pub async fn attach_request_id(request: Request, next: Next) -> Response {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .map(|s| s.to_string());
+
+    let response = next.run(request).await;
+
+    let Some(request_id) = request_id else {
+        return response;
+    };
+
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+
+    let small = known_length(&response).is_some_and(|len| len <= REWRITE_LIMIT_BYTES);
+    if !(status.is_client_error() || status.is_server_error()) || !is_json || !small {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, REWRITE_LIMIT_BYTES as usize).await {
+        Ok(b) => b,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+
+    match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut map)) => {
+            // `ApiError` bodies carry the id inside the envelope; anything
+            // else (e.g. relayed upstream errors) gets it at the top level
+            let target = match map.get_mut("error") {
+                Some(serde_json::Value::Object(error)) => error,
+                _ => &mut map,
+            };
+            target.insert("request_id".to_string(), serde_json::Value::String(request_id));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            let body = serde_json::to_vec(&map).unwrap_or_else(|_| bytes.to_vec());
+            Response::from_parts(parts, Body::from(body))
+        }
+        _ => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+
+    #[test]
+    fn only_sized_bodies_have_a_known_length() {
+        let json = axum::Json(serde_json::json!({"error": "nope"})).into_response();
+        assert_eq!(known_length(&json), Some(16));
+
+        let chunks = futures_util::stream::iter([Ok::<_, std::io::Error>(vec![0u8; 8])]);
+        let mut streamed = Response::new(Body::from_stream(chunks));
+        assert_eq!(known_length(&streamed), None);
+
+        // A relayed upstream length counts even though the body is a stream
+        streamed.headers_mut().insert(header::CONTENT_LENGTH, "1048576".parse().unwrap());
+        assert_eq!(known_length(&streamed), Some(1_048_576));
+    }
+}
@@ -0,0 +1,147 @@
+// Retry-with-jitter helper for upstream HTTP calls
+use rand::Rng;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Full jitter: a random delay between zero and the exponential backoff cap
+    fn delay_for(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        let cap = exp.min(self.max_delay).as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=cap))
+    }
+}
+
+/// Transient statuses worth retrying. 403s (e.g. YouTube `quotaExceeded`) are
+/// permanent until the quota resets, so they are returned to the caller as-is.
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Send a request, retrying connection errors and transient statuses.
+///
+/// `build` is called once per attempt since a `RequestBuilder` can't be reused.
+pub async fn send_with_retry<F>(policy: RetryPolicy, build: F) -> Result<Response, reqwest::Error>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let last_attempt = attempt >= policy.max_attempts;
+
+        match build().send().await {
+            Ok(response) if is_retryable(response.status()) && !last_attempt => {
+                warn!(
+                    "Upstream returned {} (attempt {}/{}), retrying",
+                    response.status(),
+                    attempt,
+                    policy.max_attempts
+                );
+            }
+            Ok(response) => return Ok(response),
+            Err(e) if (e.is_connect() || e.is_timeout()) && !last_attempt => {
+                warn!(
+                    "Upstream request failed (attempt {}/{}): {}, retrying",
+                    attempt, policy.max_attempts, e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+
+        tokio::time::sleep(policy.delay_for(attempt)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode as AxumStatus, routing::get, Router};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Serve `responses` in order (repeating the last one) and count the hits
+    async fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, Arc<AtomicU32>) {
+        let hits = Arc::new(AtomicU32::new(0));
+        let counter = hits.clone();
+
+        let app = Router::new().route(
+            "/search",
+            get(move || {
+                let counter = counter.clone();
+                let responses = responses.clone();
+                async move {
+                    let n = counter.fetch_add(1, Ordering::SeqCst) as usize;
+                    let (status, body) = responses[n.min(responses.len() - 1)];
+                    (AxumStatus::from_u16(status).unwrap(), body)
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{}/search", addr), hits)
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors_until_success() {
+        let (url, hits) = mock_server(vec![(503, "busy"), (503, "busy"), (200, "ok")]).await;
+        let client = reqwest::Client::new();
+
+        let response = send_with_retry(fast_policy(), || client.get(&url)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, hits) = mock_server(vec![(503, "busy")]).await;
+        let client = reqwest::Client::new();
+
+        let response = send_with_retry(fast_policy(), || client.get(&url)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_quota_exceeded() {
+        let body = r#"{"error":{"code":403,"errors":[{"reason":"quotaExceeded"}]}}"#;
+        let (url, hits) = mock_server(vec![(403, body), (200, "ok")]).await;
+        let client = reqwest::Client::new();
+
+        let response = send_with_retry(fast_policy(), || client.get(&url)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+}
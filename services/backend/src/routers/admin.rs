@@ -0,0 +1,10 @@
+// Admin routes
+use axum::{routing::post, Router};
+use crate::state::AppState;
+
+use crate::controllers::admin::reload_secrets_route;
+
+pub fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/secrets/reload", post(reload_secrets_route))
+}
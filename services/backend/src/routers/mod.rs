@@ -1,4 +1,10 @@
+pub mod admin;
 pub mod root;
+pub mod session;
+pub mod song;
 pub mod spotify;
-pub use root::{health_check_route, root_route};
+pub use admin::admin_routes;
+pub use root::{health_check_route, liveness_route, metrics_route, root_route};
+pub use session::session_routes;
+pub use song::song_routes;
 pub use spotify::spotify_routes;
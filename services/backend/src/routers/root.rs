@@ -1,11 +1,24 @@
 use axum::extract::State;
+use axum::Json;
 use crate::controllers::RootController;
 use crate::db::Database;
 
 pub async fn root_route(State(_database): State<Database>) -> impl axum::response::IntoResponse {
-    RootController::root().await
+    Json(RootController::root().await)
 }
 
-pub async fn health_check_route(State(_database): State<Database>) -> impl axum::response::IntoResponse {
-    RootController::health_check().await
-}
\ No newline at end of file
+pub async fn health_check_route(State(database): State<Database>) -> impl axum::response::IntoResponse {
+    let (status, body) = RootController::health_check(&database).await;
+    (status, Json(body))
+}
+
+pub async fn liveness_route() -> impl axum::response::IntoResponse {
+    RootController::liveness().await
+}
+
+pub async fn metrics_route() -> impl axum::response::IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        RootController::metrics(),
+    )
+}
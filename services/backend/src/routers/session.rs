@@ -0,0 +1,10 @@
+// Session routes
+use axum::{routing::get, Router};
+use crate::state::AppState;
+
+use crate::controllers::session::{get_session_profile_route, save_session_profile_route};
+
+pub fn session_routes() -> Router<AppState> {
+    Router::new()
+        .route("/{id}/profile", get(get_session_profile_route).post(save_session_profile_route))
+}
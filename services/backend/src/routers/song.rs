@@ -0,0 +1,14 @@
+// Song routes
+use axum::{routing::{get, post}, Router};
+use crate::state::AppState;
+
+use crate::controllers::song::{song_formats_route, song_info_match_route, song_info_route, song_metadata_route, song_refresh_stream_route, song_search_route};
+
+pub fn song_routes() -> Router<AppState> {
+    Router::new()
+        .route("/info", get(song_info_route).post(song_info_match_route))
+        .route("/search", get(song_search_route))
+        .route("/formats", get(song_formats_route))
+        .route("/metadata", get(song_metadata_route))
+        .route("/refresh-stream", post(song_refresh_stream_route))
+}
@@ -1,22 +1,43 @@
 // Spotify routes
-use axum::{routing::get, Router};
-use crate::db::Database;
+use axum::{routing::{get, post, put}, Router};
+use crate::state::AppState;
 
 use crate::controllers::spotify::{
     spotify_auth_route, spotify_callback_route, spotify_refresh_route,
     spotify_token_route, spotify_auto_auth_route, spotify_me_route, spotify_search_route, 
-    spotify_audio_features_route, spotify_recommendations_route,
+    spotify_audio_features_route, spotify_recommendations_route, spotify_genres_route,
+    spotify_artist_route, spotify_related_artists_route, spotify_search_normalized_route,
+    spotify_saved_tracks_route, spotify_track_to_youtube_route, spotify_enrich_route,
+    spotify_profile_recommendations_route, spotify_artwork_route, spotify_preview_route,
+    spotify_devices_route, spotify_player_route, spotify_new_releases_route,
+    spotify_featured_playlists_route, spotify_validate_session_route, spotify_audio_profile_route,
 };
 
-pub fn spotify_routes() -> Router<Database> {
+pub fn spotify_routes() -> Router<AppState> {
     Router::new()
         .route("/auth", get(spotify_auth_route))
         .route("/callback", get(spotify_callback_route))
         .route("/refresh", get(spotify_refresh_route))
         .route("/token", get(spotify_token_route))
+        .route("/session/validate", get(spotify_validate_session_route))
         .route("/auto-auth", get(spotify_auto_auth_route))
         .route("/me", get(spotify_me_route))
         .route("/search", get(spotify_search_route))
+        .route("/search/normalized", get(spotify_search_normalized_route))
+        .route("/saved-tracks", get(spotify_saved_tracks_route))
+        .route("/track-to-youtube", get(spotify_track_to_youtube_route))
         .route("/audio-features", get(spotify_audio_features_route))
+        .route("/enrich", post(spotify_enrich_route))
+        .route("/audio-profile", post(spotify_audio_profile_route))
         .route("/recommendations", get(spotify_recommendations_route))
+        .route("/recommendations/seeded-from-profile", get(spotify_profile_recommendations_route))
+        .route("/genres", get(spotify_genres_route))
+        .route("/new-releases", get(spotify_new_releases_route))
+        .route("/featured-playlists", get(spotify_featured_playlists_route))
+        .route("/track/{id}/artwork", get(spotify_artwork_route))
+        .route("/track/{id}/preview", get(spotify_preview_route))
+        .route("/devices", get(spotify_devices_route))
+        .route("/player", put(spotify_player_route))
+        .route("/artist/{id}", get(spotify_artist_route))
+        .route("/artist/{id}/related", get(spotify_related_artists_route))
 }
@@ -2,26 +2,46 @@
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::env;
-use tracing::info;
-pub static SECRET_MANAGER: Lazy<SecretManager> = Lazy::new(|| SecretManager::new());
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tracing::{debug, info};
+pub static SECRET_MANAGER: Lazy<SecretManager> = Lazy::new(SecretManager::new);
 
-enum MODE {
-    DEV,
-    PROD,
+enum Mode {
+    Dev,
+    Prod,
 }
 
 pub struct SecretManager {
-    secrets: HashMap<String, String>,
+    secrets: RwLock<HashMap<String, String>>,
+    /// `KEY=VALUE` file read on top of the environment, `SECRETS_FILE` or `.env`
+    env_file: PathBuf,
 }
 impl SecretManager {
     fn new() -> Self {
+        Self::with_env_file(env::var("SECRETS_FILE").unwrap_or(".env".to_string()))
+    }
+
+    fn with_env_file(env_file: impl Into<PathBuf>) -> Self {
+        let env_file = env_file.into();
+        SecretManager {
+            secrets: RwLock::new(Self::load(&read_env_file(&env_file), None)),
+            env_file,
+        }
+    }
+
+    /// Read secrets from `file` (the env file's entries), falling back to the
+    /// process environment. `previous_jwt_secret` keeps the generated dev JWT
+    /// secret stable across reloads so tokens stay valid.
+    fn load(file: &HashMap<String, String>, previous_jwt_secret: Option<String>) -> HashMap<String, String> {
+        let var = |key: &str| file.get(key).cloned().ok_or(env::VarError::NotPresent).or_else(|_| env::var(key));
         let mut secrets: HashMap<String, String> = HashMap::new();
-        let mode = match env::var("MODE") {
-            Ok(mode) if mode.to_lowercase() == "prod" => MODE::PROD,
-            _ => MODE::DEV,
+        let mode = match var("MODE") {
+            Ok(mode) if mode.to_lowercase() == "prod" => Mode::Prod,
+            _ => Mode::Dev,
         };
         match mode {
-            MODE::DEV => {
+            Mode::Dev => {
                 secrets.insert(
                     "DB_URI".to_string(),
                     "postgresql://:@postgres:5432/".to_string(),
@@ -36,75 +56,254 @@ impl SecretManager {
                     "http://localhost:8000".to_string(),
                 );
                 secrets.insert("BACKEND_DOMAIN".to_string(), "localhost".to_string());
+                secrets.insert(
+                    "LOG_LEVEL".to_string(),
+                    var("LOG_LEVEL").unwrap_or("debug".to_string()),
+                );
             }
-            MODE::PROD => {
-                secrets.insert("DB_URI".to_string(), env::var("DB_URI").unwrap_or_default());
-                secrets.insert("PORT".to_string(), env::var("PORT").unwrap_or_default());
+            Mode::Prod => {
+                secrets.insert("DB_URI".to_string(), var("DB_URI").unwrap_or_default());
+                secrets.insert("PORT".to_string(), var("PORT").unwrap_or_default());
                 secrets.insert(
                     "FRONTEND_URL".to_string(),
-                    env::var("FRONTEND_URL").unwrap_or_default(),
+                    var("FRONTEND_URL").unwrap_or_default(),
                 );
                 secrets.insert(
                     "BACKEND_URL".to_string(),
-                    env::var("BACKEND_URL").unwrap_or_default(),
+                    var("BACKEND_URL").unwrap_or_default(),
+                );
+                secrets.insert(
+                    "LOG_LEVEL".to_string(),
+                    var("LOG_LEVEL").unwrap_or("info".to_string()),
                 );
             }
         }
         
         // JWT secret MUST come from env in production
-        let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| {
-            if matches!(mode, MODE::PROD) {
+        let jwt_secret = var("JWT_SECRET").unwrap_or_else(|_| {
+            if matches!(mode, Mode::Prod) {
                 panic!("JWT_SECRET must be set in production mode!");
             }
             // Only use default in dev mode - generate random for dev
-            uuid::Uuid::new_v4().to_string()
+            previous_jwt_secret.unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
         });
         secrets.insert("JWT_SECRET".to_string(), jwt_secret);
         secrets.insert(
             "GOOGLE_CLIENT_ID".to_string(),
-            env::var("GOOGLE_CLIENT_ID").unwrap_or_default(),
+            var("GOOGLE_CLIENT_ID").unwrap_or_default(),
         );
         secrets.insert(
             "GOOGLE_CLIENT_SECRET".to_string(),
-            env::var("GOOGLE_CLIENT_SECRET").unwrap_or_default(),
+            var("GOOGLE_CLIENT_SECRET").unwrap_or_default(),
         );
         secrets.insert(
             "GOOGLE_REDIRECT_URL".to_string(),
-            env::var("GOOGLE_REDIRECT_URL").unwrap_or_default(),
+            var("GOOGLE_REDIRECT_URL").unwrap_or_default(),
         );
+        // YouTube endpoints; overridable to point at a proxy or a fake YouTube
         secrets.insert(
             "YOUTUBE_API_URL".to_string(),
-            "https://www.googleapis.com/youtube/v3/search".to_string(),
+            var("YOUTUBE_API_URL").unwrap_or("https://www.googleapis.com/youtube/v3/search".to_string()),
+        );
+        secrets.insert(
+            "YOUTUBE_VIDEOS_API_URL".to_string(),
+            var("YOUTUBE_VIDEOS_API_URL").unwrap_or("https://www.googleapis.com/youtube/v3/videos".to_string()),
+        );
+        secrets.insert(
+            "YOUTUBE_WATCH_URL".to_string(),
+            var("YOUTUBE_WATCH_URL").unwrap_or("https://www.youtube.com/watch".to_string()),
         );
         secrets.insert(
             "YOUTUBE_API_KEY".to_string(),
-            env::var("YOUTUBE_API_KEY").unwrap_or_default(),
+            var("YOUTUBE_API_KEY").unwrap_or_default(),
+        );
+        // Comma-separated keys rotated on quota exhaustion; falls back to the single key
+        secrets.insert(
+            "YOUTUBE_API_KEYS".to_string(),
+            var("YOUTUBE_API_KEYS").unwrap_or_else(|_| var("YOUTUBE_API_KEY").unwrap_or_default()),
+        );
+        
+        // Upper bound on a single yt-dlp extraction
+        secrets.insert(
+            "YTDLP_TIMEOUT_SECS".to_string(),
+            var("YTDLP_TIMEOUT_SECS").unwrap_or("30".to_string()),
+        );
+        // In-process YouTube search cache: entries kept (0 disables) and their lifetime
+        secrets.insert(
+            "YOUTUBE_SEARCH_LRU_CAPACITY".to_string(),
+            var("YOUTUBE_SEARCH_LRU_CAPACITY").unwrap_or("1000".to_string()),
+        );
+        secrets.insert(
+            "YOUTUBE_SEARCH_LRU_TTL_SECS".to_string(),
+            var("YOUTUBE_SEARCH_LRU_TTL_SECS")
+                .unwrap_or(crate::controllers::song::SEARCH_CACHE_TTL_SECS.to_string()),
+        );
+        // For installs outside PATH, and extra flags such as `--cookies` or
+        // `--proxy` (split shell-style, never run through a shell)
+        secrets.insert(
+            "YTDLP_PATH".to_string(),
+            var("YTDLP_PATH").unwrap_or("yt-dlp".to_string()),
+        );
+        secrets.insert(
+            "YTDLP_EXTRA_ARGS".to_string(),
+            var("YTDLP_EXTRA_ARGS").unwrap_or_default(),
         );
         
         // Spotify OAuth
         secrets.insert(
             "SPOTIFY_CLIENT_ID".to_string(),
-            env::var("SPOTIFY_CLIENT_ID").unwrap_or_default(),
+            var("SPOTIFY_CLIENT_ID").unwrap_or_default(),
         );
         secrets.insert(
             "SPOTIFY_CLIENT_SECRET".to_string(),
-            env::var("SPOTIFY_CLIENT_SECRET").unwrap_or_default(),
+            var("SPOTIFY_CLIENT_SECRET").unwrap_or_default(),
         );
         secrets.insert(
             "SPOTIFY_REDIRECT_URI".to_string(),
-            env::var("SPOTIFY_REDIRECT_URI").unwrap_or("http://localhost:8000/spotify/callback".to_string()),
+            var("SPOTIFY_REDIRECT_URI").unwrap_or("http://localhost:8000/spotify/callback".to_string()),
+        );
+        // Spotify's accounts service and Web API; overridable to point at a
+        // proxy or a fake Spotify
+        secrets.insert(
+            "SPOTIFY_ACCOUNTS_URL".to_string(),
+            var("SPOTIFY_ACCOUNTS_URL").unwrap_or("https://accounts.spotify.com".to_string()),
+        );
+        secrets.insert(
+            "SPOTIFY_API_URL".to_string(),
+            var("SPOTIFY_API_URL").unwrap_or("https://api.spotify.com/v1".to_string()),
+        );
+        // Space-separated scopes the OAuth flow asks for; trim this for
+        // deployments that don't need playback or library access
+        secrets.insert(
+            "SPOTIFY_SCOPES".to_string(),
+            var("SPOTIFY_SCOPES").unwrap_or(crate::controllers::spotify::DEFAULT_SPOTIFY_SCOPES.to_string()),
+        );
+        
+        // Postgres pool sizing
+        secrets.insert(
+            "DB_MAX_CONNECTIONS".to_string(),
+            var("DB_MAX_CONNECTIONS").unwrap_or("20".to_string()),
+        );
+        secrets.insert(
+            "DB_ACQUIRE_TIMEOUT_SECS".to_string(),
+            var("DB_ACQUIRE_TIMEOUT_SECS").unwrap_or("5".to_string()),
+        );
+        secrets.insert(
+            "DB_IDLE_TIMEOUT_SECS".to_string(),
+            var("DB_IDLE_TIMEOUT_SECS").unwrap_or("600".to_string()),
         );
         
         // Redis
         secrets.insert(
             "REDIS_URL".to_string(),
-            env::var("REDIS_URL").unwrap_or("redis://localhost:6379".to_string()),
+            var("REDIS_URL").unwrap_or("redis://localhost:6379".to_string()),
+        );
+        // Connections kept in the shared Redis pool
+        secrets.insert(
+            "REDIS_POOL_SIZE".to_string(),
+            var("REDIS_POOL_SIZE").unwrap_or("16".to_string()),
+        );
+        // Namespace for every key and channel, for environments sharing a Redis
+        secrets.insert(
+            "REDIS_PREFIX".to_string(),
+            var("REDIS_PREFIX").unwrap_or_default(),
         );
         
         // AI Orchestrator
         secrets.insert(
             "ORCHESTRATOR_URL".to_string(),
-            env::var("ORCHESTRATOR_URL").unwrap_or("http://localhost:8002".to_string()),
+            var("ORCHESTRATOR_URL").unwrap_or("http://localhost:8002".to_string()),
+        );
+        
+        // Consecutive orchestrator failures before the circuit opens, and how
+        // long it stays open before a probe request is let through
+        secrets.insert(
+            "ORCHESTRATOR_BREAKER_THRESHOLD".to_string(),
+            var("ORCHESTRATOR_BREAKER_THRESHOLD").unwrap_or("5".to_string()),
+        );
+        secrets.insert(
+            "ORCHESTRATOR_BREAKER_COOLDOWN_SECS".to_string(),
+            var("ORCHESTRATOR_BREAKER_COOLDOWN_SECS").unwrap_or("30".to_string()),
+        );
+        
+        // Set to "false" when the deployment's proxy cannot upgrade WebSockets
+        secrets.insert(
+            "WEBSOCKET_ENABLED".to_string(),
+            var("WEBSOCKET_ENABLED").unwrap_or("true".to_string()),
+        );
+        
+        // Comma-separated origins (e.g. `https://app.example.com`) allowed to
+        // open progress WebSockets and SSE streams; `*` allows any origin.
+        // Defaults to the frontend's own origin.
+        let frontend_url = secrets.get("FRONTEND_URL").cloned().unwrap_or_default();
+        secrets.insert(
+            "STREAM_ALLOWED_ORIGINS".to_string(),
+            var("STREAM_ALLOWED_ORIGINS").unwrap_or(frontend_url),
+        );
+        
+        // WebSocket progress coalescing interval
+        secrets.insert(
+            "WS_PROGRESS_FLUSH_MS".to_string(),
+            var("WS_PROGRESS_FLUSH_MS").unwrap_or("200".to_string()),
+        );
+        
+        // Longest mix prompt accepted, in characters
+        secrets.insert(
+            "MIX_PROMPT_MAX_CHARS".to_string(),
+            var("MIX_PROMPT_MAX_CHARS").unwrap_or("2000".to_string()),
+        );
+        
+        // Longest mix a single request may ask for, to cap LLM/compute cost
+        secrets.insert(
+            "MIX_MAX_DURATION_MINUTES".to_string(),
+            var("MIX_MAX_DURATION_MINUTES").unwrap_or("180".to_string()),
+        );
+        
+        // Mix generations each instance runs at once (not shared between
+        // replicas; the old unsuffixed name is still read), and how long one
+        // may go without a `complete`/`error` message before its slot is
+        // freed and the session is swept to `error`
+        secrets.insert(
+            "MIX_MAX_CONCURRENT_GENERATIONS_PER_INSTANCE".to_string(),
+            var("MIX_MAX_CONCURRENT_GENERATIONS_PER_INSTANCE")
+                .or_else(|_| var("MIX_MAX_CONCURRENT_GENERATIONS"))
+                .unwrap_or("10".to_string()),
+        );
+        secrets.insert(
+            "MIX_GENERATION_TIMEOUT_SECS".to_string(),
+            var("MIX_GENERATION_TIMEOUT_SECS").unwrap_or("900".to_string()),
+        );
+        
+        // Comma-separated hosts mix webhooks may be sent to (subdomains
+        // included); empty allows any public host
+        secrets.insert(
+            "WEBHOOK_ALLOWED_HOSTS".to_string(),
+            var("WEBHOOK_ALLOWED_HOSTS").unwrap_or_default(),
+        );
+        
+        // How long an identical prompt from the same user maps back to the
+        // existing session when the client asks for deduplication
+        secrets.insert(
+            "MIX_DEDUPE_WINDOW_SECS".to_string(),
+            var("MIX_DEDUPE_WINDOW_SECS").unwrap_or("300".to_string()),
+        );
+        
+        // Interval between SSE keep-alive comments; some CDNs need <= 15s
+        secrets.insert(
+            "SSE_KEEPALIVE_SECS".to_string(),
+            var("SSE_KEEPALIVE_SECS").unwrap_or("15".to_string()),
+        );
+
+        // How often progress streams poll Postgres while Redis pub/sub is down
+        secrets.insert(
+            "PROGRESS_POLL_INTERVAL_SECS".to_string(),
+            var("PROGRESS_POLL_INTERVAL_SECS").unwrap_or("3".to_string()),
+        );
+        
+        // Token guarding /admin endpoints; admin routes are disabled when empty
+        secrets.insert(
+            "ADMIN_TOKEN".to_string(),
+            var("ADMIN_TOKEN").unwrap_or_default(),
         );
         
         // Log which secrets are configured (NOT their values!)
@@ -115,10 +314,129 @@ impl SecretManager {
             .collect();
         info!("Secrets configured: {:?}", configured);
         
-        SecretManager { secrets }
+        secrets
     }
 
+    /// Re-read secrets without restarting the process. The process
+    /// environment can't change under us, so edits are picked up from the
+    /// env file. Values captured at startup (DB pool sizing, PORT) keep their
+    /// old values.
+    pub fn reload(&self) {
+        let previous_jwt_secret = Some(self.get("JWT_SECRET")).filter(|s| !s.is_empty());
+        let secrets = Self::load(&read_env_file(&self.env_file), previous_jwt_secret);
+
+        let mut current = self.secrets.write().unwrap_or_else(|e| e.into_inner());
+        *current = secrets;
+        info!("Secrets reloaded");
+    }
+
+    /// Returns an owned copy so callers never hold the lock
     pub fn get(&self, key: &str) -> String {
-        self.secrets.get(key).cloned().unwrap_or_default()
+        let secrets = self.secrets.read().unwrap_or_else(|e| e.into_inner());
+        secrets.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Get a comma-separated secret as a list, skipping empty entries
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        self.get(key)
+            .split(',')
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect()
+    }
+
+    /// Get a secret holding an http(s) base URL, without a trailing slash.
+    /// `None` when it's empty or doesn't parse.
+    pub fn get_url(&self, key: &str) -> Option<String> {
+        let value = self.get(key);
+        let url = reqwest::Url::parse(value.trim()).ok()?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        Some(value.trim().trim_end_matches('/').to_string())
+    }
+}
+
+/// Entries of a dotenv-style file: `KEY=VALUE` lines, optionally prefixed
+/// with `export` and with the value quoted. Blank lines and `#` comments are
+/// skipped. A missing file is empty.
+fn read_env_file(path: &Path) -> HashMap<String, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            debug!("Not reading secrets from {}: {}", path.display(), e);
+            return HashMap::new();
+        }
+    };
+    parse_env_file(&contents)
+}
+
+fn parse_env_file(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_env_files() {
+        let parsed = parse_env_file("# comment\n\nA=1\nexport B = \"two words\"\nC='x=y'\nnot a pair\n");
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed["A"], "1");
+        assert_eq!(parsed["B"], "two words");
+        assert_eq!(parsed["C"], "x=y");
+    }
+
+    #[test]
+    fn reload_picks_up_a_changed_env_file() {
+        let path = env::temp_dir().join(format!("secrets-{}.env", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "MIX_MAX_DURATION_MINUTES=10\n").unwrap();
+        let manager = SecretManager::with_env_file(&path);
+        assert_eq!(manager.get("MIX_MAX_DURATION_MINUTES"), "10");
+        let jwt_secret = manager.get("JWT_SECRET");
+
+        std::fs::write(&path, "MIX_MAX_DURATION_MINUTES=25\n").unwrap();
+        manager.reload();
+        assert_eq!(manager.get("MIX_MAX_DURATION_MINUTES"), "25");
+        assert_eq!(manager.get("JWT_SECRET"), jwt_secret);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn only_http_urls_are_usable() {
+        let manager = SecretManager {
+            secrets: RwLock::new(HashMap::from(
+                [
+                    ("LOCAL", " http://localhost:8001/ "),
+                    ("HOSTED", "https://orchestrator.example.com/api"),
+                    ("EMPTY", ""),
+                    ("BARE", "localhost:8001"),
+                    ("OTHER_SCHEME", "ftp://example.com"),
+                ]
+                .map(|(k, v)| (k.to_string(), v.to_string())),
+            )),
+            env_file: PathBuf::new(),
+        };
+        assert_eq!(manager.get_url("LOCAL").as_deref(), Some("http://localhost:8001"));
+        assert_eq!(manager.get_url("HOSTED").as_deref(), Some("https://orchestrator.example.com/api"));
+        for key in ["EMPTY", "BARE", "OTHER_SCHEME", "MISSING"] {
+            assert_eq!(manager.get_url(key), None, "{}", key);
+        }
     }
 }
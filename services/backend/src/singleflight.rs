@@ -0,0 +1,151 @@
+// Coalesce concurrent identical work onto a single execution
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Runs at most one piece of work per key at a time. Callers arriving while
+/// it's in flight wait for it and share its result. Nothing is kept once it
+/// finishes, so this is not a cache.
+///
+/// If the caller doing the work is cancelled, one of the waiters picks it up.
+pub struct SingleFlight<T> {
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    /// `work`'s result, or that of an identical call already in flight for `key`
+    pub async fn run<F, Fut>(&self, key: &str, work: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        let slot = Slot {
+            in_flight: &self.in_flight,
+            key,
+            cell: Some(cell),
+        };
+
+        slot.cell.as_ref().expect("cell is set until drop").get_or_init(work).await.clone()
+    }
+
+    /// Keys with work currently in flight
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+/// A caller's hold on a key's slot. Dropping it, whether the call finished or
+/// was cancelled, clears the slot once the work is done or nobody else is
+/// waiting on it, so an abandoned key doesn't linger.
+struct Slot<'a, T> {
+    in_flight: &'a Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+    key: &'a str,
+    cell: Option<Arc<OnceCell<T>>>,
+}
+
+impl<T> Drop for Slot<'_, T> {
+    fn drop(&mut self) {
+        let Some(cell) = self.cell.take() else { return };
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        // Our handle is released under the lock, so the count seen here is exact:
+        // two means only the map and this caller still hold the slot
+        if in_flight.get(self.key).is_some_and(|current| Arc::ptr_eq(current, &cell))
+            && (cell.initialized() || Arc::strong_count(&cell) == 2)
+        {
+            in_flight.remove(self.key);
+        }
+        drop(cell);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn concurrent_calls_share_one_execution() {
+        let flight = SingleFlight::default();
+        let runs = AtomicUsize::new(0);
+        let work = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            "url".to_string()
+        };
+
+        let (a, b, c) = tokio::join!(flight.run("abc", work), flight.run("abc", work), flight.run("xyz", work));
+        assert_eq!((a.as_str(), b.as_str(), c.as_str()), ("url", "url", "url"));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert_eq!(flight.in_flight(), 0);
+
+        // Finished work isn't reused
+        flight.run("abc", work).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn a_waiter_takes_over_from_a_cancelled_caller() {
+        let flight = Arc::new(SingleFlight::default());
+        let leader = tokio::spawn({
+            let flight = flight.clone();
+            async move {
+                flight
+                    .run("abc", || async {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        1
+                    })
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(flight.in_flight(), 1);
+
+        let waiter = flight.run("abc", || async { 2 });
+        leader.abort();
+        assert_eq!(waiter.await, 2);
+        assert_eq!(flight.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelling_every_caller_clears_the_key() {
+        let flight = Arc::new(SingleFlight::<u32>::default());
+        let callers: Vec<_> = (0..3)
+            .map(|_| {
+                let flight = flight.clone();
+                tokio::spawn(async move {
+                    flight
+                        .run("abc", || async {
+                            tokio::time::sleep(Duration::from_secs(60)).await;
+                            1
+                        })
+                        .await
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(flight.in_flight(), 1);
+
+        for caller in callers {
+            caller.abort();
+            let _ = caller.await;
+        }
+        assert_eq!(flight.in_flight(), 0);
+    }
+}
@@ -0,0 +1,17 @@
+// Shared application state handed to every route
+use axum::extract::FromRef;
+
+use crate::db::Database;
+
+/// Redis isn't here: everything, including the cache and health checks,
+/// borrows from the one process-wide `REDIS_POOL`
+#[derive(Clone)]
+pub struct AppState {
+    pub database: Database,
+}
+
+impl FromRef<AppState> for Database {
+    fn from_ref(state: &AppState) -> Self {
+        state.database.clone()
+    }
+}
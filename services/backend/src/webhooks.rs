@@ -0,0 +1,233 @@
+// Completion webhooks for mix sessions
+use reqwest::{Client, Url};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::cache;
+use crate::db::Database;
+use crate::retry::{send_with_retry, RetryPolicy};
+use crate::secrets::SECRET_MANAGER;
+
+/// Webhooks are kept this long; a mix that hasn't finished by then won't notify
+const WEBHOOK_TTL_SECS: u64 = 24 * 60 * 60;
+
+const WEBHOOK_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 4,
+    base_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(8),
+};
+
+fn webhook_key(session_id: &Uuid) -> String {
+    format!("mix:{}:webhook", session_id)
+}
+
+/// Addresses a webhook may never reach: loopback, private, link-local,
+/// carrier-grade NAT, multicast and other non-routable ranges
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_ipv4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_ipv4(v4),
+            None => is_public_ipv6(v6),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00 // unique local
+        || (first & 0xffc0) == 0xfe80) // link-local
+}
+
+/// Hosts from `WEBHOOK_ALLOWED_HOSTS`; empty means any public host
+fn allowed_hosts() -> Vec<String> {
+    SECRET_MANAGER
+        .get("WEBHOOK_ALLOWED_HOSTS")
+        .split(',')
+        .map(|h| h.trim().to_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect()
+}
+
+/// Static checks on a webhook URL: http(s), no credentials, an allowed host,
+/// and no private IP literal. DNS is checked separately at delivery time.
+pub fn validate_webhook_url(raw: &str, allowed_hosts: &[String]) -> Result<Url, String> {
+    let url = Url::parse(raw).map_err(|_| "webhook_url is not a valid URL".to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("webhook_url must be http or https".to_string());
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err("webhook_url must not contain credentials".to_string());
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| "webhook_url must have a host".to_string())?
+        .to_lowercase();
+    let is_private_literal = host
+        .trim_matches(['[', ']'])
+        .parse::<IpAddr>()
+        .is_ok_and(|ip| !is_public_ip(ip));
+    if is_private_literal || host == "localhost" || host.ends_with(".localhost") || host.ends_with(".internal") {
+        return Err("webhook_url must not point at a private address".to_string());
+    }
+
+    if !allowed_hosts.is_empty()
+        && !allowed_hosts
+            .iter()
+            .any(|allowed| host == *allowed || host.ends_with(&format!(".{}", allowed)))
+    {
+        return Err(format!("webhook_url host '{}' is not allowed", host));
+    }
+    Ok(url)
+}
+
+/// `validate_webhook_url` with the configured allowlist
+pub fn check_webhook_url(raw: &str) -> Result<Url, String> {
+    validate_webhook_url(raw, &allowed_hosts())
+}
+
+/// Resolve the webhook host, refusing it if any address is private so DNS
+/// can't be used to smuggle a request into the internal network
+async fn resolve_public(url: &Url) -> Result<SocketAddr, String> {
+    let host = url.host_str().ok_or("missing host")?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<_> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|e| format!("DNS lookup failed: {}", e))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err("host did not resolve".to_string());
+    }
+    if let Some(addr) = addrs.iter().find(|a| !is_public_ip(a.ip())) {
+        return Err(format!("host resolves to private address {}", addr.ip()));
+    }
+    Ok(addrs[0])
+}
+
+/// A client pinned to the address that was checked, so a second DNS answer
+/// can't differ, and that doesn't follow redirects to other hosts
+fn pinned_client(url: &Url, addr: SocketAddr) -> reqwest::Result<Client> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(host) = url.host_str() {
+        builder = builder.resolve(host, addr);
+    }
+    builder.build()
+}
+
+/// Remember a session's webhook until it completes or errors
+pub async fn register(session_id: Uuid, url: &Url) {
+    cache::set_json(&webhook_key(&session_id), &url.as_str(), WEBHOOK_TTL_SECS).await;
+}
+
+/// Deliver the session's webhook, if one was registered. The URL is removed
+/// atomically first, so only one replica's recorder sends it.
+pub fn notify(database: Database, session_id: Uuid, event: &'static str, payload: String) {
+    tokio::spawn(async move {
+        let Some(raw_url) = cache::take_json::<String>(&webhook_key(&session_id)).await else {
+            return;
+        };
+        let url = match check_webhook_url(&raw_url) {
+            Ok(url) => url,
+            Err(e) => {
+                warn!("Dropping webhook for mix {}: {}", session_id, e);
+                return;
+            }
+        };
+        let client = match resolve_public(&url).await.and_then(|addr| {
+            pinned_client(&url, addr).map_err(|e| e.to_string())
+        }) {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Dropping webhook for mix {} to {}: {}", session_id, url, e);
+                return;
+            }
+        };
+
+        let prompt = match database.get_mix_session(session_id).await {
+            Ok(Some(session)) => Some(session.prompt),
+            _ => None,
+        };
+        let data = serde_json::from_str::<serde_json::Value>(&payload)
+            .unwrap_or_else(|_| serde_json::json!({"raw": payload}));
+        let body = serde_json::json!({
+            "event": format!("mix.{}", event),
+            "session_id": session_id,
+            "status": if event == "complete" { "completed" } else { "error" },
+            "prompt": prompt,
+            "cdn_url": data.get("cdn_url"),
+            "error": data.get("error"),
+            "data": data,
+        });
+
+        let result = send_with_retry(WEBHOOK_RETRY, || client.post(url.clone()).json(&body)).await;
+        match result {
+            Ok(response) if response.status().is_success() => {
+                info!("Delivered {} webhook for mix {} to {}", event, session_id, url);
+            }
+            Ok(response) => {
+                warn!("Webhook for mix {} to {} returned {}", session_id, url, response.status());
+            }
+            Err(e) => warn!("Webhook for mix {} to {} failed: {}", session_id, url, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_private_and_malformed_urls() {
+        let none: Vec<String> = Vec::new();
+        assert!(validate_webhook_url("https://hooks.example.com/mix", &none).is_ok());
+        assert!(validate_webhook_url("http://93.184.216.34/hook", &none).is_ok());
+
+        for url in [
+            "ftp://example.com/hook",
+            "https://user:pw@example.com/hook",
+            "http://localhost:8080/hook",
+            "http://127.0.0.1/hook",
+            "http://10.1.2.3/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+            "not a url",
+        ] {
+            assert!(validate_webhook_url(url, &none).is_err(), "{} should be rejected", url);
+        }
+    }
+
+    #[test]
+    fn enforces_the_allowlist() {
+        let allowed = vec!["example.com".to_string()];
+        assert!(validate_webhook_url("https://example.com/hook", &allowed).is_ok());
+        assert!(validate_webhook_url("https://hooks.example.com/hook", &allowed).is_ok());
+        assert!(validate_webhook_url("https://notexample.com/hook", &allowed).is_err());
+        assert!(validate_webhook_url("https://example.com.evil.io/hook", &allowed).is_err());
+    }
+}
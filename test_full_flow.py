@@ -52,11 +52,20 @@ async def test_mix_generation():
         print(f"Failed to start mix generation: {e}")
         return
 
+    # Progress streams need a token scoped to the session
+    try:
+        response = requests.post(f"{BACKEND_URL}/mix/{session_id}/stream-token")
+        response.raise_for_status()
+        stream_token = response.json()["token"]
+    except Exception as e:
+        print(f"Failed to get stream token: {e}")
+        return
+
     # IMMEDIATELY connect to websocket for progress updates
     uri = f"ws://localhost:8000/ws/mix/{session_id}"
 
     try:
-        websocket = await websockets.connect(uri)
+        websocket = await websockets.connect(uri, subprotocols=["bearer", stream_token])
         print("Connected to websocket for progress updates")
 
         start_time = time.time()
@@ -5,13 +5,23 @@ Simple websocket test script to verify heartbeat mechanism
 import asyncio
 import websockets
 import json
+import sys
 import time
 
-async def test_websocket():
-    uri = "ws://localhost:8000/ws/mix/test-session-123"
+import requests
+
+BACKEND_URL = "http://localhost:8000"
+
+async def test_websocket(session_id):
+    uri = f"ws://localhost:8000/ws/mix/{session_id}"
+
+    # The socket only accepts a token issued for this session
+    response = requests.post(f"{BACKEND_URL}/mix/{session_id}/stream-token")
+    response.raise_for_status()
+    stream_token = response.json()["token"]
 
     try:
-        async with websockets.connect(uri) as websocket:
+        async with websockets.connect(uri, subprotocols=["bearer", stream_token]) as websocket:
             print("Connected to websocket")
 
             # Send a test message
@@ -51,4 +61,6 @@ async def test_websocket():
         print(f"WebSocket connection failed: {e}")
 
 if __name__ == "__main__":
-    asyncio.run(test_websocket())
\ No newline at end of file
+    if len(sys.argv) != 2:
+        sys.exit(f"usage: {sys.argv[0]} <mix session id>")
+    asyncio.run(test_websocket(sys.argv[1]))
\ No newline at end of file